@@ -0,0 +1,350 @@
+use std::collections::VecDeque;
+
+use glam::{Mat3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::data::volume::Volume;
+
+/// Which neighbors of a voxel belong to the same cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// The 6 neighbors that share a face.
+    Faces,
+
+    /// The 18 neighbors that share a face or an edge.
+    Edges,
+
+    /// The 26 neighbors that share a face, an edge or a corner.
+    Corners,
+}
+
+impl Connectivity {
+    /// Returns the connectivity with the given number of neighbors, `6`, `18` or `26`.
+    ///
+    /// Arguments:
+    ///
+    /// * `neighbors`: The number of neighbors of a voxel.
+    pub fn from_neighbors(neighbors: usize) -> Option<Self> {
+        match neighbors {
+            6 => Some(Self::Faces),
+            18 => Some(Self::Edges),
+            26 => Some(Self::Corners),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of neighbors of a voxel.
+    pub fn neighbors(&self) -> usize {
+        match self {
+            Self::Faces => 6,
+            Self::Edges => 18,
+            Self::Corners => 26,
+        }
+    }
+
+    /// Returns the offsets of the neighbors of a voxel.
+    fn offsets(&self) -> Vec<[isize; 3]> {
+        // The number of axes along which a neighbor is shifted tells the shared element.
+        let max_shifted_axes = match self {
+            Self::Faces => 1,
+            Self::Edges => 2,
+            Self::Corners => 3,
+        };
+        let mut offsets = Vec::new();
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let shifted_axes = [x, y, z].iter().filter(|&&d| d != 0).count();
+                    if shifted_axes > 0 && shifted_axes <= max_shifted_axes {
+                        offsets.push([x, y, z]);
+                    }
+                }
+            }
+        }
+        offsets
+    }
+}
+
+/// A connected cluster of voxels above the threshold of a statistical map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cluster {
+    /// The rank of the cluster by size, starting at `1` for the largest one.
+    pub index: usize,
+
+    /// The number of voxels of the cluster.
+    pub voxel_count: usize,
+
+    /// The volume of the cluster in cubic millimetres.
+    pub volume: f32,
+
+    /// The value with the largest magnitude, negative for clusters of negative values.
+    pub peak_value: f32,
+
+    /// The voxel index of the peak.
+    pub peak_voxel: [usize; 3],
+
+    /// The position of the peak in world coordinates in millimetres.
+    pub peak: [f32; 3],
+
+    /// The center of mass in world coordinates in millimetres, weighted by the magnitude of the
+    /// values.
+    pub center_of_mass: [f32; 3],
+}
+
+/// The clusters of a thresholded statistical map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterReport {
+    /// The smallest magnitude of a value that belongs to a cluster.
+    pub threshold: f32,
+
+    /// The number of neighbors of a voxel that belong to the same cluster.
+    pub connectivity: usize,
+
+    /// The clusters from the largest to the smallest.
+    pub clusters: Vec<Cluster>,
+}
+
+impl ClusterReport {
+    /// Finds the connected clusters of voxels above a threshold.
+    ///
+    /// Positive and negative values never belong to the same cluster.
+    ///
+    /// Arguments:
+    ///
+    /// * `volume`: The statistical map, only its displayed frame is used.
+    /// * `threshold`: The smallest magnitude of a value that belongs to a cluster. Negative
+    ///   thresholds are treated as zero and values of zero never belong to a cluster, so the
+    ///   background is not reported.
+    /// * `connectivity`: Which neighbors of a voxel belong to the same cluster.
+    /// * `two_sided`: Whether clusters of values below `-threshold` are reported as well.
+    pub fn new(
+        volume: &Volume,
+        threshold: f32,
+        connectivity: Connectivity,
+        two_sided: bool,
+    ) -> Self {
+        let [nx, ny, nz] = volume.dims;
+        let threshold = threshold.max(0.0);
+        // The sign of the cluster a voxel can belong to, or zero if it is below the threshold.
+        let sign = |value: f32| {
+            if value > 0.0 && value >= threshold {
+                1
+            } else if two_sided && value < 0.0 && value <= -threshold {
+                -1
+            } else {
+                0
+            }
+        };
+        let voxel_volume = Mat3::from_mat4(volume.affine).determinant().abs();
+        let offsets = connectivity.offsets();
+
+        let mut visited = vec![false; volume.data.len()];
+        let mut queue = VecDeque::new();
+        let mut clusters = Vec::new();
+        for start in 0..volume.data.len() {
+            let cluster_sign = sign(volume.data[start]);
+            if visited[start] || cluster_sign == 0 {
+                continue;
+            }
+            visited[start] = true;
+            queue.push_back(start);
+
+            let mut voxel_count = 0;
+            let (mut peak_index, mut peak_value) = (start, volume.data[start]);
+            let (mut weighted_sum, mut weight) = (Vec3::ZERO, 0.0);
+            while let Some(index) = queue.pop_front() {
+                let (x, y, z) = (index % nx, (index / nx) % ny, index / (nx * ny));
+                let value = volume.data[index];
+                voxel_count += 1;
+                if value.abs() > peak_value.abs() {
+                    (peak_index, peak_value) = (index, value);
+                }
+                let position = Vec3::new(x as f32, y as f32, z as f32);
+                weighted_sum += position * value.abs();
+                weight += value.abs();
+
+                for [dx, dy, dz] in &offsets {
+                    let (Some(x), Some(y), Some(z)) = (
+                        x.checked_add_signed(*dx).filter(|&x| x < nx),
+                        y.checked_add_signed(*dy).filter(|&y| y < ny),
+                        z.checked_add_signed(*dz).filter(|&z| z < nz),
+                    ) else {
+                        continue;
+                    };
+                    let neighbor = x + nx * (y + ny * z);
+                    if !visited[neighbor] && sign(volume.data[neighbor]) == cluster_sign {
+                        visited[neighbor] = true;
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+
+            let peak_voxel = [
+                peak_index % nx,
+                (peak_index / nx) % ny,
+                peak_index / (nx * ny),
+            ];
+            let peak = Vec3::new(
+                peak_voxel[0] as f32,
+                peak_voxel[1] as f32,
+                peak_voxel[2] as f32,
+            );
+            // Every voxel of a cluster has a value other than zero, so the weight is positive.
+            let center_of_mass = weighted_sum / weight;
+            clusters.push(Cluster {
+                index: 0,
+                voxel_count,
+                volume: voxel_count as f32 * voxel_volume,
+                peak_value,
+                peak_voxel,
+                peak: volume.affine.transform_point3(peak).to_array(),
+                center_of_mass: volume.affine.transform_point3(center_of_mass).to_array(),
+            });
+        }
+
+        clusters.sort_by(|a, b| {
+            b.voxel_count
+                .cmp(&a.voxel_count)
+                .then(b.peak_value.abs().total_cmp(&a.peak_value.abs()))
+        });
+        for (i, cluster) in clusters.iter_mut().enumerate() {
+            cluster.index = i + 1;
+        }
+
+        Self {
+            threshold,
+            connectivity: connectivity.neighbors(),
+            clusters,
+        }
+    }
+
+    /// Serializes the report to JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Returns the clusters as a table with comma separated values and a header row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "cluster,voxels,volume_mm3,peak_value,peak_x,peak_y,peak_z,\
+             peak_i,peak_j,peak_k,com_x,com_y,com_z\n",
+        );
+        for cluster in &self.clusters {
+            let [px, py, pz] = cluster.peak;
+            let [pi, pj, pk] = cluster.peak_voxel;
+            let [cx, cy, cz] = cluster.center_of_mass;
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                cluster.index,
+                cluster.voxel_count,
+                cluster.volume,
+                cluster.peak_value,
+                px,
+                py,
+                pz,
+                pi,
+                pj,
+                pk,
+                cx,
+                cy,
+                cz
+            ));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{ClusterReport, Connectivity};
+    use crate::data::volume::{tests::test_volume, Volume};
+
+    /// Returns a 5x5x5 map that is zero except for the given voxels.
+    fn map(affine: Mat4, voxels: &[([usize; 3], f32)]) -> Volume {
+        let mut data = vec![0.0; 125];
+        for &([x, y, z], value) in voxels {
+            data[x + 5 * (y + 5 * z)] = value;
+        }
+        test_volume([5, 5, 5], affine, &data)
+    }
+
+    #[test]
+    fn connectivity_decides_which_voxels_are_connected() {
+        // Two voxels sharing a face, one sharing an edge with them and one sharing a corner.
+        let volume = map(
+            Mat4::IDENTITY,
+            &[
+                ([1, 1, 1], 3.0),
+                ([2, 1, 1], 3.0),
+                ([3, 2, 1], 3.0),
+                ([4, 3, 2], 3.0),
+            ],
+        );
+        let counts = |connectivity| {
+            ClusterReport::new(&volume, 2.0, connectivity, false)
+                .clusters
+                .iter()
+                .map(|cluster| cluster.voxel_count)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts(Connectivity::Faces), [2, 1, 1]);
+        assert_eq!(counts(Connectivity::Edges), [3, 1]);
+        assert_eq!(counts(Connectivity::Corners), [4]);
+    }
+
+    #[test]
+    fn signs_are_separate_clusters() {
+        let volume = map(
+            Mat4::IDENTITY,
+            &[([1, 1, 1], 3.0), ([2, 1, 1], -4.0), ([3, 1, 1], -1.0)],
+        );
+        let report = ClusterReport::new(&volume, 2.0, Connectivity::Faces, true);
+        let peaks: Vec<f32> = report.clusters.iter().map(|c| c.peak_value).collect();
+        assert_eq!(peaks, [-4.0, 3.0]);
+        assert_eq!(report.clusters[0].index, 1);
+
+        let report = ClusterReport::new(&volume, 2.0, Connectivity::Faces, false);
+        assert_eq!(report.clusters.len(), 1);
+        assert_eq!(report.clusters[0].peak_value, 3.0);
+    }
+
+    #[test]
+    fn background_is_never_a_cluster() {
+        let volume = map(
+            Mat4::IDENTITY,
+            &[([1, 1, 1], 3.0), ([3, 1, 1], 0.5), ([1, 3, 3], -2.0)],
+        );
+        for threshold in [0.0, -1.0] {
+            let report = ClusterReport::new(&volume, threshold, Connectivity::Corners, true);
+            assert_eq!(report.threshold, 0.0);
+            let counts: Vec<usize> = report.clusters.iter().map(|c| c.voxel_count).collect();
+            assert_eq!(counts, [1, 1, 1]);
+            assert!(report
+                .clusters
+                .iter()
+                .all(|cluster| cluster.center_of_mass.iter().all(|c| c.is_finite())));
+
+            let report = ClusterReport::new(&volume, threshold, Connectivity::Corners, false);
+            assert_eq!(report.clusters.len(), 2);
+        }
+    }
+
+    #[test]
+    fn peak_and_center_of_mass_are_in_world_space() {
+        let affine = Mat4::from_translation(Vec3::new(-10.0, 20.0, 5.0))
+            * Mat4::from_scale(Vec3::new(-2.0, 2.0, 3.0));
+        let volume = map(affine, &[([1, 2, 3], 2.0), ([2, 2, 3], 6.0)]);
+        let report = ClusterReport::new(&volume, 1.0, Connectivity::Faces, false);
+        let cluster = &report.clusters[0];
+
+        assert_eq!(cluster.voxel_count, 2);
+        assert_eq!(cluster.volume, 24.0);
+        assert_eq!(cluster.peak_voxel, [2, 2, 3]);
+        assert_eq!(cluster.peak, [-14.0, 24.0, 14.0]);
+        // The center of mass is at x = 1.75 voxels, weighted by the values.
+        let center_of_mass = Vec3::from(cluster.center_of_mass);
+        assert!(center_of_mass.abs_diff_eq(Vec3::new(-13.5, 24.0, 14.0), 1e-4));
+    }
+}
@@ -0,0 +1,379 @@
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+use crate::data::volume::VolumeError;
+
+/// The size of a NIfTI-1 header in bytes.
+pub const NIFTI1_HEADER_SIZE: usize = 348;
+
+/// The byte order of a NIfTI file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// The fields of a NIfTI-1 header that are needed to interpret the voxel data.
+///
+/// In contrast to `Nifti1Header` this is decoded field by field, so it
+/// works for both byte orders and on every target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NiftiHeader {
+    /// The byte order the header and the voxel data are stored in.
+    pub byte_order: ByteOrder,
+
+    /// The number of dimensions followed by the size of each dimension.
+    pub dim: [i16; 8],
+
+    /// The parameters of the statistic given by `intent_code`.
+    pub intent_p1: f32,
+    pub intent_p2: f32,
+    pub intent_p3: f32,
+
+    /// The NIfTI intent code, e.g. `3` for t-statistics.
+    pub intent_code: i16,
+
+    /// The NIfTI data type code of the voxels.
+    pub datatype: i16,
+
+    /// The number of bits per voxel.
+    pub bitpix: i16,
+
+    /// `pixdim[0]` is the qfac, `pixdim[1..4]` the voxel size and `pixdim[4]` the repetition time.
+    pub pixdim: [f32; 8],
+
+    /// The byte offset of the voxel data in the file.
+    pub vox_offset: f32,
+
+    /// The intensity scaling applied to the stored values.
+    pub scl_slope: f32,
+    pub scl_inter: f32,
+
+    /// The spatial and temporal units, see [NiftiHeader::spatial_unit_to_mm].
+    pub xyzt_units: u8,
+
+    /// The suggested display range.
+    pub cal_max: f32,
+    pub cal_min: f32,
+
+    /// The time offset of the first volume.
+    pub toffset: f32,
+
+    /// A free text description of the data.
+    pub descrip: String,
+
+    /// The codes describing which coordinate system the qform and sform map to.
+    pub qform_code: i16,
+    pub sform_code: i16,
+
+    /// The quaternion based voxel-to-world transform.
+    pub quatern_b: f32,
+    pub quatern_c: f32,
+    pub quatern_d: f32,
+    pub qoffset_x: f32,
+    pub qoffset_y: f32,
+    pub qoffset_z: f32,
+
+    /// The rows of the affine voxel-to-world transform.
+    pub srow_x: [f32; 4],
+    pub srow_y: [f32; 4],
+    pub srow_z: [f32; 4],
+
+    /// The name of the intent.
+    pub intent_name: String,
+}
+
+/// Reads numbers of a given byte order from a header buffer.
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    byte_order: ByteOrder,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn array<const N: usize>(&self, offset: usize) -> [u8; N] {
+        let mut array = [0; N];
+        array.copy_from_slice(&self.bytes[offset..offset + N]);
+        if self.byte_order == ByteOrder::BigEndian {
+            array.reverse();
+        }
+        array
+    }
+
+    fn i16(&self, offset: usize) -> i16 {
+        i16::from_le_bytes(self.array(offset))
+    }
+
+    fn f32(&self, offset: usize) -> f32 {
+        f32::from_le_bytes(self.array(offset))
+    }
+
+    fn string(&self, offset: usize, len: usize) -> String {
+        let bytes = &self.bytes[offset..offset + len];
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(len);
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    }
+}
+
+/// Writes little endian numbers into a header buffer.
+struct HeaderWriter {
+    bytes: Vec<u8>,
+}
+
+impl HeaderWriter {
+    fn put(&mut self, offset: usize, value: &[u8]) {
+        self.bytes[offset..offset + value.len()].copy_from_slice(value);
+    }
+
+    fn i16(&mut self, offset: usize, value: i16) {
+        self.put(offset, &value.to_le_bytes());
+    }
+
+    fn f32(&mut self, offset: usize, value: f32) {
+        self.put(offset, &value.to_le_bytes());
+    }
+
+    fn string(&mut self, offset: usize, len: usize, value: &str) {
+        // The last byte is kept zero so that the string is terminated.
+        let bytes = value.as_bytes();
+        self.put(offset, &bytes[..bytes.len().min(len - 1)]);
+    }
+}
+
+impl NiftiHeader {
+    /// Decodes a NIfTI-1 header from the start of a file.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file or a `.hdr` file.
+    pub fn parse(bytes: &[u8]) -> Result<Self, VolumeError> {
+        if bytes.len() < NIFTI1_HEADER_SIZE {
+            return Err(VolumeError::Truncated);
+        }
+
+        let byte_order = if i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            == NIFTI1_HEADER_SIZE as i32
+        {
+            ByteOrder::LittleEndian
+        } else if i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            == NIFTI1_HEADER_SIZE as i32
+        {
+            ByteOrder::BigEndian
+        } else {
+            return Err(VolumeError::InvalidHeader);
+        };
+        let reader = HeaderReader { bytes, byte_order };
+
+        let mut dim = [0; 8];
+        for (i, d) in dim.iter_mut().enumerate() {
+            *d = reader.i16(40 + 2 * i);
+        }
+        let mut pixdim = [0.0; 8];
+        for (i, p) in pixdim.iter_mut().enumerate() {
+            *p = reader.f32(76 + 4 * i);
+        }
+        let row = |offset: usize| {
+            [
+                reader.f32(offset),
+                reader.f32(offset + 4),
+                reader.f32(offset + 8),
+                reader.f32(offset + 12),
+            ]
+        };
+
+        Ok(Self {
+            byte_order,
+            dim,
+            intent_p1: reader.f32(56),
+            intent_p2: reader.f32(60),
+            intent_p3: reader.f32(64),
+            intent_code: reader.i16(68),
+            datatype: reader.i16(70),
+            bitpix: reader.i16(72),
+            pixdim,
+            vox_offset: reader.f32(108),
+            scl_slope: reader.f32(112),
+            scl_inter: reader.f32(116),
+            xyzt_units: bytes[123],
+            cal_max: reader.f32(124),
+            cal_min: reader.f32(128),
+            toffset: reader.f32(136),
+            descrip: reader.string(148, 80),
+            qform_code: reader.i16(252),
+            sform_code: reader.i16(254),
+            quatern_b: reader.f32(256),
+            quatern_c: reader.f32(260),
+            quatern_d: reader.f32(264),
+            qoffset_x: reader.f32(268),
+            qoffset_y: reader.f32(272),
+            qoffset_z: reader.f32(276),
+            srow_x: row(280),
+            srow_y: row(296),
+            srow_z: row(312),
+            intent_name: reader.string(328, 16),
+        })
+    }
+
+    /// Encodes the header as a little endian NIfTI-1 header of a single `.nii` file.
+    ///
+    /// Fields that are not part of [NiftiHeader] are written as zero. The `byte_order` field is
+    /// ignored, so the voxel data has to follow in little endian byte order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = HeaderWriter {
+            bytes: vec![0; NIFTI1_HEADER_SIZE],
+        };
+        writer.put(0, &(NIFTI1_HEADER_SIZE as i32).to_le_bytes());
+        for (i, d) in self.dim.iter().enumerate() {
+            writer.i16(40 + 2 * i, *d);
+        }
+        writer.f32(56, self.intent_p1);
+        writer.f32(60, self.intent_p2);
+        writer.f32(64, self.intent_p3);
+        writer.i16(68, self.intent_code);
+        writer.i16(70, self.datatype);
+        writer.i16(72, self.bitpix);
+        for (i, p) in self.pixdim.iter().enumerate() {
+            writer.f32(76 + 4 * i, *p);
+        }
+        writer.f32(108, self.vox_offset);
+        writer.f32(112, self.scl_slope);
+        writer.f32(116, self.scl_inter);
+        writer.put(123, &[self.xyzt_units]);
+        writer.f32(124, self.cal_max);
+        writer.f32(128, self.cal_min);
+        writer.f32(136, self.toffset);
+        writer.string(148, 80, &self.descrip);
+        writer.i16(252, self.qform_code);
+        writer.i16(254, self.sform_code);
+        writer.f32(256, self.quatern_b);
+        writer.f32(260, self.quatern_c);
+        writer.f32(264, self.quatern_d);
+        writer.f32(268, self.qoffset_x);
+        writer.f32(272, self.qoffset_y);
+        writer.f32(276, self.qoffset_z);
+        for (offset, row) in [(280, self.srow_x), (296, self.srow_y), (312, self.srow_z)] {
+            for (i, value) in row.iter().enumerate() {
+                writer.f32(offset + 4 * i, *value);
+            }
+        }
+        writer.string(328, 16, &self.intent_name);
+        writer.put(344, b"n+1\0");
+        writer.bytes
+    }
+
+    /// Returns the number of voxels along the x, y and z axes.
+    pub fn spatial_dims(&self) -> [usize; 3] {
+        let d = |i: usize| {
+            if self.dim[0] as usize >= i {
+                self.dim[i].max(1) as usize
+            } else {
+                1
+            }
+        };
+        [d(1), d(2), d(3)]
+    }
+
+    /// Returns the number of volumes of a time series, which is `1` for 3D volumes.
+    ///
+    /// Only the fourth dimension is taken into account, higher dimensions are ignored.
+    pub fn frame_count(&self) -> usize {
+        if self.dim[0] >= 4 {
+            self.dim[4].max(1) as usize
+        } else {
+            1
+        }
+    }
+
+    /// Returns the time between two volumes of a time series in seconds.
+    ///
+    /// The repetition time is taken from `pixdim[4]` and the temporal unit of `xyzt_units`.
+    /// Files that do not specify a unit are assumed to be in seconds. Returns [None] for 3D
+    /// volumes, if the repetition time is not positive or if the fourth dimension is not time.
+    pub fn repetition_time(&self) -> Option<f32> {
+        if self.frame_count() < 2 {
+            return None;
+        }
+        let tr = self.pixdim[4] * self.time_unit_to_seconds()?;
+        (tr.is_finite() && tr > 0.0).then_some(tr)
+    }
+
+    /// Returns the factor that converts the temporal unit of `pixdim[4]` and `toffset` to
+    /// seconds, or [None] if the unit is not a time, e.g. hertz.
+    ///
+    /// Files that do not specify a unit are assumed to be in seconds.
+    pub fn time_unit_to_seconds(&self) -> Option<f32> {
+        match self.xyzt_units & 0x38 {
+            0 | 8 => Some(1.0), // NIFTI_UNITS_UNKNOWN, NIFTI_UNITS_SEC
+            16 => Some(0.001),  // NIFTI_UNITS_MSEC
+            24 => Some(1e-6),   // NIFTI_UNITS_USEC
+            _ => None,
+        }
+    }
+
+    /// Returns the size of a voxel in millimetres.
+    pub fn voxel_size(&self) -> Vec3 {
+        let factor = self.spatial_unit_to_mm();
+        Vec3::new(
+            self.pixdim[1].abs(),
+            self.pixdim[2].abs(),
+            self.pixdim[3].abs(),
+        ) * factor
+    }
+
+    /// Returns the factor that converts the spatial unit of `pixdim` to millimetres.
+    ///
+    /// Files that do not specify a unit are assumed to be in millimetres.
+    pub fn spatial_unit_to_mm(&self) -> f32 {
+        match self.xyzt_units & 0x07 {
+            1 => 1000.0, // NIFTI_UNITS_METER
+            3 => 0.001,  // NIFTI_UNITS_MICRON
+            _ => 1.0,    // NIFTI_UNITS_MM or unknown
+        }
+    }
+
+    /// Returns the intensity scaling as `(slope, intercept)`.
+    ///
+    /// A slope of zero means that no scaling is applied.
+    pub fn scaling(&self) -> (f32, f32) {
+        if self.scl_slope == 0.0 || !self.scl_slope.is_finite() {
+            (1.0, 0.0)
+        } else {
+            (self.scl_slope, self.scl_inter)
+        }
+    }
+
+    /// Returns the transform from voxel indices to world coordinates in millimetres.
+    ///
+    /// The sform is preferred over the qform. If neither is set the voxel size is used.
+    pub fn affine(&self) -> Mat4 {
+        let factor = self.spatial_unit_to_mm();
+        if self.sform_code > 0 {
+            let mut affine = Mat4::from_cols(
+                Vec4::new(self.srow_x[0], self.srow_y[0], self.srow_z[0], 0.0),
+                Vec4::new(self.srow_x[1], self.srow_y[1], self.srow_z[1], 0.0),
+                Vec4::new(self.srow_x[2], self.srow_y[2], self.srow_z[2], 0.0),
+                Vec4::new(self.srow_x[3], self.srow_y[3], self.srow_z[3], 1.0),
+            );
+            affine = Mat4::from_scale(Vec3::splat(factor)) * affine;
+            return affine;
+        }
+
+        if self.qform_code > 0 {
+            let (b, c, d) = (self.quatern_b, self.quatern_c, self.quatern_d);
+            let a = (1.0 - (b * b + c * c + d * d)).max(0.0).sqrt();
+            let qfac = if self.pixdim[0] < 0.0 { -1.0 } else { 1.0 };
+            let rotation = Mat4::from_quat(Quat::from_xyzw(b, c, d, a));
+            let scale = Mat4::from_scale(Vec3::new(
+                self.pixdim[1].abs(),
+                self.pixdim[2].abs(),
+                self.pixdim[3].abs() * qfac,
+            ));
+            let translation = Mat4::from_translation(Vec3::new(
+                self.qoffset_x,
+                self.qoffset_y,
+                self.qoffset_z,
+            ));
+            return Mat4::from_scale(Vec3::splat(factor)) * translation * rotation * scale;
+        }
+
+        Mat4::from_scale(self.voxel_size())
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use glam::Vec3;
+
+use crate::data::{
+    lookup_table::{Label, LookupTable},
+    volume::Volume,
+};
+
+/// A volume of integer labels, e.g. a segmentation or an atlas, with the lookup table that
+/// names and colors them.
+///
+/// Labels are never interpolated or windowed, every position takes the label of the closest
+/// voxel.
+#[derive(Debug, Clone)]
+pub struct LabelMap {
+    /// The volume whose voxels hold the labels.
+    pub volume: Volume,
+
+    /// The names, colors and visibility of the labels.
+    pub table: LookupTable,
+}
+
+impl LabelMap {
+    /// Creates a label map from a volume and its lookup table.
+    ///
+    /// Arguments:
+    ///
+    /// * `volume`: The volume whose voxels hold the labels.
+    /// * `table`: The names, colors and visibility of the labels.
+    pub fn new(volume: Volume, table: LookupTable) -> Self {
+        Self { volume, table }
+    }
+
+    /// Returns the labels that occur in the volume in ascending order, without the
+    /// background label `0`.
+    pub fn labels(&self) -> Vec<u32> {
+        self.volume
+            .data
+            .iter()
+            .filter_map(|&value| label_value(value))
+            .filter(|&label| label != 0)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the label of the voxel closest to a position, or [None] outside of the volume.
+    ///
+    /// Arguments:
+    ///
+    /// * `position`: The position in world coordinates in millimetres.
+    pub fn label_at(&self, position: Vec3) -> Option<u32> {
+        let [x, y, z] = self
+            .volume
+            .voxel_index(self.volume.world_to_voxel(position))?;
+        label_value(self.volume.get(x, y, z))
+    }
+
+    /// Returns the entry of the lookup table for the label at a position, e.g. to show the name
+    /// of the region under the cursor.
+    ///
+    /// The position is mapped to a voxel through the qform or sform of the label map itself, so
+    /// atlases on another grid than the displayed volumes are looked up correctly.
+    ///
+    /// Arguments:
+    ///
+    /// * `position`: The position in world coordinates in millimetres.
+    pub fn entry_at(&self, position: Vec3) -> Option<&Label> {
+        self.table.get(self.label_at(position)?)
+    }
+}
+
+/// Returns the label of a voxel value, or [None] if it is negative or not finite.
+///
+/// Arguments:
+///
+/// * `value`: The value of a voxel of a label map.
+pub fn label_value(value: f32) -> Option<u32> {
+    (value.is_finite() && value >= 0.0).then(|| value.round() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{label_value, LabelMap};
+    use crate::data::{lookup_table::LookupTable, volume::tests::test_volume};
+
+    /// A 3x2x1 atlas with 2 mm voxels whose origin is at (-3, 10, 4) mm.
+    fn atlas() -> LabelMap {
+        let affine =
+            Mat4::from_translation(Vec3::new(-3.0, 10.0, 4.0)) * Mat4::from_scale(Vec3::splat(2.0));
+        let volume = test_volume([3, 2, 1], affine, &[0.0, 17.0, 17.0, 53.0, 0.0, 17.0]);
+        let table = LookupTable::parse(
+            "#No. Label Name:    R   G   B   A\n\
+             0   Unknown           0   0   0   0\n\
+             17  Left-Hippocampus  220 216 20  0\n\
+             53  Right-Hippocampus 220 216 20  0\n",
+        )
+        .unwrap();
+        LabelMap::new(volume, table)
+    }
+
+    #[test]
+    fn label_at_world_position() {
+        let atlas = atlas();
+        assert_eq!(atlas.labels(), [17, 53]);
+        // The voxel (1, 0, 0) is centered at (-1, 10, 4) mm and reaches 1 mm to each side.
+        assert_eq!(atlas.label_at(Vec3::new(-1.0, 10.0, 4.0)), Some(17));
+        assert_eq!(atlas.label_at(Vec3::new(-2.1, 10.9, 4.5)), Some(0));
+        assert_eq!(atlas.label_at(Vec3::new(-3.0, 12.0, 4.0)), Some(53));
+        assert_eq!(atlas.label_at(Vec3::new(-5.0, 10.0, 4.0)), None);
+        assert_eq!(atlas.label_at(Vec3::new(-1.0, 10.0, 6.0)), None);
+    }
+
+    #[test]
+    fn entry_at_world_position() {
+        let atlas = atlas();
+        let entry = atlas.entry_at(Vec3::new(-3.0, 12.0, 4.0)).unwrap();
+        assert_eq!(entry.name, "Right-Hippocampus");
+        assert_eq!(entry.color, [220, 216, 20]);
+        assert!(atlas.entry_at(Vec3::new(20.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn label_values() {
+        assert_eq!(label_value(16.9999), Some(17));
+        assert_eq!(label_value(-1.0), None);
+        assert_eq!(label_value(f32::NAN), None);
+    }
+}
@@ -0,0 +1,292 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+/// The errors that can occur while parsing a label lookup table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupTableError {
+    /// A line does not contain a label, a name and a color.
+    InvalidLine(usize),
+
+    /// The file does not contain any label.
+    Empty,
+}
+
+impl fmt::Display for LookupTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine(line) => write!(f, "Line {} is not a valid label.", line),
+            Self::Empty => write!(f, "The lookup table does not contain any label."),
+        }
+    }
+}
+
+impl std::error::Error for LookupTableError {}
+
+/// A label of a label map with its name and how it is drawn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    /// The name of the anatomical region, e.g. `Left-Hippocampus`.
+    pub name: String,
+
+    /// The RGB color.
+    pub color: [u8; 3],
+
+    /// The opacity from `0.0` for transparent to `1.0` for opaque.
+    pub opacity: f32,
+
+    /// Whether the label is drawn.
+    pub visible: bool,
+}
+
+impl Label {
+    /// Returns the RGBA color of the label with its opacity as alpha.
+    pub fn rgba(&self) -> [f32; 4] {
+        let [r, g, b] = self.color.map(|c| c as f32 / 255.0);
+        [r, g, b, self.opacity]
+    }
+}
+
+/// The names and colors of the labels of a label map, e.g. a segmentation or an atlas.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LookupTable {
+    /// The labels by their value in the label map.
+    pub labels: BTreeMap<u32, Label>,
+}
+
+impl LookupTable {
+    /// Parses a lookup table and detects its format.
+    ///
+    /// ITK-SNAP label descriptions are recognized by their quoted names, everything else is
+    /// parsed as FreeSurfer color table.
+    ///
+    /// Arguments:
+    ///
+    /// * `text`: The contents of the file.
+    pub fn parse(text: &str) -> Result<Self, LookupTableError> {
+        if data_lines(text).any(|(_, line)| line.contains('"')) {
+            Self::from_itksnap(text)
+        } else {
+            Self::from_freesurfer(text)
+        }
+    }
+
+    /// Parses a FreeSurfer color table like `FreeSurferColorLUT.txt`.
+    ///
+    /// Every line holds the label, a name without spaces and the red, green, blue and
+    /// transparency components from `0` to `255`. Lines starting with `#` are comments.
+    ///
+    /// Arguments:
+    ///
+    /// * `text`: The contents of the file.
+    pub fn from_freesurfer(text: &str) -> Result<Self, LookupTableError> {
+        Self::from_lines(text, parse_freesurfer_line)
+    }
+
+    /// Parses an ITK-SNAP label description file.
+    ///
+    /// Every line holds the label, the red, green and blue components from `0` to `255`, the
+    /// opacity from `0.0` to `1.0`, the visibility and mesh visibility as `0` or `1` and the
+    /// name in double quotes. Lines starting with `#` are comments.
+    ///
+    /// Arguments:
+    ///
+    /// * `text`: The contents of the file.
+    pub fn from_itksnap(text: &str) -> Result<Self, LookupTableError> {
+        Self::from_lines(text, parse_itksnap_line)
+    }
+
+    /// Returns a label by its value in the label map.
+    ///
+    /// Arguments:
+    ///
+    /// * `value`: The value of the label.
+    pub fn get(&self, value: u32) -> Option<&Label> {
+        self.labels.get(&value)
+    }
+
+    /// Shows or hides a label. Returns `false` if the table does not contain the label.
+    ///
+    /// Arguments:
+    ///
+    /// * `value`: The value of the label.
+    /// * `visible`: Whether the label is drawn.
+    pub fn set_visible(&mut self, value: u32, visible: bool) -> bool {
+        self.labels
+            .get_mut(&value)
+            .map(|label| label.visible = visible)
+            .is_some()
+    }
+
+    /// Changes the opacity of a label. Returns `false` if the table does not contain the label.
+    ///
+    /// Arguments:
+    ///
+    /// * `value`: The value of the label.
+    /// * `opacity`: The opacity from `0.0` for transparent to `1.0` for opaque.
+    pub fn set_opacity(&mut self, value: u32, opacity: f32) -> bool {
+        self.labels
+            .get_mut(&value)
+            .map(|label| label.opacity = opacity.clamp(0.0, 1.0))
+            .is_some()
+    }
+
+    /// Parses every line that is neither empty nor a comment into a label.
+    fn from_lines<F>(text: &str, parse_line: F) -> Result<Self, LookupTableError>
+    where
+        F: Fn(&str) -> Option<(u32, Label)>,
+    {
+        let mut labels = BTreeMap::new();
+        for (number, line) in data_lines(text) {
+            let (value, label) = parse_line(line).ok_or(LookupTableError::InvalidLine(number))?;
+            labels.insert(value, label);
+        }
+        if labels.is_empty() {
+            return Err(LookupTableError::Empty);
+        }
+        Ok(Self { labels })
+    }
+}
+
+/// Returns the lines that are neither empty nor comments with their line numbers.
+fn data_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Parses a line of a FreeSurfer color table, `label name r g b [transparency]`.
+fn parse_freesurfer_line(line: &str) -> Option<(u32, Label)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (value, name, r, g, b, transparency) = match fields[..] {
+        [value, name, r, g, b] => (value, name, r, g, b, "0"),
+        [value, name, r, g, b, transparency] => (value, name, r, g, b, transparency),
+        _ => return None,
+    };
+    let label = Label {
+        name: name.to_string(),
+        color: [r.parse().ok()?, g.parse().ok()?, b.parse().ok()?],
+        opacity: 1.0 - transparency.parse::<u8>().ok()? as f32 / 255.0,
+        visible: true,
+    };
+    Some((value.parse().ok()?, label))
+}
+
+/// Parses a line of an ITK-SNAP label description, `label r g b opacity visible mesh "name"`.
+fn parse_itksnap_line(line: &str) -> Option<(u32, Label)> {
+    let (fields, name) = line.split_once('"')?;
+    let name = name.trim_end().strip_suffix('"')?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    let [value, r, g, b, opacity, visible, _mesh_visible] = fields[..] else {
+        return None;
+    };
+    let label = Label {
+        name: name.to_string(),
+        color: [r.parse().ok()?, g.parse().ok()?, b.parse().ok()?],
+        opacity: opacity.parse::<f32>().ok()?.clamp(0.0, 1.0),
+        visible: visible != "0",
+    };
+    Some((value.parse().ok()?, label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LookupTable, LookupTableError};
+
+    const FREESURFER: &str = "\
+#$Id: FreeSurferColorLUT.txt
+#No. Label Name:                            R   G   B   A
+
+0   Unknown                                 0   0   0   0
+17  Left-Hippocampus                        220 216 20  0
+  # Labels may be indented and the transparency may be left out.
+  53  Right-Hippocampus                     220 216 20
+1000    ctx-lh-unknown                      25  5   25  255
+";
+
+    const ITKSNAP: &str = "\
+################################################
+# ITK-SnAP Label Description File
+#    IDX   -R-  -G-  -B-  -A--  VIS MSH  LABEL
+################################################
+    0     0    0    0        0  0  0    \"Clear Label\"
+    1   255    0    0        1  1  1    \"Label 1\"
+
+    2     0  255    0      0.5  0  1    \"Left hippocampus\"
+";
+
+    #[test]
+    fn freesurfer_color_table() {
+        let table = LookupTable::parse(FREESURFER).unwrap();
+        assert_eq!(table, LookupTable::from_freesurfer(FREESURFER).unwrap());
+        assert_eq!(
+            table.labels.keys().copied().collect::<Vec<_>>(),
+            [0, 17, 53, 1000]
+        );
+
+        let hippocampus = table.get(53).unwrap();
+        assert_eq!(hippocampus.name, "Right-Hippocampus");
+        assert_eq!(hippocampus.color, [220, 216, 20]);
+        assert_eq!(hippocampus.opacity, 1.0);
+        assert!(hippocampus.visible);
+        // FreeSurfer stores the transparency rather than the opacity.
+        assert_eq!(table.get(1000).unwrap().opacity, 0.0);
+        assert!(table.get(2).is_none());
+    }
+
+    #[test]
+    fn itksnap_label_description() {
+        let table = LookupTable::parse(ITKSNAP).unwrap();
+        assert_eq!(table, LookupTable::from_itksnap(ITKSNAP).unwrap());
+        assert_eq!(table.labels.len(), 3);
+
+        let label = table.get(1).unwrap();
+        assert_eq!(label.name, "Label 1");
+        assert_eq!(label.rgba(), [1.0, 0.0, 0.0, 1.0]);
+        assert!(label.visible);
+        // The name keeps its spaces and the visibility is independent of the mesh visibility.
+        let label = table.get(2).unwrap();
+        assert_eq!(label.name, "Left hippocampus");
+        assert_eq!(label.opacity, 0.5);
+        assert!(!label.visible);
+        assert!(!table.get(0).unwrap().visible);
+    }
+
+    #[test]
+    fn malformed_lines() {
+        // The line numbers count comments and empty lines.
+        let cases = [
+            ("# Header\n\n17 Left-Hippocampus 220 216\n", 3),
+            ("17 Left-Hippocampus 220 216 256 0\n", 1),
+            ("0 Unknown 0 0 0 0\n-1 Negative 0 0 0 0\n", 2),
+            ("0 Unknown 0 0 0 0 extra\n", 1),
+            ("1 255 0 0 1 1 1 \"Unterminated\n", 1),
+            ("1 255 0 0 1 1 \"No mesh visibility\"\n", 1),
+            ("1 255 0 0 high 1 1 \"Label 1\"\n", 1),
+        ];
+        for (text, line) in cases {
+            assert_eq!(
+                LookupTable::parse(text).unwrap_err(),
+                LookupTableError::InvalidLine(line),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            LookupTable::parse("# Only a comment\n\n").unwrap_err(),
+            LookupTableError::Empty
+        );
+    }
+
+    #[test]
+    fn visibility_and_opacity() {
+        let mut table = LookupTable::parse(FREESURFER).unwrap();
+        assert!(table.set_visible(17, false));
+        assert!(!table.get(17).unwrap().visible);
+        assert!(table.set_opacity(17, 1.5));
+        assert_eq!(table.get(17).unwrap().opacity, 1.0);
+        assert!(!table.set_visible(18, false));
+        assert!(!table.set_opacity(18, 0.5));
+    }
+}
@@ -0,0 +1,238 @@
+use std::f32::consts::PI;
+
+use glam::{Mat4, Vec3};
+
+use crate::data::volume::Volume;
+
+/// The number of voxels on each side of a position that contribute to sinc interpolation.
+pub const SINC_RADIUS: usize = 3;
+
+/// How intensities between voxel centers are interpolated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// The intensity of the closest voxel, which keeps the labels of label maps.
+    Nearest,
+
+    /// The intensities of the eight surrounding voxels weighted by their distance.
+    Trilinear,
+
+    /// A sinc kernel with a Hann window of [SINC_RADIUS] voxels, which keeps edges sharper
+    /// than trilinear interpolation.
+    Sinc,
+}
+
+impl Interpolation {
+    /// Returns the interpolation with a name like `nearest`, `trilinear` or `sinc`.
+    ///
+    /// Arguments:
+    ///
+    /// * `name`: The name of the interpolation, `linear` is accepted for trilinear.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "nearest" | "nearestneighbour" | "nearestneighbor" => Some(Self::Nearest),
+            "trilinear" | "linear" => Some(Self::Trilinear),
+            "sinc" => Some(Self::Sinc),
+            _ => None,
+        }
+    }
+}
+
+/// Resamples the displayed frame of a volume onto the voxel grid of a reference.
+///
+/// Every voxel of the reference is mapped through the inverse transform into the moving volume,
+/// voxels outside of it are `0.0`. The result has the header of the reference with the intent
+/// of the moving volume, so it can be saved with [Volume::to_nifti_bytes].
+///
+/// Arguments:
+///
+/// * `moving`: The volume that is resampled.
+/// * `reference`: The volume whose grid the result has.
+/// * `transform`: The transform from world coordinates of the moving volume to world
+///   coordinates of the reference, e.g. from [crate::data::transform::parse_transform].
+/// * `interpolation`: How intensities between voxel centers are interpolated.
+pub fn resample(
+    moving: &Volume,
+    reference: &Volume,
+    transform: Mat4,
+    interpolation: Interpolation,
+) -> Volume {
+    let reference_to_moving = moving.affine.inverse() * transform.inverse() * reference.affine;
+    let [nx, ny, nz] = reference.dims;
+    let mut data = Vec::with_capacity(nx * ny * nz);
+    for z in 0..nz {
+        for y in 0..ny {
+            for x in 0..nx {
+                let voxel = Vec3::new(x as f32, y as f32, z as f32);
+                let position = reference_to_moving.transform_point3(voxel);
+                data.push(sample(moving, position, interpolation));
+            }
+        }
+    }
+
+    let mut resampled = reference.with_data(data);
+    resampled.header.intent_code = moving.header.intent_code;
+    resampled.header.intent_p1 = moving.header.intent_p1;
+    resampled.header.intent_p2 = moving.header.intent_p2;
+    resampled.header.intent_p3 = moving.header.intent_p3;
+    resampled.header.intent_name = moving.header.intent_name.clone();
+    resampled
+}
+
+/// Returns the interpolated intensity of a volume, or `0.0` outside of it.
+///
+/// Arguments:
+///
+/// * `volume`: The volume that is sampled.
+/// * `position`: The position in voxel coordinates.
+/// * `interpolation`: How intensities between voxel centers are interpolated.
+fn sample(volume: &Volume, position: Vec3, interpolation: Interpolation) -> f32 {
+    let inside = (0..3).all(|i| position[i] >= -0.5 && position[i] <= volume.dims[i] as f32 - 0.5);
+    if !inside {
+        return 0.0;
+    }
+    match interpolation {
+        Interpolation::Nearest => volume
+            .voxel_index(position)
+            .map_or(0.0, |[x, y, z]| volume.get(x, y, z)),
+        Interpolation::Trilinear => volume.sample_trilinear(position),
+        Interpolation::Sinc => sample_sinc(volume, position),
+    }
+}
+
+/// Samples a volume with a Hann windowed sinc kernel, voxels beyond the edges repeat the
+/// closest voxel.
+///
+/// Arguments:
+///
+/// * `volume`: The volume that is sampled.
+/// * `position`: The position in voxel coordinates within the volume.
+fn sample_sinc(volume: &Volume, position: Vec3) -> f32 {
+    // The indices and weights of the voxels along each axis.
+    let mut taps = [[(0, 0.0); 2 * SINC_RADIUS]; 3];
+    for (axis, axis_taps) in taps.iter_mut().enumerate() {
+        let first = position[axis].floor() as i64 - SINC_RADIUS as i64 + 1;
+        let last = volume.dims[axis] as i64 - 1;
+        for (i, tap) in axis_taps.iter_mut().enumerate() {
+            let index = first + i as i64;
+            *tap = (
+                index.clamp(0, last) as usize,
+                hann_sinc(position[axis] - index as f32),
+            );
+        }
+    }
+
+    let mut sum = 0.0;
+    let mut weights = 0.0;
+    for &(z, wz) in &taps[2] {
+        for &(y, wy) in &taps[1] {
+            for &(x, wx) in &taps[0] {
+                let weight = wx * wy * wz;
+                sum += volume.get(x, y, z) * weight;
+                weights += weight;
+            }
+        }
+    }
+    if weights.abs() > f32::EPSILON {
+        sum / weights
+    } else {
+        volume.sample_trilinear(position)
+    }
+}
+
+/// Returns the weight of a voxel at a distance in voxels for sinc interpolation.
+fn hann_sinc(distance: f32) -> f32 {
+    let radius = SINC_RADIUS as f32;
+    if distance.abs() >= radius {
+        return 0.0;
+    }
+    if distance.abs() < 1.0e-6 {
+        return 1.0;
+    }
+    let x = PI * distance;
+    let window = 0.5 * (1.0 + (x / radius).cos());
+    x.sin() / x * window
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{resample, Interpolation};
+    use crate::data::volume::{tests::test_volume, Volume};
+
+    const INTERPOLATIONS: [Interpolation; 3] = [
+        Interpolation::Nearest,
+        Interpolation::Trilinear,
+        Interpolation::Sinc,
+    ];
+
+    /// Returns a 5x5x5 volume with 2 mm voxels that is `0.0` except for the given voxels.
+    fn volume(voxels: &[([usize; 3], f32)]) -> Volume {
+        let mut data = vec![0.0; 125];
+        for &([x, y, z], value) in voxels {
+            data[x + 5 * (y + 5 * z)] = value;
+        }
+        test_volume([5, 5, 5], Mat4::from_scale(Vec3::splat(2.0)), &data)
+    }
+
+    #[test]
+    fn identity_reproduces_the_volume() {
+        let affine = Mat4::from_translation(Vec3::new(-4.0, 3.0, 10.0))
+            * Mat4::from_rotation_y(0.4)
+            * Mat4::from_scale(Vec3::new(1.5, 2.0, 3.0));
+        let data: Vec<f32> = (0..60).map(|i| ((i * 37) % 11) as f32).collect();
+        let moving = test_volume([5, 4, 3], affine, &data);
+
+        for interpolation in INTERPOLATIONS {
+            let resampled = resample(&moving, &moving, Mat4::IDENTITY, interpolation);
+            assert_eq!(resampled.dims, moving.dims);
+            for (a, b) in resampled.data.iter().zip(&data) {
+                assert!((a - b).abs() < 1e-4, "{:?}: {} != {}", interpolation, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn translation_shifts_a_bright_voxel() {
+        let moving = volume(&[([2, 2, 2], 10.0)]);
+        // The moving volume is 2 mm, which is one voxel, further along x than the reference.
+        let transform = Mat4::from_translation(Vec3::new(2.0, 0.0, 0.0));
+
+        for interpolation in INTERPOLATIONS {
+            let resampled = resample(&moving, &moving, transform, interpolation);
+            let brightest = (0..125)
+                .max_by(|&a, &b| resampled.data[a].total_cmp(&resampled.data[b]))
+                .unwrap();
+            assert_eq!(brightest, 3 + 5 * (2 + 5 * 2), "{:?}", interpolation);
+            assert!((resampled.get(3, 2, 2) - 10.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn voxels_outside_of_the_moving_volume_are_zero() {
+        let moving = test_volume([5, 5, 5], Mat4::from_scale(Vec3::splat(2.0)), &[1.0; 125]);
+        // Two voxels of the reference along x are left of the moving volume.
+        let transform = Mat4::from_translation(Vec3::new(4.0, 0.0, 0.0));
+
+        for interpolation in INTERPOLATIONS {
+            let resampled = resample(&moving, &moving, transform, interpolation);
+            for (i, value) in resampled.data.iter().enumerate() {
+                let expected = if i % 5 < 2 { 0.0 } else { 1.0 };
+                assert!((value - expected).abs() < 1e-4, "{:?}", interpolation);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolation_names() {
+        assert_eq!(
+            Interpolation::from_name("NearestNeighbour"),
+            Some(Interpolation::Nearest)
+        );
+        assert_eq!(
+            Interpolation::from_name("linear"),
+            Some(Interpolation::Trilinear)
+        );
+        assert_eq!(Interpolation::from_name("cubic"), None);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::data::header::NiftiHeader;
+
+/// The one-sided p-value that statistical maps are thresholded at by default.
+pub const DEFAULT_P_VALUE: f32 = 0.001;
+
+/// A threshold of a statistical map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatThreshold {
+    /// A value of the statistic, e.g. `3.1` for a z-score.
+    Value(f32),
+
+    /// A one-sided p-value, e.g. `0.001`.
+    PValue(f32),
+}
+
+/// The statistic of a statistical map, decoded from the NIfTI intent code and parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    /// A Pearson correlation, `NIFTI_INTENT_CORREL`.
+    Correlation {
+        /// The degrees of freedom, `intent_p1`.
+        dof: f32,
+    },
+
+    /// A t-statistic, `NIFTI_INTENT_TTEST`.
+    TTest {
+        /// The degrees of freedom, `intent_p1`.
+        dof: f32,
+    },
+
+    /// An F-statistic, `NIFTI_INTENT_FTEST`.
+    FTest {
+        /// The degrees of freedom of the numerator, `intent_p1`.
+        numerator_dof: f32,
+
+        /// The degrees of freedom of the denominator, `intent_p2`.
+        denominator_dof: f32,
+    },
+
+    /// A standard normal z-score, `NIFTI_INTENT_ZSCORE`.
+    ZScore,
+}
+
+impl Statistic {
+    /// Returns the statistic of a header, or [None] if its intent is not a supported statistic
+    /// or the degrees of freedom are missing.
+    ///
+    /// Arguments:
+    ///
+    /// * `header`: The header of the statistical map.
+    pub fn from_header(header: &NiftiHeader) -> Option<Self> {
+        let dof = |p: f32| (p.is_finite() && p > 0.0).then_some(p);
+        match header.intent_code {
+            2 => Some(Self::Correlation {
+                dof: dof(header.intent_p1)?,
+            }),
+            3 => Some(Self::TTest {
+                dof: dof(header.intent_p1)?,
+            }),
+            4 => Some(Self::FTest {
+                numerator_dof: dof(header.intent_p1)?,
+                denominator_dof: dof(header.intent_p2)?,
+            }),
+            5 => Some(Self::ZScore),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the statistic.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Correlation { .. } => "correlation",
+            Self::TTest { .. } => "t",
+            Self::FTest { .. } => "F",
+            Self::ZScore => "z",
+        }
+    }
+
+    /// Returns whether the statistic has negative values that are shown separately.
+    pub fn is_signed(&self) -> bool {
+        !matches!(self, Self::FTest { .. })
+    }
+
+    /// Returns the one-sided p-value of a statistic, the probability of a value at least as
+    /// large as its magnitude.
+    ///
+    /// Arguments:
+    ///
+    /// * `value`: The value of the statistic.
+    pub fn p_value(&self, value: f32) -> f32 {
+        let value = (value as f64).abs();
+        let p = match *self {
+            Self::Correlation { dof } => {
+                if value >= 1.0 {
+                    return 0.0;
+                }
+                let dof = dof as f64;
+                student_t_upper_tail(value * (dof / (1.0 - value * value)).sqrt(), dof)
+            }
+            Self::TTest { dof } => student_t_upper_tail(value, dof as f64),
+            Self::FTest {
+                numerator_dof,
+                denominator_dof,
+            } => {
+                let (d1, d2) = (numerator_dof as f64, denominator_dof as f64);
+                incomplete_beta(d2 / 2.0, d1 / 2.0, d2 / (d2 + d1 * value))
+            }
+            Self::ZScore => 0.5 * erfc(value / std::f64::consts::SQRT_2),
+        };
+        p as f32
+    }
+
+    /// Returns the value of the statistic whose one-sided p-value is `p`.
+    ///
+    /// Arguments:
+    ///
+    /// * `p`: The p-value between `0.0` and `0.5`.
+    pub fn value_for_p(&self, p: f32) -> f32 {
+        let p = p.clamp(f32::MIN_POSITIVE, 0.5);
+        // The p-value decreases with the statistic, so the value is found by bisection after
+        // the upper bound was found by doubling.
+        let upper_limit = match self {
+            Self::Correlation { .. } => 1.0,
+            _ => f32::MAX,
+        };
+        let mut high = 1.0f32.min(upper_limit);
+        while self.p_value(high) > p && high < upper_limit {
+            high = (high * 2.0).min(upper_limit);
+        }
+        let mut low = 0.0f32;
+        for _ in 0..64 {
+            let middle = (low + high) / 2.0;
+            if self.p_value(middle) > p {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        high
+    }
+
+    /// Returns the value of the statistic of a threshold.
+    ///
+    /// Arguments:
+    ///
+    /// * `threshold`: The threshold as value or as p-value.
+    pub fn threshold_value(&self, threshold: StatThreshold) -> f32 {
+        match threshold {
+            StatThreshold::Value(value) => value.abs(),
+            StatThreshold::PValue(p) => self.value_for_p(p),
+        }
+    }
+}
+
+/// Returns the probability that a Student t distributed variable is at least `t >= 0`.
+fn student_t_upper_tail(t: f64, dof: f64) -> f64 {
+    0.5 * incomplete_beta(dof / 2.0, 0.5, dof / (dof + t * t))
+}
+
+/// Returns the natural logarithm of the gamma function with the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Returns the regularized incomplete beta function `I_x(a, b)`.
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly only on one side of the mean.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Evaluates the continued fraction of the incomplete beta function with Lentz's method.
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let (qab, qap, qam) = (a + b, a + 1.0, a - 1.0);
+    let mut c = 1.0;
+    let mut d = 1.0 - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < 1e-14 {
+            break;
+        }
+    }
+    h
+}
+
+/// Returns the complementary error function with a Chebyshev approximation, whose relative
+/// error is below `1.2e-7`.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let polynomial = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |sum, c| sum * t + c);
+    let result = t * (-z * z + polynomial).exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StatThreshold, Statistic};
+
+    /// Asserts that two numbers differ by less than a relative tolerance.
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * tolerance,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn z_p_values() {
+        let z = Statistic::ZScore;
+        assert_close(z.p_value(0.0), 0.5, 1e-4);
+        assert_close(z.p_value(1.644_854), 0.05, 1e-3);
+        assert_close(z.p_value(-3.090_232), 0.001, 1e-3);
+        assert_close(z.value_for_p(0.001), 3.090_232, 1e-4);
+    }
+
+    #[test]
+    fn t_p_values() {
+        let t = Statistic::TTest { dof: 10.0 };
+        assert_close(t.p_value(2.763_769), 0.01, 1e-3);
+        assert_close(t.value_for_p(0.01), 2.763_769, 1e-4);
+        let t = Statistic::TTest { dof: 20.0 };
+        assert_close(t.p_value(1.724_718), 0.05, 1e-3);
+    }
+
+    #[test]
+    fn f_p_values() {
+        // F(1, d) is the square of t(d), whose two-sided p-value is twice the one-sided one.
+        let f = Statistic::FTest {
+            numerator_dof: 1.0,
+            denominator_dof: 10.0,
+        };
+        assert_close(f.p_value(2.763_769f32.powi(2)), 0.02, 1e-3);
+        let f = Statistic::FTest {
+            numerator_dof: 2.0,
+            denominator_dof: 30.0,
+        };
+        assert_close(f.p_value(3.315_83), 0.05, 1e-3);
+        assert_close(f.value_for_p(0.05), 3.315_83, 1e-4);
+    }
+
+    #[test]
+    fn correlation_p_values() {
+        // r = 0.5 with 10 degrees of freedom is t = 0.5 * sqrt(10 / 0.75).
+        let r = Statistic::Correlation { dof: 10.0 };
+        let t = Statistic::TTest { dof: 10.0 };
+        assert_close(
+            r.p_value(0.5),
+            t.p_value(0.5 * (10.0f32 / 0.75).sqrt()),
+            1e-4,
+        );
+        assert_eq!(r.p_value(1.0), 0.0);
+        assert!(r.value_for_p(1e-12) <= 1.0);
+    }
+
+    #[test]
+    fn value_for_p_inverts_p_value() {
+        let statistics = [
+            Statistic::ZScore,
+            Statistic::TTest { dof: 5.0 },
+            Statistic::TTest { dof: 120.0 },
+            Statistic::FTest {
+                numerator_dof: 3.0,
+                denominator_dof: 40.0,
+            },
+            Statistic::Correlation { dof: 30.0 },
+        ];
+        for statistic in statistics {
+            for p in [0.2, 0.05, 0.001, 1e-5] {
+                let value = statistic.threshold_value(StatThreshold::PValue(p));
+                assert_close(statistic.p_value(value), p, 1e-3);
+            }
+            assert_eq!(statistic.threshold_value(StatThreshold::Value(-2.5)), 2.5);
+        }
+    }
+}
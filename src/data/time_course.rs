@@ -0,0 +1,355 @@
+use std::fmt;
+
+use glam::{Mat3, Vec3};
+
+use crate::data::volume::Volume;
+
+/// The errors that can occur while extracting a time course.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeCourseError {
+    /// The voxel or position is outside of the volume.
+    OutsideVolume,
+
+    /// The region of interest does not contain any voxel of the volume.
+    EmptyRegion,
+
+    /// The mask has a different number of voxels than a frame of the volume.
+    MaskSize { expected: usize, actual: usize },
+
+    /// The seed time course has a different number of values than the volume has frames.
+    SeedLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for TimeCourseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutsideVolume => write!(f, "The position is outside of the volume."),
+            Self::EmptyRegion => write!(f, "The region of interest does not contain any voxel."),
+            Self::MaskSize { expected, actual } => write!(
+                f,
+                "The mask has {} voxels but the volume has {}.",
+                actual, expected
+            ),
+            Self::SeedLength { expected, actual } => write!(
+                f,
+                "The seed time course has {} values but the volume has {} frames.",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimeCourseError {}
+
+/// A region of interest whose voxels are averaged by [get_mean_time_course].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Roi {
+    /// The voxels whose centers are within a sphere.
+    Sphere {
+        /// The center in world coordinates in millimetres.
+        center: Vec3,
+
+        /// The radius in millimetres.
+        radius: f32,
+    },
+
+    /// The voxels that are set in a mask on the voxel grid of the volume, x varies fastest.
+    Mask(Vec<bool>),
+}
+
+impl Roi {
+    /// Creates a mask of the voxels of a volume that are not zero.
+    ///
+    /// The mask has to be on the same voxel grid as the time series it is applied to.
+    ///
+    /// Arguments:
+    ///
+    /// * `mask`: The volume of the mask, only its displayed frame is used.
+    pub fn from_mask_volume(mask: &Volume) -> Self {
+        Self::Mask(mask.data.iter().map(|&value| value != 0.0).collect())
+    }
+}
+
+/// Returns the time of each frame in seconds, starting at `toffset`.
+///
+/// Volumes without a repetition time return the frame indices instead.
+///
+/// Arguments:
+///
+/// * `volume`: The time series.
+pub fn get_frame_times(volume: &Volume) -> Vec<f32> {
+    let header = &volume.header;
+    let (start, interval) = match header.repetition_time() {
+        Some(tr) => (
+            header.toffset * header.time_unit_to_seconds().unwrap_or(1.0),
+            tr,
+        ),
+        None => (0.0, 1.0),
+    };
+    (0..volume.frame_count)
+        .map(|frame| start + frame as f32 * interval)
+        .collect()
+}
+
+/// Returns the intensities of a voxel in every frame, with scaling applied.
+///
+/// Arguments:
+///
+/// * `volume`: The time series.
+/// * `voxel`: The voxel index along the x, y and z axes.
+pub fn get_time_course(volume: &Volume, voxel: [usize; 3]) -> Result<Vec<f32>, TimeCourseError> {
+    if (0..3).any(|i| voxel[i] >= volume.dims[i]) {
+        return Err(TimeCourseError::OutsideVolume);
+    }
+    let index = voxel[0] + volume.dims[0] * (voxel[1] + volume.dims[1] * voxel[2]);
+    Ok((0..volume.frame_count)
+        .map(|frame| volume.frame_data(frame)[index])
+        .collect())
+}
+
+/// Returns the intensities of the voxel closest to a position in every frame.
+///
+/// Arguments:
+///
+/// * `volume`: The time series.
+/// * `position`: The position in world coordinates in millimetres.
+pub fn get_time_course_at(volume: &Volume, position: Vec3) -> Result<Vec<f32>, TimeCourseError> {
+    let voxel = volume
+        .voxel_index(volume.world_to_voxel(position))
+        .ok_or(TimeCourseError::OutsideVolume)?;
+    get_time_course(volume, voxel)
+}
+
+/// Returns the mean intensity of the voxels of a region of interest in every frame.
+///
+/// Voxels that are not finite in a frame are left out of the mean of that frame.
+///
+/// Arguments:
+///
+/// * `volume`: The time series.
+/// * `roi`: The region of interest.
+pub fn get_mean_time_course(volume: &Volume, roi: &Roi) -> Result<Vec<f32>, TimeCourseError> {
+    let indices = roi_indices(volume, roi)?;
+    if indices.is_empty() {
+        return Err(TimeCourseError::EmptyRegion);
+    }
+    Ok((0..volume.frame_count)
+        .map(|frame| {
+            let data = volume.frame_data(frame);
+            let (sum, count) = indices
+                .iter()
+                .map(|&i| data[i])
+                .filter(|value| value.is_finite())
+                .fold((0.0f64, 0usize), |(sum, count), value| {
+                    (sum + value as f64, count + 1)
+                });
+            if count == 0 {
+                f32::NAN
+            } else {
+                (sum / count as f64) as f32
+            }
+        })
+        .collect())
+}
+
+/// Calculates the Pearson correlation between a seed time course and the time course of every
+/// voxel.
+///
+/// The result is a 3D volume on the voxel grid of the time series with the NIfTI intent
+/// `NIFTI_INTENT_CORREL` and `frame_count - 2` degrees of freedom. Voxels outside of the mask
+/// and voxels or seeds that do not vary over time have a correlation of zero.
+///
+/// Arguments:
+///
+/// * `volume`: The time series.
+/// * `seed`: The seed time course, e.g. from [get_time_course] or [get_mean_time_course].
+/// * `mask`: The region the correlation is calculated in, or [None] for every voxel.
+pub fn get_correlation_map(
+    volume: &Volume,
+    seed: &[f32],
+    mask: Option<&Roi>,
+) -> Result<Volume, TimeCourseError> {
+    let frames = volume.frame_count;
+    if seed.len() != frames {
+        return Err(TimeCourseError::SeedLength {
+            expected: frames,
+            actual: seed.len(),
+        });
+    }
+    let count = volume.data.len();
+    let indices = match mask {
+        Some(roi) => roi_indices(volume, roi)?,
+        None => (0..count).collect(),
+    };
+
+    // The seed is centered and normalized, so the correlation reduces to a dot product with
+    // the voxel time course divided by the norm of the centered voxel time course.
+    let mean = seed.iter().map(|&v| v as f64).sum::<f64>() / frames as f64;
+    let mut seed: Vec<f64> = seed.iter().map(|&v| v as f64 - mean).collect();
+    let norm = seed.iter().map(|v| v * v).sum::<f64>().sqrt();
+    let mut map = vec![0.0; count];
+    if norm > 0.0 && norm.is_finite() {
+        seed.iter_mut().for_each(|v| *v /= norm);
+
+        let mut sums = vec![[0.0f64; 3]; indices.len()];
+        for (frame, s) in seed.iter().enumerate() {
+            let data = volume.frame_data(frame);
+            for (sum, &i) in sums.iter_mut().zip(&indices) {
+                let value = data[i] as f64;
+                sum[0] += value;
+                sum[1] += value * value;
+                sum[2] += value * s;
+            }
+        }
+        for ([sum, squares, product], &i) in sums.into_iter().zip(&indices) {
+            let variance = squares - sum * sum / frames as f64;
+            let r = product / variance.sqrt();
+            // Rounding leaves a tiny variance for voxels that do not vary.
+            if variance > squares * 1e-10 && r.is_finite() {
+                map[i] = r.clamp(-1.0, 1.0) as f32;
+            }
+        }
+    }
+
+    let mut map = volume.with_data(map);
+    map.header.intent_code = 2; // NIFTI_INTENT_CORREL
+    map.header.intent_p1 = frames.saturating_sub(2) as f32;
+    map.header.intent_name = "correlation".to_string();
+    map.header.cal_min = -1.0;
+    map.header.cal_max = 1.0;
+    Ok(map)
+}
+
+/// Returns the linear indices of the voxels of a region of interest.
+fn roi_indices(volume: &Volume, roi: &Roi) -> Result<Vec<usize>, TimeCourseError> {
+    let [nx, ny, nz] = volume.dims;
+    match roi {
+        Roi::Mask(mask) => {
+            if mask.len() != nx * ny * nz {
+                return Err(TimeCourseError::MaskSize {
+                    expected: nx * ny * nz,
+                    actual: mask.len(),
+                });
+            }
+            Ok((0..mask.len()).filter(|&i| mask[i]).collect())
+        }
+        Roi::Sphere { center, radius } => {
+            // Only the voxels within the bounding box of the sphere are tested.
+            let voxel_center = volume.world_to_voxel(*center);
+            let inverse = Mat3::from_mat4(volume.affine.inverse());
+            let range = |axis: usize, count: usize| {
+                let reach = *radius * inverse.row(axis).length();
+                let start = (voxel_center[axis] - reach).ceil().max(0.0) as usize;
+                let end = ((voxel_center[axis] + reach).floor() + 1.0).max(0.0) as usize;
+                start..end.min(count)
+            };
+            let mut indices = Vec::new();
+            for z in range(2, nz) {
+                for y in range(1, ny) {
+                    for x in range(0, nx) {
+                        let position = volume
+                            .affine
+                            .transform_point3(Vec3::new(x as f32, y as f32, z as f32));
+                        if position.distance(*center) <= *radius {
+                            indices.push(x + nx * (y + ny * z));
+                        }
+                    }
+                }
+            }
+            Ok(indices)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{
+        get_correlation_map, get_frame_times, get_mean_time_course, get_time_course_at, Roi,
+        TimeCourseError,
+    };
+    use crate::data::volume::{tests::test_volume, Volume};
+
+    /// A 2x2x1 time series of 5 frames with 2 mm voxels. The voxels follow the seed, its
+    /// negative, a constant and a noisy copy of the seed.
+    fn series() -> Volume {
+        let seed = [1.0, 3.0, 2.0, 5.0, 4.0];
+        let noisy = [2.0, 2.0, 3.0, 4.0, 5.0];
+        let mut data = Vec::new();
+        for frame in 0..5 {
+            data.extend([seed[frame], 3.0 - 2.0 * seed[frame], 7.0, noisy[frame]]);
+        }
+        test_volume([2, 2, 1], Mat4::from_scale(Vec3::splat(2.0)), &data)
+    }
+
+    #[test]
+    fn time_course_at_world_position() {
+        let volume = series();
+        assert_eq!(get_frame_times(&volume), [0.0, 2.0, 4.0, 6.0, 8.0]);
+        // The voxel (1, 1, 0) is centered at (2, 2, 0) mm.
+        let course = get_time_course_at(&volume, Vec3::new(2.4, 1.6, 0.0)).unwrap();
+        assert_eq!(course, [2.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(
+            get_time_course_at(&volume, Vec3::new(5.0, 0.0, 0.0)).unwrap_err(),
+            TimeCourseError::OutsideVolume
+        );
+    }
+
+    #[test]
+    fn mean_time_course() {
+        let volume = series();
+        let mask = Roi::Mask(vec![true, false, true, false]);
+        let mean = get_mean_time_course(&volume, &mask).unwrap();
+        assert_eq!(mean, [4.0, 5.0, 4.5, 6.0, 5.5]);
+
+        // The sphere only contains the center of the first voxel.
+        let sphere = Roi::Sphere {
+            center: Vec3::new(0.0, 0.5, 0.0),
+            radius: 1.0,
+        };
+        let mean = get_mean_time_course(&volume, &sphere).unwrap();
+        assert_eq!(mean, [1.0, 3.0, 2.0, 5.0, 4.0]);
+
+        assert_eq!(
+            get_mean_time_course(&volume, &Roi::Mask(vec![false; 4])).unwrap_err(),
+            TimeCourseError::EmptyRegion
+        );
+    }
+
+    #[test]
+    fn seed_correlation() {
+        let volume = series();
+        let seed = get_time_course_at(&volume, Vec3::new(0.4, -0.4, 0.0)).unwrap();
+        assert_eq!(seed, [1.0, 3.0, 2.0, 5.0, 4.0]);
+
+        let map = get_correlation_map(&volume, &seed, None).unwrap();
+        assert_eq!(map.frame_count, 1);
+        assert_eq!(map.header.intent_code, 2);
+        assert_eq!(map.header.intent_p1, 3.0);
+        assert!((map.data[0] - 1.0).abs() < 1e-6);
+        assert!((map.data[1] + 1.0).abs() < 1e-6);
+        assert_eq!(map.data[2], 0.0);
+        // The centered time courses are (-2, 0, -1, 2, 1) and (-1.2, -1.2, -0.2, 0.8, 1.8).
+        let expected = 6.0 / (10.0f32 * 6.8).sqrt();
+        assert!((map.data[3] - expected).abs() < 1e-6, "{}", map.data[3]);
+    }
+
+    #[test]
+    fn seed_correlation_within_mask() {
+        let volume = series();
+        let seed = [1.0, 3.0, 2.0, 5.0, 4.0];
+        let mask = Roi::Mask(vec![false, true, true, false]);
+        let map = get_correlation_map(&volume, &seed, Some(&mask)).unwrap();
+        assert_eq!(map.data[0], 0.0);
+        assert!((map.data[1] + 1.0).abs() < 1e-6);
+
+        assert_eq!(
+            get_correlation_map(&volume, &seed[..4], None).unwrap_err(),
+            TimeCourseError::SeedLength {
+                expected: 5,
+                actual: 4
+            }
+        );
+    }
+}
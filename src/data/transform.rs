@@ -0,0 +1,424 @@
+use std::fmt;
+
+use glam::{DMat3, DMat4, DQuat, DVec3, Mat4};
+
+use crate::data::volume::Volume;
+
+/// The errors that can occur while parsing a transform file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformError {
+    /// The format of the file can not be told from its name.
+    UnknownFormat(String),
+
+    /// A line does not contain numbers where they are expected.
+    InvalidLine(usize),
+
+    /// An FSL matrix does not have four rows of four numbers.
+    InvalidMatrix,
+
+    /// The file does not contain a transform.
+    Empty,
+
+    /// An ITK transform type that is not affine or not three-dimensional.
+    UnsupportedTransform(String),
+
+    /// An ITK transform has the wrong number of parameters.
+    ParameterCount {
+        transform: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// The transform can not be inverted.
+    Singular,
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat(file_name) => {
+                write!(f, "The transform format of {} is unknown.", file_name)
+            }
+            Self::InvalidLine(line) => write!(f, "Line {} does not contain valid numbers.", line),
+            Self::InvalidMatrix => write!(f, "The FSL matrix does not have 4 rows of 4 numbers."),
+            Self::Empty => write!(f, "The file does not contain a transform."),
+            Self::UnsupportedTransform(name) => {
+                write!(f, "The ITK transform {} is not supported.", name)
+            }
+            Self::ParameterCount {
+                transform,
+                expected,
+                found,
+            } => write!(
+                f,
+                "The ITK transform {} has {} parameters instead of {}.",
+                transform, found, expected
+            ),
+            Self::Singular => write!(f, "The transform can not be inverted."),
+        }
+    }
+}
+
+impl std::error::Error for TransformError {}
+
+/// The file formats affine transforms can be loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformFormat {
+    /// FSL FLIRT matrices like `moving2reference.mat`.
+    Fsl,
+
+    /// ITK transform files like `.txt` or `.tfm` written by ANTs, ITK-SNAP or 3D Slicer.
+    Itk,
+}
+
+impl TransformFormat {
+    /// Returns the format of a file based on its name.
+    ///
+    /// Arguments:
+    ///
+    /// * `file_name`: The name or path of the file.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let name = file_name.to_ascii_lowercase();
+        if name.ends_with(".mat") {
+            Some(Self::Fsl)
+        } else if name.ends_with(".txt") || name.ends_with(".tfm") {
+            Some(Self::Itk)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses an affine transform and detects its format from the file name.
+///
+/// The transform maps world coordinates of the moving volume to world coordinates of the
+/// reference, so `transform * moving.affine` places the moving volume onto the reference.
+///
+/// Arguments:
+///
+/// * `text`: The contents of the file.
+/// * `file_name`: The name or path of the file.
+/// * `moving`: The volume that was registered to the reference, FSL matrices depend on its grid.
+/// * `reference`: The volume the moving one was registered to.
+pub fn parse_transform(
+    text: &str,
+    file_name: &str,
+    moving: &Volume,
+    reference: &Volume,
+) -> Result<Mat4, TransformError> {
+    match TransformFormat::from_file_name(file_name)
+        .ok_or_else(|| TransformError::UnknownFormat(file_name.to_string()))?
+    {
+        TransformFormat::Fsl => parse_fsl_transform(text, moving, reference),
+        TransformFormat::Itk => parse_itk_transform(text),
+    }
+}
+
+/// Parses an FSL FLIRT matrix and converts it to world coordinates.
+///
+/// FLIRT maps between scaled voxel coordinates, the voxel indices times the voxel size with the
+/// x axis flipped if the voxel to world transform has a positive determinant. The result maps
+/// world coordinates of the moving volume to world coordinates of the reference.
+///
+/// Arguments:
+///
+/// * `text`: The contents of the `.mat` file, four rows of four numbers.
+/// * `moving`: The input volume of FLIRT.
+/// * `reference`: The reference volume of FLIRT.
+pub fn parse_fsl_transform(
+    text: &str,
+    moving: &Volume,
+    reference: &Volume,
+) -> Result<Mat4, TransformError> {
+    let mut rows = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = parse_numbers(line).ok_or(TransformError::InvalidLine(number + 1))?;
+        rows.push(row);
+    }
+    if rows.len() != 4 || rows.iter().any(|row| row.len() != 4) {
+        return Err(TransformError::InvalidMatrix);
+    }
+    let flirt = DMat4::from_cols_array_2d(&[
+        [rows[0][0], rows[1][0], rows[2][0], rows[3][0]],
+        [rows[0][1], rows[1][1], rows[2][1], rows[3][1]],
+        [rows[0][2], rows[1][2], rows[2][2], rows[3][2]],
+        [rows[0][3], rows[1][3], rows[2][3], rows[3][3]],
+    ]);
+
+    let reference_to_scaled = fsl_scaled_voxels(reference);
+    if reference_to_scaled.determinant() == 0.0 || moving.affine.determinant() == 0.0 {
+        return Err(TransformError::Singular);
+    }
+    let transform = reference.affine.as_dmat4()
+        * reference_to_scaled.inverse()
+        * flirt
+        * fsl_scaled_voxels(moving)
+        * moving.affine.as_dmat4().inverse();
+    Ok(transform.as_mat4())
+}
+
+/// Parses an ITK transform file and converts it from LPS to RAS world coordinates.
+///
+/// ITK transforms map points of the fixed image to the moving image, so the result is the
+/// inverse: it maps world coordinates of the moving volume to world coordinates of the fixed
+/// reference. Affine, rigid and versor rigid transforms are supported, several transforms like
+/// in a composite transform are applied from the last to the first.
+///
+/// Arguments:
+///
+/// * `text`: The contents of the `.txt` or `.tfm` file.
+pub fn parse_itk_transform(text: &str) -> Result<Mat4, TransformError> {
+    // Every entry holds the type, the parameters and the fixed parameters of a transform.
+    let mut entries: Vec<(String, Vec<f64>, Vec<f64>)> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let numbers = || parse_numbers(value).ok_or(TransformError::InvalidLine(number + 1));
+        if key.trim() == "Transform" {
+            entries.push((value.trim().to_string(), Vec::new(), Vec::new()));
+            continue;
+        }
+        match (key.trim(), entries.last_mut()) {
+            ("Parameters", Some(entry)) => entry.1 = numbers()?,
+            ("FixedParameters", Some(entry)) => entry.2 = numbers()?,
+            _ => {}
+        }
+    }
+
+    let mut fixed_to_moving = None;
+    for (name, parameters, fixed_parameters) in &entries {
+        if name.starts_with("CompositeTransform") {
+            continue;
+        }
+        let matrix = itk_matrix(name, parameters, fixed_parameters)?;
+        fixed_to_moving = Some(fixed_to_moving.unwrap_or(DMat4::IDENTITY) * matrix);
+    }
+    let fixed_to_moving = fixed_to_moving.ok_or(TransformError::Empty)?;
+
+    // LPS and RAS coordinates differ by the signs of x and y.
+    let lps = DMat4::from_scale(DVec3::new(-1.0, -1.0, 1.0));
+    let fixed_to_moving = lps * fixed_to_moving * lps;
+    if fixed_to_moving.determinant() == 0.0 {
+        return Err(TransformError::Singular);
+    }
+    Ok(fixed_to_moving.inverse().as_mat4())
+}
+
+/// Returns the transform from voxel indices to the scaled voxel coordinates of FSL.
+///
+/// Arguments:
+///
+/// * `volume`: The volume whose grid the coordinates refer to.
+fn fsl_scaled_voxels(volume: &Volume) -> DMat4 {
+    let scale = DMat4::from_scale(volume.header.voxel_size().as_dvec3());
+    // FSL flips the x axis of volumes in neurological orientation.
+    if volume.affine.determinant() > 0.0 {
+        let flip = DMat4::from_translation(DVec3::new(volume.dims[0] as f64 - 1.0, 0.0, 0.0))
+            * DMat4::from_scale(DVec3::new(-1.0, 1.0, 1.0));
+        scale * flip
+    } else {
+        scale
+    }
+}
+
+/// Returns the transform from fixed to moving LPS coordinates of an ITK transform.
+///
+/// Arguments:
+///
+/// * `name`: The type of the transform, e.g. `AffineTransform_double_3_3`.
+/// * `parameters`: The parameters, e.g. the matrix followed by the translation.
+/// * `fixed_parameters`: The center of rotation, the origin if missing.
+fn itk_matrix(
+    name: &str,
+    parameters: &[f64],
+    fixed_parameters: &[f64],
+) -> Result<DMat4, TransformError> {
+    let kind = name.split('_').next().unwrap_or(name);
+    let expected = match kind {
+        "AffineTransform" | "MatrixOffsetTransformBase" | "Rigid3DTransform" => 12,
+        "VersorRigid3DTransform" | "Euler3DTransform" => 6,
+        _ => return Err(TransformError::UnsupportedTransform(name.to_string())),
+    };
+    // The dimensions of the input and output space follow the precision, e.g. `_double_3_3`.
+    if !name.ends_with("_3_3") {
+        return Err(TransformError::UnsupportedTransform(name.to_string()));
+    }
+    if parameters.len() != expected {
+        return Err(TransformError::ParameterCount {
+            transform: name.to_string(),
+            expected,
+            found: parameters.len(),
+        });
+    }
+
+    let translation = DVec3::from_slice(&parameters[expected - 3..]);
+    let matrix = match kind {
+        "VersorRigid3DTransform" => {
+            let [x, y, z] = [parameters[0], parameters[1], parameters[2]];
+            let w = (1.0 - x * x - y * y - z * z).max(0.0).sqrt();
+            DMat3::from_quat(DQuat::from_xyzw(x, y, z, w))
+        }
+        "Euler3DTransform" => {
+            let [x, y, z] = [parameters[0], parameters[1], parameters[2]];
+            // The fourth fixed parameter selects the order of the rotations.
+            if fixed_parameters.get(3).is_some_and(|&zyx| zyx != 0.0) {
+                DMat3::from_rotation_z(z) * DMat3::from_rotation_y(y) * DMat3::from_rotation_x(x)
+            } else {
+                DMat3::from_rotation_z(z) * DMat3::from_rotation_x(x) * DMat3::from_rotation_y(y)
+            }
+        }
+        // The matrix is stored row by row.
+        _ => DMat3::from_cols_slice(&parameters[..9]).transpose(),
+    };
+
+    // Points are rotated about the center, x' = A (x - c) + t + c.
+    let center = match fixed_parameters {
+        [x, y, z, ..] => DVec3::new(*x, *y, *z),
+        _ => DVec3::ZERO,
+    };
+    let offset = translation + center - matrix * center;
+    Ok(DMat4::from_translation(offset) * DMat4::from_mat3(matrix))
+}
+
+/// Parses numbers separated by whitespace, or returns [None] if one is not a number.
+fn parse_numbers(text: &str) -> Option<Vec<f64>> {
+    text.split_whitespace()
+        .map(|number| number.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{parse_fsl_transform, parse_itk_transform, parse_transform, TransformError};
+    use crate::data::volume::{tests::test_volume, Volume};
+
+    /// Returns an empty volume of 10x10x10 voxels.
+    fn volume(affine: Mat4) -> Volume {
+        test_volume([10, 10, 10], affine, &[0.0; 1000])
+    }
+
+    const FSL_IDENTITY: &str = "1 0 0 0\n0 1 0 0\n0 0 1 0\n0 0 0 1\n";
+
+    #[test]
+    fn fsl_identity() {
+        for affine in [
+            Mat4::from_scale(Vec3::new(-2.0, 2.0, 2.0)),
+            Mat4::from_translation(Vec3::new(-9.0, 4.0, 1.0)) * Mat4::from_scale(Vec3::splat(2.0)),
+        ] {
+            let transform = parse_fsl_transform(FSL_IDENTITY, &volume(affine), &volume(affine));
+            assert!(transform.unwrap().abs_diff_eq(Mat4::IDENTITY, 1e-5));
+        }
+    }
+
+    #[test]
+    fn fsl_translation_in_scaled_voxels() {
+        let matrix = "1 0 0 4\n0 1 0 -2\n0 0 1 6\n0 0 0 1\n";
+
+        // In radiological orientation the scaled voxels point along the voxel axes.
+        let radiological = volume(Mat4::from_scale(Vec3::new(-2.0, 2.0, 2.0)));
+        let transform = parse_fsl_transform(matrix, &radiological, &radiological).unwrap();
+        let moved = transform.transform_point3(Vec3::ZERO);
+        assert!(moved.abs_diff_eq(Vec3::new(-4.0, -2.0, 6.0), 1e-5));
+
+        // In neurological orientation FSL flips the x axis.
+        let neurological = volume(Mat4::from_scale(Vec3::splat(2.0)));
+        let transform = parse_fsl_transform(matrix, &neurological, &neurological).unwrap();
+        let moved = transform.transform_point3(Vec3::ZERO);
+        assert!(moved.abs_diff_eq(Vec3::new(-4.0, -2.0, 6.0), 1e-5));
+    }
+
+    #[test]
+    fn fsl_errors() {
+        let volume = volume(Mat4::IDENTITY);
+        assert_eq!(
+            parse_fsl_transform("1 0 0 0\n0 1 0 0\n0 0 1 0\n", &volume, &volume).unwrap_err(),
+            TransformError::InvalidMatrix
+        );
+        assert_eq!(
+            parse_fsl_transform("1 0 0 0\n\n0 x 0 0\n", &volume, &volume).unwrap_err(),
+            TransformError::InvalidLine(3)
+        );
+        assert_eq!(
+            parse_transform(FSL_IDENTITY, "moving2reference.xfm", &volume, &volume).unwrap_err(),
+            TransformError::UnknownFormat("moving2reference.xfm".to_string())
+        );
+    }
+
+    #[test]
+    fn itk_translation_is_inverted_and_converted_to_ras() {
+        let text = "#Insight Transform File V1.0\n\
+                    #Transform 0\n\
+                    Transform: AffineTransform_double_3_3\n\
+                    Parameters: 1 0 0 0 1 0 0 0 1 1 2 3\n\
+                    FixedParameters: 0 0 0\n";
+        let transform = parse_itk_transform(text).unwrap();
+        let moved = transform.transform_point3(Vec3::ZERO);
+        assert!(moved.abs_diff_eq(Vec3::new(1.0, 2.0, -3.0), 1e-5));
+    }
+
+    #[test]
+    fn itk_euler_matches_affine_about_center() {
+        let (angle, center) = (0.4f32, "10 -5 20");
+        let euler = parse_itk_transform(&format!(
+            "Transform: Euler3DTransform_double_3_3\n\
+             Parameters: 0 0 {angle} 1 2 3\n\
+             FixedParameters: {center} 0\n"
+        ))
+        .unwrap();
+        let (sin, cos) = angle.sin_cos();
+        let affine = parse_itk_transform(&format!(
+            "Transform: AffineTransform_double_3_3\n\
+             Parameters: {cos} {} 0 {sin} {cos} 0 0 0 1 1 2 3\n\
+             FixedParameters: {center}\n",
+            -sin
+        ))
+        .unwrap();
+        assert!(euler.abs_diff_eq(affine, 1e-5));
+    }
+
+    #[test]
+    fn itk_composite_applies_every_transform() {
+        let text = "Transform: CompositeTransform_double_3_3\n\
+                    Transform: AffineTransform_double_3_3\n\
+                    Parameters: 1 0 0 0 1 0 0 0 1 1 0 0\n\
+                    Transform: AffineTransform_double_3_3\n\
+                    Parameters: 2 0 0 0 2 0 0 0 2 0 0 5\n";
+        // The last transform is applied first, fixed to moving maps x to 2 x + (1, 0, 5) in LPS.
+        let transform = parse_itk_transform(text).unwrap();
+        let moved = transform.transform_point3(Vec3::new(-1.0, 0.0, 5.0));
+        assert!(moved.abs_diff_eq(Vec3::ZERO, 1e-5));
+    }
+
+    #[test]
+    fn itk_errors() {
+        assert_eq!(
+            parse_itk_transform("#Insight Transform File V1.0\n").unwrap_err(),
+            TransformError::Empty
+        );
+        assert!(matches!(
+            parse_itk_transform("Transform: BSplineTransform_double_3_3\n"),
+            Err(TransformError::UnsupportedTransform(_))
+        ));
+        assert_eq!(
+            parse_itk_transform(
+                "Transform: VersorRigid3DTransform_double_3_3\nParameters: 0 0 0 1 2\n"
+            )
+            .unwrap_err(),
+            TransformError::ParameterCount {
+                transform: "VersorRigid3DTransform_double_3_3".to_string(),
+                expected: 6,
+                found: 5,
+            }
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use std::{fmt, io::Read};
+
+use glam::{Mat4, Vec3};
+
+use crate::data::header::{ByteOrder, NiftiHeader, NIFTI1_HEADER_SIZE};
+
+/// The errors that can occur while decoding a volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VolumeError {
+    /// The file ends before the header or the voxel data is complete.
+    Truncated,
+
+    /// The file does not start with a NIfTI-1 header.
+    InvalidHeader,
+
+    /// The voxels are stored in a NIfTI data type that is not supported.
+    UnsupportedDataType(i16),
+
+    /// The gzip compressed contents of a `.nii.gz` file can not be decompressed.
+    InvalidCompression,
+}
+
+impl fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "The file is truncated."),
+            Self::InvalidHeader => write!(f, "The file does not contain a NIfTI-1 header."),
+            Self::UnsupportedDataType(code) => {
+                write!(f, "The NIfTI data type {} is not supported.", code)
+            }
+            Self::InvalidCompression => write!(f, "The file is not valid gzip data."),
+        }
+    }
+}
+
+impl std::error::Error for VolumeError {}
+
+/// A decoded scalar volume with scaled intensities.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    /// The header the volume was decoded from.
+    pub header: NiftiHeader,
+
+    /// The number of voxels along the x, y and z axes.
+    pub dims: [usize; 3],
+
+    /// The transform from voxel indices to world coordinates in millimetres.
+    pub affine: Mat4,
+
+    /// The intensities of the displayed frame with scaling applied, x varies fastest.
+    pub data: Vec<f32>,
+
+    /// The number of frames of a time series, `1` for 3D volumes.
+    pub frame_count: usize,
+
+    /// The index of the frame held in `data`.
+    frame: usize,
+
+    /// The intensities of all frames one after another, empty for 3D volumes.
+    series: Vec<f32>,
+}
+
+impl Volume {
+    /// Decodes a volume from the contents of a `.nii` or `.nii.gz` file.
+    ///
+    /// All frames of a time series are decoded and kept in memory, the first one is displayed.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of the file, which are decompressed if they start with the gzip
+    ///   magic number.
+    pub fn from_nifti_bytes(bytes: &[u8]) -> Result<Self, VolumeError> {
+        let decompressed;
+        let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut raw = Vec::new();
+            flate2::read::GzDecoder::new(bytes)
+                .read_to_end(&mut raw)
+                .map_err(|_| VolumeError::InvalidCompression)?;
+            decompressed = raw;
+            &decompressed
+        } else {
+            bytes
+        };
+        let header = NiftiHeader::parse(bytes)?;
+        let dims = header.spatial_dims();
+        let frame_count = header.frame_count();
+        // The sizes come from the header, so they are checked before they index the data.
+        let count = dims
+            .into_iter()
+            .try_fold(1usize, |count, size| count.checked_mul(size))
+            .ok_or(VolumeError::InvalidHeader)?;
+        let total = count
+            .checked_mul(frame_count)
+            .ok_or(VolumeError::InvalidHeader)?;
+        let mut data = decode_voxels(&header, bytes, header.vox_offset as usize, total)?;
+        let series = if frame_count > 1 {
+            let series = data;
+            data = series[..count].to_vec();
+            series
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            affine: header.affine(),
+            dims,
+            data,
+            frame_count,
+            frame: 0,
+            series,
+            header,
+        })
+    }
+
+    /// Creates a 3D volume on the voxel grid of this volume, e.g. for a statistical map.
+    ///
+    /// The header is copied with the data type set to 32 bit floats and without intensity
+    /// scaling, display range or intent.
+    ///
+    /// Arguments:
+    ///
+    /// * `data`: The intensities, x varies fastest. Their number has to match `dims`.
+    pub fn with_data(&self, data: Vec<f32>) -> Self {
+        let mut header = self.header.clone();
+        header.dim[0] = header.dim[0].min(3);
+        header.dim[4..].fill(1);
+        header.datatype = 16; // DT_FLOAT32
+        header.bitpix = 32;
+        header.scl_slope = 1.0;
+        header.scl_inter = 0.0;
+        header.cal_min = 0.0;
+        header.cal_max = 0.0;
+        header.intent_code = 0;
+        header.intent_p1 = 0.0;
+        header.intent_p2 = 0.0;
+        header.intent_p3 = 0.0;
+        header.intent_name = String::new();
+
+        Self {
+            header,
+            dims: self.dims,
+            affine: self.affine,
+            data,
+            frame_count: 1,
+            frame: 0,
+            series: Vec::new(),
+        }
+    }
+
+    /// Encodes the displayed frame as a `.nii` file with 32 bit float voxels.
+    pub fn to_nifti_bytes(&self) -> Vec<u8> {
+        let mut header = self.header.clone();
+        header.dim[0] = header.dim[0].min(3);
+        header.dim[4..].fill(1);
+        header.datatype = 16; // DT_FLOAT32
+        header.bitpix = 32;
+        // The header is followed by 4 bytes that announce that there are no extensions.
+        header.vox_offset = (NIFTI1_HEADER_SIZE + 4) as f32;
+        header.scl_slope = 1.0;
+        header.scl_inter = 0.0;
+
+        let mut bytes = header.to_bytes();
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.reserve(self.data.len() * 4);
+        for value in &self.data {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Returns the index of the displayed frame.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Displays another frame of a time series by copying it into `data`.
+    ///
+    /// Arguments:
+    ///
+    /// * `frame`: The index of the frame, which is clamped to `frame_count`.
+    pub fn set_frame(&mut self, frame: usize) {
+        let frame = frame.min(self.frame_count - 1);
+        if frame != self.frame {
+            let count = self.data.len();
+            self.data
+                .copy_from_slice(&self.series[frame * count..(frame + 1) * count]);
+            self.frame = frame;
+        }
+    }
+
+    /// Returns the intensities of a frame, x varies fastest.
+    ///
+    /// Arguments:
+    ///
+    /// * `frame`: The index of the frame, which has to be below `frame_count`.
+    pub fn frame_data(&self, frame: usize) -> &[f32] {
+        if self.series.is_empty() {
+            return &self.data;
+        }
+        let count = self.data.len();
+        &self.series[frame * count..(frame + 1) * count]
+    }
+
+    /// Returns the intensity at a voxel index.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`, `z`: The voxel index, which has to be within `dims`.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.data[x + self.dims[0] * (y + self.dims[1] * z)]
+    }
+
+    /// Samples the volume with trilinear interpolation.
+    ///
+    /// Positions outside of the volume are clamped to the closest voxel.
+    ///
+    /// Arguments:
+    ///
+    /// * `position`: The position in voxel coordinates.
+    pub fn sample_trilinear(&self, position: Vec3) -> f32 {
+        let max = Vec3::new(
+            (self.dims[0] - 1) as f32,
+            (self.dims[1] - 1) as f32,
+            (self.dims[2] - 1) as f32,
+        );
+        let p = position.clamp(Vec3::ZERO, max);
+        let p0 = p.floor();
+        let t = p - p0;
+        let (x0, y0, z0) = (p0.x as usize, p0.y as usize, p0.z as usize);
+        let x1 = (x0 + 1).min(self.dims[0] - 1);
+        let y1 = (y0 + 1).min(self.dims[1] - 1);
+        let z1 = (z0 + 1).min(self.dims[2] - 1);
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let c00 = lerp(self.get(x0, y0, z0), self.get(x1, y0, z0), t.x);
+        let c10 = lerp(self.get(x0, y1, z0), self.get(x1, y1, z0), t.x);
+        let c01 = lerp(self.get(x0, y0, z1), self.get(x1, y0, z1), t.x);
+        let c11 = lerp(self.get(x0, y1, z1), self.get(x1, y1, z1), t.x);
+        lerp(lerp(c00, c10, t.y), lerp(c01, c11, t.y), t.z)
+    }
+
+    /// Returns the continuous voxel coordinates of a position in world coordinates.
+    ///
+    /// Arguments:
+    ///
+    /// * `position`: The position in world coordinates in millimetres.
+    pub fn world_to_voxel(&self, position: Vec3) -> Vec3 {
+        self.affine.inverse().transform_point3(position)
+    }
+
+    /// Returns the index of the voxel closest to a position in voxel coordinates, or [None] if
+    /// the position is outside of the volume.
+    ///
+    /// Arguments:
+    ///
+    /// * `position`: The position in voxel coordinates, see [Volume::world_to_voxel].
+    pub fn voxel_index(&self, position: Vec3) -> Option<[usize; 3]> {
+        let index = position.round();
+        let inside = (0..3).all(|i| index[i] >= 0.0 && (index[i] as usize) < self.dims[i]);
+        inside.then_some([index.x as usize, index.y as usize, index.z as usize])
+    }
+
+    /// Returns whether the header marks the volume as label map, `NIFTI_INTENT_LABEL`.
+    pub fn is_label_map(&self) -> bool {
+        self.header.intent_code == 1002
+    }
+
+    /// Returns the smallest and largest intensity of the displayed frame.
+    pub fn min_max(&self) -> (f32, f32) {
+        finite_min_max(&self.data)
+    }
+
+    /// Returns the smallest and largest intensity of all frames of a time series.
+    ///
+    /// Unlike [Volume::min_max] this does not change while the frames are played.
+    pub fn series_min_max(&self) -> (f32, f32) {
+        if self.series.is_empty() {
+            self.min_max()
+        } else {
+            finite_min_max(&self.series)
+        }
+    }
+
+    /// Returns the physical size of the volume in millimetres.
+    ///
+    /// This is the size of the box proxy passed to [crate::render::state::State::update_box_size].
+    pub fn extent(&self) -> Vec3 {
+        Vec3::new(
+            self.dims[0] as f32,
+            self.dims[1] as f32,
+            self.dims[2] as f32,
+        ) * self.header.voxel_size()
+    }
+
+    /// Returns the transform from the box proxy to world coordinates in millimetres.
+    ///
+    /// The box is centered on the origin and has the size of [Volume::extent], so its corners
+    /// are mapped to the outer edges of the corner voxels.
+    pub fn box_transform(&self) -> Mat4 {
+        let center = Vec3::new(
+            self.dims[0] as f32 - 1.0,
+            self.dims[1] as f32 - 1.0,
+            self.dims[2] as f32 - 1.0,
+        ) / 2.0;
+        self.affine
+            * Mat4::from_translation(center)
+            * Mat4::from_scale(self.header.voxel_size().recip())
+    }
+}
+
+/// Returns the smallest and largest finite value.
+fn finite_min_max(values: &[f32]) -> (f32, f32) {
+    values
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        })
+}
+
+/// Decodes voxels of the header's data type and applies the intensity scaling.
+///
+/// Arguments:
+///
+/// * `header`: The header describing the data type, byte order and scaling.
+/// * `bytes`: The contents of the file.
+/// * `offset`: The byte offset of the first voxel.
+/// * `count`: The number of voxels to decode.
+pub(crate) fn decode_voxels(
+    header: &NiftiHeader,
+    bytes: &[u8],
+    offset: usize,
+    count: usize,
+) -> Result<Vec<f32>, VolumeError> {
+    let bytes_per_voxel = match header.datatype {
+        2 | 256 => 1,         // DT_UINT8, DT_INT8
+        4 | 512 => 2,         // DT_INT16, DT_UINT16
+        8 | 16 | 768 => 4,    // DT_INT32, DT_FLOAT32, DT_UINT32
+        64 => 8,              // DT_FLOAT64
+        code => return Err(VolumeError::UnsupportedDataType(code)),
+    };
+    let end = count
+        .checked_mul(bytes_per_voxel)
+        .and_then(|size| size.checked_add(offset))
+        .ok_or(VolumeError::Truncated)?;
+    let raw = bytes.get(offset..end).ok_or(VolumeError::Truncated)?;
+
+    let big_endian = header.byte_order == ByteOrder::BigEndian;
+    let (slope, intercept) = header.scaling();
+    let voxels = raw.chunks_exact(bytes_per_voxel).map(|chunk| {
+        let mut b = [0u8; 8];
+        b[..bytes_per_voxel].copy_from_slice(chunk);
+        if big_endian {
+            b[..bytes_per_voxel].reverse();
+        }
+        let value = match header.datatype {
+            2 => b[0] as f32,
+            256 => b[0] as i8 as f32,
+            4 => i16::from_le_bytes([b[0], b[1]]) as f32,
+            512 => u16::from_le_bytes([b[0], b[1]]) as f32,
+            8 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+            768 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+            16 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            _ => f64::from_le_bytes(b) as f32,
+        };
+        value * slope + intercept
+    });
+
+    Ok(voxels.collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use glam::{Mat4, Vec3};
+
+    use super::{Volume, VolumeError};
+
+    /// Creates a volume with 32 bit float voxels and an sform by decoding a `.nii` file.
+    ///
+    /// Arguments:
+    ///
+    /// * `dims`: The number of voxels along the x, y and z axes.
+    /// * `affine`: The transform from voxel indices to world coordinates.
+    /// * `data`: The intensities, x varies fastest. Several frames 2 seconds apart follow
+    ///   each other.
+    pub(crate) fn test_volume(dims: [usize; 3], affine: Mat4, data: &[f32]) -> Volume {
+        Volume::from_nifti_bytes(&nifti_bytes(dims, affine, data)).unwrap()
+    }
+
+    /// Encodes a `.nii` file with 32 bit float voxels, see [test_volume].
+    fn nifti_bytes(dims: [usize; 3], affine: Mat4, data: &[f32]) -> Vec<u8> {
+        let frames = data.len() / (dims[0] * dims[1] * dims[2]).max(1);
+        let mut bytes = vec![0; 352];
+        let mut put = |offset: usize, value: &[u8]| {
+            bytes[offset..offset + value.len()].copy_from_slice(value);
+        };
+        put(0, &348i32.to_le_bytes());
+        let dim = [
+            if frames > 1 { 4 } else { 3 },
+            dims[0] as i16,
+            dims[1] as i16,
+            dims[2] as i16,
+            frames.max(1) as i16,
+            1,
+            1,
+            1,
+        ];
+        for (i, d) in dim.iter().enumerate() {
+            put(40 + 2 * i, &d.to_le_bytes());
+        }
+        put(70, &16i16.to_le_bytes()); // DT_FLOAT32
+        put(72, &32i16.to_le_bytes());
+        let pixdim = [
+            1.0,
+            affine.x_axis.truncate().length(),
+            affine.y_axis.truncate().length(),
+            affine.z_axis.truncate().length(),
+            2.0,
+        ];
+        for (i, p) in pixdim.iter().enumerate() {
+            put(76 + 4 * i, &p.to_le_bytes());
+        }
+        put(108, &352.0f32.to_le_bytes());
+        put(112, &1.0f32.to_le_bytes());
+        put(123, &[2 | 8]); // NIFTI_UNITS_MM, NIFTI_UNITS_SEC
+        put(254, &2i16.to_le_bytes()); // NIFTI_XFORM_ALIGNED_ANAT
+        for (row, offset) in [280, 296, 312].into_iter().enumerate() {
+            for (i, value) in affine.row(row).to_array().iter().enumerate() {
+                put(offset + 4 * i, &value.to_le_bytes());
+            }
+        }
+        put(344, b"n+1\0");
+        for value in data {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// An oblique, mirrored transform with anisotropic voxels.
+    fn oblique_affine() -> Mat4 {
+        Mat4::from_translation(Vec3::new(-90.0, 126.0, -72.0))
+            * Mat4::from_rotation_z(0.3)
+            * Mat4::from_scale(Vec3::new(-2.0, 1.5, 3.0))
+    }
+
+    #[test]
+    fn float_voxels_with_sform() {
+        let dims = [4, 3, 2];
+        let data: Vec<f32> = (0..24).map(|i| i as f32 * 0.5 - 3.0).collect();
+        let volume = test_volume(dims, oblique_affine(), &data);
+
+        assert_eq!(volume.dims, dims);
+        assert_eq!(volume.data, data);
+        assert!(volume.affine.abs_diff_eq(oblique_affine(), 1e-4));
+        assert_eq!(volume.get(1, 2, 1), data[1 + 4 * (2 + 3)]);
+        assert_eq!(volume.min_max(), (-3.0, 8.5));
+        assert!(volume.extent().abs_diff_eq(Vec3::new(8.0, 4.5, 6.0), 1e-5));
+    }
+
+    #[test]
+    fn scaled_integer_voxels() {
+        let mut bytes = nifti_bytes([2, 1, 1], Mat4::IDENTITY, &[]);
+        bytes[70..72].copy_from_slice(&4i16.to_le_bytes()); // DT_INT16
+        bytes[72..74].copy_from_slice(&16i16.to_le_bytes());
+        bytes[112..116].copy_from_slice(&2.0f32.to_le_bytes());
+        bytes[116..120].copy_from_slice(&1.0f32.to_le_bytes());
+        bytes.extend_from_slice(&(-3i16).to_le_bytes());
+        bytes.extend_from_slice(&7i16.to_le_bytes());
+
+        let volume = Volume::from_nifti_bytes(&bytes).unwrap();
+        assert_eq!(volume.data, [-5.0, 15.0]);
+    }
+
+    #[test]
+    fn truncated_voxels() {
+        let mut bytes = nifti_bytes([2, 2, 2], Mat4::IDENTITY, &[0.0; 8]);
+        bytes.truncate(bytes.len() - 1);
+        assert_eq!(
+            Volume::from_nifti_bytes(&bytes).unwrap_err(),
+            VolumeError::Truncated
+        );
+        assert_eq!(
+            Volume::from_nifti_bytes(&bytes[..100]).unwrap_err(),
+            VolumeError::Truncated
+        );
+    }
+
+    #[test]
+    fn frames_of_a_time_series() {
+        let data: Vec<f32> = (0..24).map(|i| i as f32).collect();
+        let mut volume = test_volume([2, 2, 2], Mat4::IDENTITY, &data);
+        assert_eq!(volume.frame_count, 3);
+        assert_eq!(volume.header.repetition_time(), Some(2.0));
+        assert_eq!(volume.data, &data[..8]);
+        assert_eq!(volume.frame_data(1), &data[8..16]);
+        assert_eq!(volume.series_min_max(), (0.0, 23.0));
+
+        volume.set_frame(5);
+        assert_eq!(volume.frame(), 2);
+        assert_eq!(volume.get(1, 1, 1), 23.0);
+        assert_eq!(volume.min_max(), (16.0, 23.0));
+    }
+
+    #[test]
+    fn huge_dimensions_are_rejected() {
+        let mut bytes = nifti_bytes([1, 1, 1], Mat4::IDENTITY, &[0.0]);
+        for i in 0..5 {
+            let offset = 42 + 2 * i;
+            bytes[offset..offset + 2].copy_from_slice(&i16::MAX.to_le_bytes());
+        }
+        bytes[40..42].copy_from_slice(&4i16.to_le_bytes());
+        assert!(Volume::from_nifti_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn nifti_round_trip() {
+        let data: Vec<f32> = (0..24).map(|i| i as f32 * 0.5 - 3.0).collect();
+        let volume = test_volume([4, 3, 2], oblique_affine(), &data);
+
+        let decoded = Volume::from_nifti_bytes(&volume.to_nifti_bytes()).unwrap();
+        assert_eq!(decoded.dims, volume.dims);
+        assert_eq!(decoded.frame_count, 1);
+        assert_eq!(decoded.data, data);
+        assert!(decoded.affine.abs_diff_eq(volume.affine, 1e-6));
+    }
+
+    #[test]
+    fn nifti_round_trip_keeps_displayed_frame() {
+        let data: Vec<f32> = (0..24).map(|i| i as f32).collect();
+        let mut volume = test_volume([2, 2, 2], Mat4::IDENTITY, &data);
+        volume.set_frame(2);
+
+        let decoded = Volume::from_nifti_bytes(&volume.to_nifti_bytes()).unwrap();
+        assert_eq!(decoded.frame_count, 1);
+        assert_eq!(decoded.data, &data[16..]);
+    }
+
+    #[test]
+    fn gzip_compressed_nifti() {
+        let data: Vec<f32> = (0..24).map(|i| i as f32).collect();
+        let bytes = nifti_bytes([2, 2, 2], Mat4::IDENTITY, &data);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let volume = Volume::from_nifti_bytes(&compressed).unwrap();
+        assert_eq!(volume.frame_count, 3);
+        assert_eq!(volume.frame_data(2), &data[16..]);
+
+        let truncated = &compressed[..compressed.len() / 2];
+        assert_eq!(
+            Volume::from_nifti_bytes(truncated).unwrap_err(),
+            VolumeError::InvalidCompression
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use std::io::{self, Write};
+
+use glam::Vec3;
+
+use crate::render::vertex::Vertex;
+
+/// The file formats meshes can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// Binary STL, the most common format for 3D printing.
+    StlBinary,
+
+    /// ASCII STL.
+    StlAscii,
+
+    /// Wavefront OBJ with normals, texture coordinates and optional per-vertex colors.
+    Obj,
+
+    /// Binary little endian PLY with normals and optional per-vertex colors.
+    Ply,
+}
+
+impl MeshFormat {
+    /// Returns the format for a name or file extension like `"stl"`, `"obj"` or `"ply"`.
+    ///
+    /// `"stl"` selects binary STL, `"stl-ascii"` selects ASCII STL.
+    ///
+    /// Arguments:
+    ///
+    /// * `name`: The case insensitive name of the format.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim_start_matches('.').to_ascii_lowercase().as_str() {
+            "stl" => Some(Self::StlBinary),
+            "stl-ascii" => Some(Self::StlAscii),
+            "obj" => Some(Self::Obj),
+            "ply" => Some(Self::Ply),
+            _ => None,
+        }
+    }
+
+    /// Returns the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::StlBinary | Self::StlAscii => "stl",
+            Self::Obj => "obj",
+            Self::Ply => "ply",
+        }
+    }
+}
+
+/// Exports a mesh into a byte buffer.
+///
+/// Arguments:
+///
+/// * `format`: The file format.
+/// * `vertices`: The vertices of the mesh.
+/// * `indices`: The indices of the triangle list.
+/// * `colors`: Optional RGBA colors per vertex, used by [MeshFormat::Obj] and [MeshFormat::Ply].
+pub fn export_mesh(
+    format: MeshFormat,
+    vertices: &[Vertex],
+    indices: &[u32],
+    colors: Option<&[[u8; 4]]>,
+) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match format {
+        MeshFormat::StlBinary => write_stl_binary(&mut bytes, vertices, indices)?,
+        MeshFormat::StlAscii => write_stl_ascii(&mut bytes, vertices, indices)?,
+        MeshFormat::Obj => write_obj(&mut bytes, vertices, indices, colors)?,
+        MeshFormat::Ply => write_ply(&mut bytes, vertices, indices, colors)?,
+    }
+    Ok(bytes)
+}
+
+/// Writes a mesh as binary STL.
+///
+/// STL stores one normal per triangle, which is computed from the triangle's vertices.
+///
+/// Arguments:
+///
+/// * `writer`: The destination of the file contents.
+/// * `vertices`: The vertices of the mesh.
+/// * `indices`: The indices of the triangle list.
+pub fn write_stl_binary<W: Write>(
+    writer: &mut W,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> io::Result<()> {
+    let mut header = [0u8; 80];
+    let title = b"Binary STL exported by mri_cublet";
+    header[..title.len()].copy_from_slice(title);
+    writer.write_all(&header)?;
+    writer.write_all(&((indices.len() / 3) as u32).to_le_bytes())?;
+
+    for (normal, corners) in triangles(vertices, indices) {
+        for v in std::iter::once(normal).chain(corners) {
+            for c in v.to_array() {
+                writer.write_all(&c.to_le_bytes())?;
+            }
+        }
+        // The attribute byte count is unused.
+        writer.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
+
+/// Writes a mesh as ASCII STL.
+///
+/// Arguments:
+///
+/// * `writer`: The destination of the file contents.
+/// * `vertices`: The vertices of the mesh.
+/// * `indices`: The indices of the triangle list.
+pub fn write_stl_ascii<W: Write>(
+    writer: &mut W,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> io::Result<()> {
+    writeln!(writer, "solid mesh")?;
+    for (normal, corners) in triangles(vertices, indices) {
+        writeln!(
+            writer,
+            "  facet normal {} {} {}",
+            normal.x, normal.y, normal.z
+        )?;
+        writeln!(writer, "    outer loop")?;
+        for corner in corners {
+            writeln!(
+                writer,
+                "      vertex {} {} {}",
+                corner.x, corner.y, corner.z
+            )?;
+        }
+        writeln!(writer, "    endloop")?;
+        writeln!(writer, "  endfacet")?;
+    }
+    writeln!(writer, "endsolid mesh")
+}
+
+/// Writes a mesh as Wavefront OBJ with normals and texture coordinates.
+///
+/// Colors are appended to the vertex positions as `v x y z r g b`, which MeshLab, Blender and
+/// most slicers read. The alpha channel is dropped.
+///
+/// Arguments:
+///
+/// * `writer`: The destination of the file contents.
+/// * `vertices`: The vertices of the mesh.
+/// * `indices`: The indices of the triangle list.
+/// * `colors`: Optional RGBA colors, one per vertex.
+pub fn write_obj<W: Write>(
+    writer: &mut W,
+    vertices: &[Vertex],
+    indices: &[u32],
+    colors: Option<&[[u8; 4]]>,
+) -> io::Result<()> {
+    check_colors(vertices, colors)?;
+
+    writeln!(writer, "# Exported by mri_cublet")?;
+    for (i, v) in vertices.iter().enumerate() {
+        let [x, y, z] = v.position;
+        match colors {
+            Some(colors) => {
+                let [r, g, b, _] = colors[i].map(|c| c as f32 / 255.0);
+                writeln!(writer, "v {} {} {} {} {} {}", x, y, z, r, g, b)?;
+            }
+            None => writeln!(writer, "v {} {} {}", x, y, z)?,
+        }
+    }
+    for v in vertices {
+        writeln!(writer, "vt {} {}", v.tex_coords[0], v.tex_coords[1])?;
+    }
+    for v in vertices {
+        writeln!(writer, "vn {} {} {}", v.normal[0], v.normal[1], v.normal[2])?;
+    }
+    // OBJ indices start at one.
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+        writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+    }
+    Ok(())
+}
+
+/// Writes a mesh as binary little endian PLY.
+///
+/// Arguments:
+///
+/// * `writer`: The destination of the file contents.
+/// * `vertices`: The vertices of the mesh.
+/// * `indices`: The indices of the triangle list.
+/// * `colors`: Optional RGBA colors, one per vertex.
+pub fn write_ply<W: Write>(
+    writer: &mut W,
+    vertices: &[Vertex],
+    indices: &[u32],
+    colors: Option<&[[u8; 4]]>,
+) -> io::Result<()> {
+    check_colors(vertices, colors)?;
+
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format binary_little_endian 1.0")?;
+    writeln!(writer, "comment Exported by mri_cublet")?;
+    writeln!(writer, "element vertex {}", vertices.len())?;
+    for property in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(writer, "property float {}", property)?;
+    }
+    if colors.is_some() {
+        for property in ["red", "green", "blue", "alpha"] {
+            writeln!(writer, "property uchar {}", property)?;
+        }
+    }
+    writeln!(writer, "element face {}", indices.len() / 3)?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for (i, v) in vertices.iter().enumerate() {
+        for c in v.position.iter().chain(v.normal.iter()) {
+            writer.write_all(&c.to_le_bytes())?;
+        }
+        if let Some(colors) = colors {
+            writer.write_all(&colors[i])?;
+        }
+    }
+    for triangle in indices.chunks_exact(3) {
+        writer.write_all(&[3])?;
+        for index in triangle {
+            writer.write_all(&index.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Iterates over the triangles of a mesh, yielding the face normal and the three corners.
+fn triangles<'a>(
+    vertices: &'a [Vertex],
+    indices: &'a [u32],
+) -> impl Iterator<Item = (Vec3, [Vec3; 3])> + 'a {
+    indices.chunks_exact(3).map(move |triangle| {
+        let corners = [
+            Vec3::from(vertices[triangle[0] as usize].position),
+            Vec3::from(vertices[triangle[1] as usize].position),
+            Vec3::from(vertices[triangle[2] as usize].position),
+        ];
+        let normal = (corners[1] - corners[0])
+            .cross(corners[2] - corners[0])
+            .normalize_or_zero();
+        (normal, corners)
+    })
+}
+
+/// Returns an error if the colors are not one per vertex.
+fn check_colors(vertices: &[Vertex], colors: Option<&[[u8; 4]]>) -> io::Result<()> {
+    match colors {
+        Some(colors) if colors.len() != vertices.len() => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The number of colors does not match the number of vertices.",
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use glam::Vec3;
+
+    use super::{export_mesh, MeshFormat};
+    use crate::{loaders::mesh::load_mesh, render::vertex::Vertex};
+
+    /// Returns a closed tetrahedron whose normals point away from its center.
+    fn tetrahedron() -> (Vec<Vertex>, Vec<u32>) {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [10.5, 0.0, 0.0],
+            [0.0, 7.25, 0.0],
+            [0.0, 0.0, 3.125],
+        ];
+        let center = Vec3::new(10.5, 7.25, 3.125) / 4.0;
+        let vertices = positions
+            .iter()
+            .map(|&position| Vertex {
+                position,
+                tex_coords: [0.0; 2],
+                normal: (Vec3::from(position) - center).normalize().to_array(),
+            })
+            .collect();
+        (vertices, vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3])
+    }
+
+    /// Returns the corner positions of every triangle.
+    fn triangles(vertices: &[Vertex], indices: &[u32]) -> Vec<[[f32; 3]; 3]> {
+        indices
+            .chunks_exact(3)
+            .map(|t| [0, 1, 2].map(|i| vertices[t[i] as usize].position))
+            .collect()
+    }
+
+    const FORMATS: [MeshFormat; 4] = [
+        MeshFormat::StlBinary,
+        MeshFormat::StlAscii,
+        MeshFormat::Obj,
+        MeshFormat::Ply,
+    ];
+
+    #[test]
+    fn binary_stl() {
+        let (vertices, indices) = tetrahedron();
+        let bytes = export_mesh(MeshFormat::StlBinary, &vertices, &indices, None).unwrap();
+        assert_eq!(bytes.len(), 84 + 4 * 50);
+        assert_eq!(bytes[80..84], 4u32.to_le_bytes());
+        // The first triangle lies in the z = 0 plane and faces away from the tetrahedron.
+        let normal: Vec<f32> = bytes[84..96]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(normal, [0.0, 0.0, -1.0]);
+        assert_eq!(bytes[132..134], [0, 0]);
+    }
+
+    #[test]
+    fn ascii_stl() {
+        let (vertices, indices) = tetrahedron();
+        let bytes = export_mesh(MeshFormat::StlAscii, &vertices, &indices, None).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("solid mesh\n  facet normal 0 0 -1\n    outer loop\n"));
+        assert!(text.contains("\n      vertex 0 7.25 0\n"));
+        assert_eq!(text.matches("endfacet").count(), 4);
+        assert!(text.ends_with("endsolid mesh\n"));
+    }
+
+    #[test]
+    fn obj() {
+        let (vertices, indices) = tetrahedron();
+        let bytes = export_mesh(MeshFormat::Obj, &vertices, &indices, None).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains("\nv 10.5 0 0\n"));
+        assert_eq!(text.matches("vt 0 0\n").count(), 4);
+        assert_eq!(text.matches("\nvn ").count(), 4);
+        // OBJ indices start at one.
+        assert!(text.contains("\nf 1/1/1 3/3/3 2/2/2\n"));
+        assert!(text.ends_with("f 2/2/2 3/3/3 4/4/4\n"));
+    }
+
+    #[test]
+    fn ply_with_colors() {
+        let (vertices, indices) = tetrahedron();
+        let colors = [[255, 0, 0, 255]; 4];
+        let bytes = export_mesh(MeshFormat::Ply, &vertices, &indices, Some(&colors)).unwrap();
+        let header_end = b"end_header\n";
+        let body = bytes
+            .windows(header_end.len())
+            .position(|w| w == header_end)
+            .unwrap()
+            + header_end.len();
+        let header = String::from_utf8_lossy(&bytes[..body]);
+        assert!(header.contains("element vertex 4\n"));
+        assert!(header.contains("property uchar red\n"));
+        assert!(header.contains("element face 4\n"));
+        // Every vertex has six floats and four color channels, every face a count and three
+        // indices.
+        assert_eq!(bytes.len(), body + 4 * (6 * 4 + 4) + 4 * (1 + 3 * 4));
+        assert_eq!(bytes[body + 24..body + 28], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn export_and_load_round_trip() {
+        let (vertices, indices) = tetrahedron();
+        for format in FORMATS {
+            let bytes = export_mesh(format, &vertices, &indices, None).unwrap();
+            let file_name = format!("mesh.{}", format.extension());
+            let (loaded, loaded_indices) = load_mesh(&bytes, &file_name).unwrap();
+            assert_eq!(
+                triangles(&loaded, &loaded_indices),
+                triangles(&vertices, &indices),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn obj_and_ply_keep_normals() {
+        let (vertices, indices) = tetrahedron();
+        for format in [MeshFormat::Obj, MeshFormat::Ply] {
+            let bytes = export_mesh(format, &vertices, &indices, None).unwrap();
+            let file_name = format!("mesh.{}", format.extension());
+            let (loaded, loaded_indices) = load_mesh(&bytes, &file_name).unwrap();
+            // The vertices may be reordered, so the normals are compared at the same corners.
+            for (&a, &b) in loaded_indices.iter().zip(&indices) {
+                let normal = Vec3::from(loaded[a as usize].normal);
+                assert!(normal.abs_diff_eq(Vec3::from(vertices[b as usize].normal), 1e-6));
+            }
+        }
+    }
+
+    #[test]
+    fn colors_are_exported_to_obj_and_ply() {
+        let (vertices, indices) = tetrahedron();
+        let colors = [
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [255; 4],
+        ];
+
+        let obj = export_mesh(MeshFormat::Obj, &vertices, &indices, Some(&colors)).unwrap();
+        let text = String::from_utf8(obj.clone()).unwrap();
+        assert!(text.contains("\nv 0 0 0 1 0 0\n"));
+        assert!(text.contains("\nv 0 0 3.125 1 1 1\n"));
+        let (loaded, loaded_indices) = load_mesh(&obj, "mesh.obj").unwrap();
+        assert_eq!(
+            triangles(&loaded, &loaded_indices),
+            triangles(&vertices, &indices)
+        );
+
+        let ply = export_mesh(MeshFormat::Ply, &vertices, &indices, Some(&colors)).unwrap();
+        let (loaded, loaded_indices) = load_mesh(&ply, "mesh.ply").unwrap();
+        assert_eq!(
+            triangles(&loaded, &loaded_indices),
+            triangles(&vertices, &indices)
+        );
+    }
+
+    #[test]
+    fn color_count_has_to_match() {
+        let (vertices, indices) = tetrahedron();
+        for format in [MeshFormat::Obj, MeshFormat::Ply] {
+            let error = export_mesh(format, &vertices, &indices, Some(&[[0; 4]])).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!(MeshFormat::from_name(".PLY"), Some(MeshFormat::Ply));
+        assert_eq!(
+            MeshFormat::from_name("stl-ascii"),
+            Some(MeshFormat::StlAscii)
+        );
+        assert_eq!(MeshFormat::from_name("gii"), None);
+        assert_eq!(MeshFormat::StlAscii.extension(), "stl");
+    }
+}
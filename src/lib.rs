@@ -5,13 +5,29 @@ pub mod extras {
 }
 pub mod render {
     pub mod camera;
+    pub mod cine;
+    pub mod cpu;
+    pub mod error;
     pub mod geometry {
         pub mod r#box;
+        pub mod crosshair;
+        pub mod cublets;
+        pub mod isosurface;
+        pub mod letters;
+        pub mod outline;
+        pub mod primitives;
+        pub mod slice;
     }
+    pub mod gizmo;
+    pub mod label_surfaces;
+    pub mod layers;
     pub(crate) mod light;
+    pub mod overlay;
+    pub mod scene;
+    pub mod settings;
     pub mod state;
     pub(crate) mod texture;
-    pub(crate) mod vertex;
+    pub mod vertex;
 }
 
 pub mod platform_impl {
@@ -21,15 +37,31 @@ pub mod platform_impl {
     }
 }
 
+pub mod exporters {
+    pub mod mesh;
+}
+
 pub mod loaders {
     pub mod json_loader;
+    pub mod mesh;
+    pub mod overlay;
 }
 
 #[cfg(target_arch = "wasm32")]
 pub mod utils;
 
-#[cfg(target_arch = "wasm32")]
 pub mod data {
+    pub mod cluster;
+    pub mod header;
+    pub mod label_map;
+    pub mod lookup_table;
+    #[cfg(target_arch = "wasm32")]
     pub mod nifti;
+    #[cfg(target_arch = "wasm32")]
     pub mod mri_image;
+    pub mod resample;
+    pub mod statistic;
+    pub mod time_course;
+    pub mod transform;
+    pub mod volume;
 }
\ No newline at end of file
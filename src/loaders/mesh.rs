@@ -0,0 +1,1036 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+
+use base64::Engine;
+use glam::{Mat4, Vec3};
+
+use crate::render::vertex::Vertex;
+
+/// The errors that can occur while loading a mesh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MeshError {
+    /// The file name does not have the extension of a supported format.
+    UnknownFormat(String),
+
+    /// The file ends before the mesh is complete.
+    Truncated,
+
+    /// The file does not follow its format.
+    Invalid(String),
+
+    /// The file uses a feature of its format that is not supported.
+    Unsupported(String),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat(name) => write!(f, "The format of {} is not supported.", name),
+            Self::Truncated => write!(f, "The file is truncated."),
+            Self::Invalid(reason) => write!(f, "The file is invalid: {}", reason),
+            Self::Unsupported(feature) => {
+                write!(f, "The file uses {}, which is not supported.", feature)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// The file formats meshes can be loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceFormat {
+    /// GIfTI surfaces like `lh.pial.surf.gii`.
+    Gifti,
+
+    /// FreeSurfer binary surfaces like `lh.pial` or `rh.white`.
+    FreeSurfer,
+
+    /// Wavefront OBJ.
+    Obj,
+
+    /// Binary or ASCII STL.
+    Stl,
+
+    /// Binary or ASCII PLY.
+    Ply,
+}
+
+/// The names of FreeSurfer surfaces, which have no file extension.
+const FREESURFER_SURFACES: [&str; 9] = [
+    "pial",
+    "white",
+    "inflated",
+    "orig",
+    "smoothwm",
+    "sphere",
+    "sphere.reg",
+    "midthickness",
+    "graymid",
+];
+
+impl SurfaceFormat {
+    /// Returns the format of a file based on its name.
+    ///
+    /// Arguments:
+    ///
+    /// * `file_name`: The name or path of the file.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let name = file_name
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(file_name)
+            .to_ascii_lowercase();
+        if name.ends_with(".gii") {
+            return Some(Self::Gifti);
+        }
+        match name.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("obj") => return Some(Self::Obj),
+            Some("stl") => return Some(Self::Stl),
+            Some("ply") => return Some(Self::Ply),
+            _ => {}
+        }
+        // FreeSurfer names surfaces after the hemisphere, like `lh.pial`.
+        match name.split_once('.') {
+            Some(("lh" | "rh", surface)) if FREESURFER_SURFACES.contains(&surface) => {
+                Some(Self::FreeSurfer)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Loads a mesh, picking the format from the file name.
+///
+/// Positions are in world coordinates in millimetres. Normals are computed when the file lacks them.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+/// * `file_name`: The name or path of the file.
+pub fn load_mesh(bytes: &[u8], file_name: &str) -> Result<(Vec<Vertex>, Vec<u32>), MeshError> {
+    // FreeSurfer surfaces can be renamed freely, so their magic number is checked as well.
+    let format = if bytes.starts_with(&FREESURFER_TRIANGLE_MAGIC) {
+        SurfaceFormat::FreeSurfer
+    } else {
+        SurfaceFormat::from_file_name(file_name)
+            .ok_or_else(|| MeshError::UnknownFormat(file_name.to_string()))?
+    };
+    match format {
+        SurfaceFormat::Gifti => load_gifti(bytes),
+        SurfaceFormat::FreeSurfer => load_freesurfer(bytes),
+        SurfaceFormat::Obj => load_obj(bytes),
+        SurfaceFormat::Stl => load_stl(bytes),
+        SurfaceFormat::Ply => load_ply(bytes),
+    }
+}
+
+/// Computes smooth vertex normals by summing the area weighted normals of adjacent triangles.
+///
+/// Arguments:
+///
+/// * `vertices`: The vertices whose normals are replaced.
+/// * `indices`: The indices of the triangle list.
+pub fn compute_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+        let pa = Vec3::from(vertices[a].position);
+        // The cross product is proportional to the area of the triangle.
+        let normal =
+            (Vec3::from(vertices[b].position) - pa).cross(Vec3::from(vertices[c].position) - pa);
+        normals[a] += normal;
+        normals[b] += normal;
+        normals[c] += normal;
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal.normalize_or_zero().to_array();
+    }
+}
+
+/// Builds the vertices of a mesh, projecting the bounding box onto the texture when there are no
+/// texture coordinates.
+///
+/// Arguments:
+///
+/// * `positions`: The positions of the vertices.
+/// * `normals`: The normals of the vertices, which are computed when missing.
+/// * `indices`: The indices of the triangle list.
+fn build_mesh(
+    positions: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+    indices: Vec<u32>,
+) -> Result<(Vec<Vertex>, Vec<u32>), MeshError> {
+    if let Some(&index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+        return Err(MeshError::Invalid(format!(
+            "the vertex index {} is out of range",
+            index
+        )));
+    }
+
+    let min = positions
+        .iter()
+        .fold(Vec3::splat(f32::MAX), |a, &b| a.min(b));
+    let max = positions
+        .iter()
+        .fold(Vec3::splat(f32::MIN), |a, &b| a.max(b));
+    let size = (max - min).max(Vec3::splat(f32::EPSILON));
+
+    let has_normals = normals.as_ref().is_some_and(|n| n.len() == positions.len());
+    let mut vertices: Vec<Vertex> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, &position)| {
+            let tex_coords = (position - min) / size;
+            Vertex {
+                position: position.to_array(),
+                tex_coords: [tex_coords.x, tex_coords.y],
+                normal: match &normals {
+                    Some(normals) if has_normals => normals[i].normalize_or_zero().to_array(),
+                    _ => [0.0; 3],
+                },
+            }
+        })
+        .collect();
+    if !has_normals {
+        compute_normals(&mut vertices, &indices);
+    }
+    Ok((vertices, indices))
+}
+
+/// The magic number of FreeSurfer triangle surfaces.
+const FREESURFER_TRIANGLE_MAGIC: [u8; 3] = [0xff, 0xff, 0xfe];
+
+/// The magic number of FreeSurfer quadrangle surfaces.
+const FREESURFER_QUAD_MAGIC: [u8; 3] = [0xff, 0xff, 0xff];
+
+/// Loads a FreeSurfer binary triangle surface.
+///
+/// FreeSurfer stores positions in tkr-RAS coordinates. When the file includes the geometry of the
+/// volume it was created from, the `c_ras` offset is added so that the surface lines up with the
+/// volume in scanner coordinates.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_freesurfer(bytes: &[u8]) -> Result<(Vec<Vertex>, Vec<u32>), MeshError> {
+    if bytes.starts_with(&FREESURFER_QUAD_MAGIC) {
+        return Err(MeshError::Unsupported("quadrangle surfaces".to_string()));
+    }
+    if !bytes.starts_with(&FREESURFER_TRIANGLE_MAGIC) {
+        return Err(MeshError::Invalid(
+            "the FreeSurfer magic number is missing".to_string(),
+        ));
+    }
+
+    // The creator comment ends with two line feeds.
+    let comment_end = bytes[3..]
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .ok_or(MeshError::Truncated)?;
+    let mut reader = ByteReader::new(bytes, 3 + comment_end + 2, false);
+
+    let vertex_count = usize::try_from(reader.i32()?)
+        .map_err(|_| MeshError::Invalid("the vertex count is negative".to_string()))?;
+    let face_count = usize::try_from(reader.i32()?)
+        .map_err(|_| MeshError::Invalid("the face count is negative".to_string()))?;
+    // Every vertex and every face takes 12 bytes, so the counts are checked before allocating.
+    let size = vertex_count
+        .checked_add(face_count)
+        .and_then(|count| count.checked_mul(12));
+    if size.is_none_or(|size| size > reader.remaining()) {
+        return Err(MeshError::Truncated);
+    }
+    let mut positions = Vec::with_capacity(vertex_count);
+    for _ in 0..vertex_count {
+        positions.push(Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?));
+    }
+    let mut indices = Vec::with_capacity(face_count * 3);
+    for _ in 0..face_count * 3 {
+        indices.push(reader.i32()? as u32);
+    }
+
+    if let Some(c_ras) = freesurfer_c_ras(&bytes[reader.offset..]) {
+        for position in positions.iter_mut() {
+            *position += c_ras;
+        }
+    }
+
+    build_mesh(positions, None, indices)
+}
+
+/// Finds the `cras` entry in the volume geometry that trails a FreeSurfer surface.
+///
+/// Arguments:
+///
+/// * `trailer`: The bytes after the faces.
+fn freesurfer_c_ras(trailer: &[u8]) -> Option<Vec3> {
+    let text = String::from_utf8_lossy(trailer);
+    // The entries follow the binary tag of the volume geometry.
+    let valid = text.lines().any(|line| match line.split_once('=') {
+        Some((key, value)) => {
+            key.trim_end().ends_with("valid") && value.trim_start().starts_with('1')
+        }
+        None => false,
+    });
+    if !valid {
+        return None;
+    }
+    let line = text
+        .lines()
+        .find(|line| line.trim_start().starts_with("cras"))?;
+    let values: Vec<f32> = line
+        .split('=')
+        .nth(1)?
+        .split_whitespace()
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    match values[..] {
+        [x, y, z, ..] => Some(Vec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+/// Loads a GIfTI surface with its points and triangles.
+///
+/// Points are transformed with the first coordinate system transform of the point set. ASCII,
+/// base64 and gzipped base64 encodings are supported, external files are not.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_gifti(bytes: &[u8]) -> Result<(Vec<Vertex>, Vec<u32>), MeshError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| MeshError::Invalid("the XML is not UTF-8".to_string()))?;
+    let document =
+        roxmltree::Document::parse(text).map_err(|e| MeshError::Invalid(e.to_string()))?;
+
+    let mut positions = None;
+    let mut normals = None;
+    let mut indices = None;
+    for array in document
+        .descendants()
+        .filter(|n| n.has_tag_name("DataArray"))
+    {
+        match array.attribute("Intent") {
+            Some("NIFTI_INTENT_POINTSET") if positions.is_none() => {
+                let values = gifti_values(&array)?;
+                let transform = gifti_transform(&array)?;
+                positions = Some(
+                    values
+                        .chunks_exact(3)
+                        .map(|p| {
+                            transform.transform_point3(Vec3::new(
+                                p[0] as f32,
+                                p[1] as f32,
+                                p[2] as f32,
+                            ))
+                        })
+                        .collect::<Vec<_>>(),
+                );
+            }
+            Some("NIFTI_INTENT_VECTOR") if normals.is_none() => {
+                let values = gifti_values(&array)?;
+                normals = Some(
+                    values
+                        .chunks_exact(3)
+                        .map(|n| Vec3::new(n[0] as f32, n[1] as f32, n[2] as f32))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            Some("NIFTI_INTENT_TRIANGLE") if indices.is_none() => {
+                indices = Some(
+                    gifti_values(&array)?
+                        .into_iter()
+                        .map(|i| i as u32)
+                        .collect::<Vec<_>>(),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let positions =
+        positions.ok_or_else(|| MeshError::Invalid("the point set is missing".to_string()))?;
+    let indices =
+        indices.ok_or_else(|| MeshError::Invalid("the triangles are missing".to_string()))?;
+    build_mesh(positions, normals, indices)
+}
+
+/// Decodes the values of a GIfTI data array in row major order.
+///
+/// Arguments:
+///
+/// * `array`: The `DataArray` element.
+pub(super) fn gifti_values(array: &roxmltree::Node) -> Result<Vec<f64>, MeshError> {
+    let attribute = |name: &str| array.attribute(name).unwrap_or_default();
+    let dimensionality: usize = attribute("Dimensionality").parse().unwrap_or(1);
+    let dims: Vec<usize> = (0..dimensionality)
+        .map(|i| {
+            array
+                .attribute(format!("Dim{}", i).as_str())
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(1)
+        })
+        .collect();
+    let count: usize = dims.iter().product();
+
+    let data = array
+        .children()
+        .find(|n| n.has_tag_name("Data"))
+        .and_then(|n| n.text())
+        .unwrap_or_default();
+
+    let mut values = match attribute("Encoding") {
+        "ASCII" => data
+            .split_whitespace()
+            .map(|v| {
+                v.parse::<f64>()
+                    .map_err(|_| MeshError::Invalid(format!("{} is not a number", v)))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        encoding @ ("Base64Binary" | "GZipBase64Binary") => {
+            let compact: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+            let mut raw = base64::engine::general_purpose::STANDARD
+                .decode(compact)
+                .map_err(|e| MeshError::Invalid(e.to_string()))?;
+            if encoding == "GZipBase64Binary" {
+                raw = inflate(&raw)?;
+            }
+            let big_endian = attribute("Endian") == "BigEndian";
+            decode_gifti_binary(&raw, attribute("DataType"), big_endian)?
+        }
+        encoding => {
+            return Err(MeshError::Unsupported(format!(
+                "the GIfTI encoding {}",
+                encoding
+            )))
+        }
+    };
+    if values.len() < count {
+        return Err(MeshError::Truncated);
+    }
+    values.truncate(count);
+
+    // Column major arrays store all values of the first column before the second column.
+    if attribute("ArrayIndexingOrder") == "ColumnMajorOrder" && dims.len() == 2 {
+        let (rows, columns) = (dims[0], dims[1]);
+        values = (0..count)
+            .map(|i| values[(i % columns) * rows + i / columns])
+            .collect();
+    }
+    Ok(values)
+}
+
+/// Decodes the binary values of a GIfTI data array.
+///
+/// Arguments:
+///
+/// * `raw`: The decoded bytes.
+/// * `data_type`: The `DataType` attribute of the array.
+/// * `big_endian`: Whether the values are big endian.
+fn decode_gifti_binary(
+    raw: &[u8],
+    data_type: &str,
+    big_endian: bool,
+) -> Result<Vec<f64>, MeshError> {
+    let size = match data_type {
+        "NIFTI_TYPE_UINT8" => 1,
+        "NIFTI_TYPE_INT32" | "NIFTI_TYPE_FLOAT32" => 4,
+        _ => {
+            return Err(MeshError::Unsupported(format!(
+                "the GIfTI data type {}",
+                data_type
+            )))
+        }
+    };
+    Ok(raw
+        .chunks_exact(size)
+        .map(|chunk| {
+            let mut b = [0u8; 4];
+            b[..size].copy_from_slice(chunk);
+            if big_endian {
+                b[..size].reverse();
+            }
+            match data_type {
+                "NIFTI_TYPE_UINT8" => b[0] as f64,
+                "NIFTI_TYPE_INT32" => i32::from_le_bytes(b) as f64,
+                _ => f32::from_le_bytes(b) as f64,
+            }
+        })
+        .collect())
+}
+
+/// Returns the first coordinate system transform of a GIfTI data array, or the identity.
+///
+/// Arguments:
+///
+/// * `array`: The `DataArray` element.
+fn gifti_transform(array: &roxmltree::Node) -> Result<Mat4, MeshError> {
+    let matrix = array
+        .descendants()
+        .find(|n| n.has_tag_name("MatrixData"))
+        .and_then(|n| n.text());
+    let Some(matrix) = matrix else {
+        return Ok(Mat4::IDENTITY);
+    };
+    let values: Vec<f32> = matrix
+        .split_whitespace()
+        .map(|v| {
+            v.parse()
+                .map_err(|_| MeshError::Invalid(format!("{} is not a number", v)))
+        })
+        .collect::<Result<_, _>>()?;
+    if values.len() != 16 {
+        return Err(MeshError::Invalid(
+            "the transform does not have 16 values".to_string(),
+        ));
+    }
+    // GIfTI stores the matrix in row major order.
+    Ok(Mat4::from_cols_slice(&values).transpose())
+}
+
+/// Decompresses zlib or gzip data.
+///
+/// Arguments:
+///
+/// * `compressed`: The compressed bytes.
+pub(super) fn inflate(compressed: &[u8]) -> Result<Vec<u8>, MeshError> {
+    let mut raw = Vec::new();
+    let result = if compressed.starts_with(&[0x1f, 0x8b]) {
+        flate2::read::GzDecoder::new(compressed).read_to_end(&mut raw)
+    } else {
+        flate2::read::ZlibDecoder::new(compressed).read_to_end(&mut raw)
+    };
+    result.map_err(|e| MeshError::Invalid(e.to_string()))?;
+    Ok(raw)
+}
+
+/// Loads a Wavefront OBJ mesh, triangulating polygons as fans.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_obj(bytes: &[u8]) -> Result<(Vec<Vertex>, Vec<u32>), MeshError> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut positions = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut normals = Vec::new();
+
+    // Every distinct combination of position, texture coordinates and normal becomes a vertex.
+    let mut corners: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices = Vec::new();
+    let mut missing_normals = false;
+
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => positions.push(parse_vec3(&mut tokens)?),
+            Some("vt") => {
+                let u = parse_f32(tokens.next())?;
+                let v = tokens.next().map_or(Ok(0.0), |v| parse_f32(Some(v)))?;
+                tex_coords.push([u, v]);
+            }
+            Some("vn") => normals.push(parse_vec3(&mut tokens)?),
+            Some("f") => {
+                let mut face = Vec::new();
+                for corner in tokens {
+                    let mut parts = corner.split('/');
+                    let position = obj_index(parts.next(), positions.len())?.ok_or_else(|| {
+                        MeshError::Invalid(format!("the face corner {} has no position", corner))
+                    })?;
+                    let tex_coord = obj_index(parts.next(), tex_coords.len())?;
+                    let normal = obj_index(parts.next(), normals.len())?;
+                    missing_normals |= normal.is_none();
+
+                    let key = (position, tex_coord, normal);
+                    let index = *corners.entry(key).or_insert_with(|| {
+                        vertices.push(Vertex {
+                            position: positions[position].to_array(),
+                            tex_coords: tex_coord.map_or([0.0; 2], |t| tex_coords[t]),
+                            normal: normal
+                                .map_or([0.0; 3], |n| normals[n].normalize_or_zero().to_array()),
+                        });
+                        (vertices.len() - 1) as u32
+                    });
+                    face.push(index);
+                }
+                for i in 1..face.len().saturating_sub(1) {
+                    indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if tex_coords.is_empty() || missing_normals {
+        let positions = vertices.iter().map(|v| Vec3::from(v.position)).collect();
+        let normals =
+            (!missing_normals).then(|| vertices.iter().map(|v| Vec3::from(v.normal)).collect());
+        let (mut built, indices) = build_mesh(positions, normals, indices)?;
+        if !tex_coords.is_empty() {
+            for (vertex, original) in built.iter_mut().zip(&vertices) {
+                vertex.tex_coords = original.tex_coords;
+            }
+        }
+        return Ok((built, indices));
+    }
+    Ok((vertices, indices))
+}
+
+/// Resolves a one based or negative OBJ index.
+///
+/// Arguments:
+///
+/// * `token`: The index, which may be missing or empty.
+/// * `len`: The number of elements defined so far.
+fn obj_index(token: Option<&str>, len: usize) -> Result<Option<usize>, MeshError> {
+    let Some(token) = token.filter(|t| !t.is_empty()) else {
+        return Ok(None);
+    };
+    let index: i64 = token
+        .parse()
+        .map_err(|_| MeshError::Invalid(format!("{} is not an index", token)))?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(MeshError::Invalid(format!(
+            "the index {} is out of range",
+            index
+        )));
+    }
+    Ok(Some(resolved as usize))
+}
+
+/// Loads a binary or ASCII STL mesh.
+///
+/// STL stores every triangle separately, so corners with the same position are merged to get smooth
+/// normals.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_stl(bytes: &[u8]) -> Result<(Vec<Vertex>, Vec<u32>), MeshError> {
+    let mut corners = Vec::new();
+
+    // ASCII files start with `solid`, but so do some binary files, so the size is checked first.
+    let binary_count = bytes
+        .get(80..84)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let binary_size = binary_count
+        .and_then(|count| count.checked_mul(50))
+        .and_then(|size| size.checked_add(84));
+    if binary_size == Some(bytes.len()) {
+        for triangle in bytes[84..].chunks_exact(50) {
+            let mut reader = ByteReader::new(triangle, 12, true);
+            for _ in 0..3 {
+                corners.push(Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?));
+            }
+        }
+    } else if bytes.trim_ascii_start().starts_with(b"solid") {
+        let text = String::from_utf8_lossy(bytes);
+        for line in text.lines() {
+            let mut tokens = line.split_whitespace();
+            if tokens.next() == Some("vertex") {
+                corners.push(parse_vec3(&mut tokens)?);
+            }
+        }
+        if corners.len() % 3 != 0 {
+            return Err(MeshError::Truncated);
+        }
+    } else {
+        return Err(MeshError::Invalid(
+            "it is neither ASCII nor binary STL".to_string(),
+        ));
+    }
+
+    let mut positions = Vec::new();
+    let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+    let indices = corners
+        .iter()
+        .map(|corner| {
+            *welded
+                .entry(corner.to_array().map(f32::to_bits))
+                .or_insert_with(|| {
+                    positions.push(*corner);
+                    (positions.len() - 1) as u32
+                })
+        })
+        .collect();
+    build_mesh(positions, None, indices)
+}
+
+/// The scalar types of PLY properties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<Self, MeshError> {
+        match name {
+            "char" | "int8" => Ok(Self::I8),
+            "uchar" | "uint8" => Ok(Self::U8),
+            "short" | "int16" => Ok(Self::I16),
+            "ushort" | "uint16" => Ok(Self::U16),
+            "int" | "int32" => Ok(Self::I32),
+            "uint" | "uint32" => Ok(Self::U32),
+            "float" | "float32" => Ok(Self::F32),
+            "double" | "float64" => Ok(Self::F64),
+            _ => Err(MeshError::Invalid(format!("{} is not a PLY type", name))),
+        }
+    }
+}
+
+/// A property of a PLY element, which is a list when it has a count type.
+struct PlyProperty {
+    name: String,
+    count_type: Option<PlyType>,
+    value_type: PlyType,
+}
+
+/// An element of a PLY file, like the vertices or the faces.
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// Reads PLY values from either the ASCII or the binary body.
+enum PlyReader<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary(ByteReader<'a>),
+}
+
+impl PlyReader<'_> {
+    fn read(&mut self, ty: PlyType) -> Result<f64, MeshError> {
+        match self {
+            Self::Ascii(tokens) => tokens
+                .next()
+                .ok_or(MeshError::Truncated)?
+                .parse()
+                .map_err(|_| MeshError::Invalid("a PLY value is not a number".to_string())),
+            Self::Binary(reader) => Ok(match ty {
+                PlyType::I8 => reader.bytes::<1>()?[0] as i8 as f64,
+                PlyType::U8 => reader.bytes::<1>()?[0] as f64,
+                PlyType::I16 => i16::from_le_bytes(reader.bytes()?) as f64,
+                PlyType::U16 => u16::from_le_bytes(reader.bytes()?) as f64,
+                PlyType::I32 => reader.i32()? as f64,
+                PlyType::U32 => u32::from_le_bytes(reader.bytes()?) as f64,
+                PlyType::F32 => reader.f32()? as f64,
+                PlyType::F64 => f64::from_le_bytes(reader.bytes()?),
+            }),
+        }
+    }
+}
+
+/// Loads an ASCII or binary PLY mesh with its positions, faces and optional normals.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_ply(bytes: &[u8]) -> Result<(Vec<Vertex>, Vec<u32>), MeshError> {
+    const END_HEADER: &[u8] = b"end_header";
+    let header_end = bytes
+        .windows(END_HEADER.len())
+        .position(|w| w == END_HEADER)
+        .ok_or(MeshError::Truncated)?;
+    let body_start = bytes[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|i| header_end + i + 1)
+        .ok_or(MeshError::Truncated)?;
+    let header = String::from_utf8_lossy(&bytes[..header_end]);
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(MeshError::Invalid(
+            "the PLY magic number is missing".to_string(),
+        ));
+    }
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens[..] {
+            ["format", name, _] => format = Some(name.to_string()),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| MeshError::Invalid(format!("{} is not a count", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, value_type, name] => {
+                let element = elements.last_mut().ok_or(MeshError::Truncated)?;
+                element.properties.push(PlyProperty {
+                    name: name.to_string(),
+                    count_type: Some(PlyType::parse(count_type)?),
+                    value_type: PlyType::parse(value_type)?,
+                });
+            }
+            ["property", value_type, name] => {
+                let element = elements.last_mut().ok_or(MeshError::Truncated)?;
+                element.properties.push(PlyProperty {
+                    name: name.to_string(),
+                    count_type: None,
+                    value_type: PlyType::parse(value_type)?,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let body = &bytes[body_start..];
+    let text;
+    let mut reader = match format.as_deref() {
+        Some("ascii") => {
+            text = String::from_utf8_lossy(body);
+            PlyReader::Ascii(text.split_whitespace())
+        }
+        Some("binary_little_endian") => PlyReader::Binary(ByteReader::new(body, 0, true)),
+        Some("binary_big_endian") => PlyReader::Binary(ByteReader::new(body, 0, false)),
+        _ => return Err(MeshError::Invalid("the PLY format is missing".to_string())),
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut position = Vec3::ZERO;
+            let mut normal = Vec3::ZERO;
+            for property in &element.properties {
+                if let Some(count_type) = property.count_type {
+                    let count = reader.read(count_type)? as usize;
+                    // The count is not trusted for reserving memory, the list grows as it is read.
+                    let mut list = Vec::new();
+                    for _ in 0..count {
+                        list.push(reader.read(property.value_type)? as u32);
+                    }
+                    if element.name == "face"
+                        && matches!(property.name.as_str(), "vertex_indices" | "vertex_index")
+                    {
+                        for i in 1..list.len().saturating_sub(1) {
+                            indices.extend_from_slice(&[list[0], list[i], list[i + 1]]);
+                        }
+                    }
+                    continue;
+                }
+                let value = reader.read(property.value_type)? as f32;
+                match property.name.as_str() {
+                    "x" => position.x = value,
+                    "y" => position.y = value,
+                    "z" => position.z = value,
+                    "nx" => normal.x = value,
+                    "ny" => normal.y = value,
+                    "nz" => normal.z = value,
+                    _ => {}
+                }
+            }
+            if element.name == "vertex" {
+                positions.push(position);
+                normals.push(normal);
+            }
+        }
+    }
+
+    let has_normals = elements
+        .iter()
+        .filter(|e| e.name == "vertex")
+        .any(|e| e.properties.iter().any(|p| p.name == "nx"));
+    build_mesh(positions, has_normals.then_some(normals), indices)
+}
+
+/// Reads numbers from a binary file.
+pub(super) struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    little_endian: bool,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(super) fn new(bytes: &'a [u8], offset: usize, little_endian: bool) -> Self {
+        Self {
+            bytes,
+            offset,
+            little_endian,
+        }
+    }
+
+    /// Reads `N` bytes, reversed into little endian order.
+    pub(super) fn bytes<const N: usize>(&mut self) -> Result<[u8; N], MeshError> {
+        let mut array: [u8; N] = self
+            .bytes
+            .get(self.offset..self.offset + N)
+            .ok_or(MeshError::Truncated)?
+            .try_into()
+            .map_err(|_| MeshError::Truncated)?;
+        if !self.little_endian {
+            array.reverse();
+        }
+        self.offset += N;
+        Ok(array)
+    }
+
+    /// Returns the number of bytes after the current offset.
+    pub(super) fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.offset)
+    }
+
+    pub(super) fn i32(&mut self) -> Result<i32, MeshError> {
+        Ok(i32::from_le_bytes(self.bytes()?))
+    }
+
+    pub(super) fn f32(&mut self) -> Result<f32, MeshError> {
+        Ok(f32::from_le_bytes(self.bytes()?))
+    }
+}
+
+fn parse_f32(token: Option<&str>) -> Result<f32, MeshError> {
+    let token = token.ok_or(MeshError::Truncated)?;
+    token
+        .parse()
+        .map_err(|_| MeshError::Invalid(format!("{} is not a number", token)))
+}
+
+fn parse_vec3<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vec3, MeshError> {
+    Ok(Vec3::new(
+        parse_f32(tokens.next())?,
+        parse_f32(tokens.next())?,
+        parse_f32(tokens.next())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{load_freesurfer, load_mesh, load_stl, MeshError};
+
+    /// Encodes a FreeSurfer triangle surface.
+    ///
+    /// Arguments:
+    ///
+    /// * `positions`: The positions of the vertices.
+    /// * `indices`: The indices of the triangle list.
+    /// * `c_ras`: The center of the volume the surface was created from, written to the
+    ///   volume geometry after the faces if given.
+    fn freesurfer_bytes(positions: &[[f32; 3]], indices: &[i32], c_ras: Option<Vec3>) -> Vec<u8> {
+        let mut bytes = vec![0xff, 0xff, 0xfe];
+        bytes.extend_from_slice(b"created by test on today\n\n");
+        bytes.extend_from_slice(&(positions.len() as i32).to_be_bytes());
+        bytes.extend_from_slice(&(indices.len() as i32 / 3).to_be_bytes());
+        for value in positions.iter().flatten() {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        for index in indices {
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+        if let Some(c) = c_ras {
+            bytes.extend_from_slice(&[0, 0, 0, 20]);
+            let geometry = format!(
+                "valid = 1  # volume info valid\ncras   = {} {} {}\n",
+                c.x, c.y, c.z
+            );
+            bytes.extend_from_slice(geometry.as_bytes());
+        }
+        bytes
+    }
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [10.0, 0.0, 0.0],
+        [0.0, 10.0, 0.0],
+        [0.0, 0.0, 10.0],
+    ];
+    const INDICES: [i32; 12] = [0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3];
+
+    #[test]
+    fn freesurfer_surface() {
+        let bytes = freesurfer_bytes(&POSITIONS, &INDICES, None);
+        // The magic number is recognized whatever the file is called.
+        let (vertices, indices) = load_mesh(&bytes, "lh.pial").unwrap();
+        let positions: Vec<[f32; 3]> = vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, POSITIONS);
+        assert_eq!(indices, INDICES.map(|i| i as u32));
+        // Smooth normals point away from the inside of the tetrahedron.
+        let center = Vec3::splat(2.5);
+        for vertex in &vertices {
+            let outwards = Vec3::from(vertex.position) - center;
+            assert!(Vec3::from(vertex.normal).dot(outwards) > 0.0);
+        }
+    }
+
+    #[test]
+    fn freesurfer_surface_with_c_ras() {
+        let c_ras = Vec3::new(1.5, -20.0, 8.0);
+        let bytes = freesurfer_bytes(&POSITIONS, &INDICES, Some(c_ras));
+        let (vertices, _) = load_freesurfer(&bytes).unwrap();
+        for (vertex, position) in vertices.iter().zip(POSITIONS) {
+            assert_eq!(Vec3::from(vertex.position), Vec3::from(position) + c_ras);
+        }
+    }
+
+    #[test]
+    fn freesurfer_counts_are_checked() {
+        let bytes = freesurfer_bytes(&POSITIONS, &INDICES, None);
+        let counts = 3 + b"created by test on today\n\n".len();
+
+        let mut negative = bytes.clone();
+        negative[counts..counts + 4].copy_from_slice(&(-1i32).to_be_bytes());
+        assert!(matches!(
+            load_freesurfer(&negative),
+            Err(MeshError::Invalid(_))
+        ));
+
+        let mut huge = bytes.clone();
+        huge[counts + 4..counts + 8].copy_from_slice(&i32::MAX.to_be_bytes());
+        assert_eq!(load_freesurfer(&huge).unwrap_err(), MeshError::Truncated);
+
+        let mut out_of_range = bytes;
+        let last = out_of_range.len() - 4;
+        out_of_range[last..].copy_from_slice(&4i32.to_be_bytes());
+        assert!(matches!(
+            load_freesurfer(&out_of_range),
+            Err(MeshError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn stl_that_is_neither_ascii_nor_binary() {
+        assert!(matches!(
+            load_stl(b"PK\x03\x04 not a mesh"),
+            Err(MeshError::Invalid(_))
+        ));
+
+        // A binary header that announces more triangles than the file holds.
+        let mut bytes = vec![0; 84 + 50];
+        bytes[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(load_stl(&bytes), Err(MeshError::Invalid(_))));
+    }
+
+    #[test]
+    fn unknown_file_name() {
+        assert_eq!(
+            load_mesh(b"", "mesh.xyz").unwrap_err(),
+            MeshError::UnknownFormat("mesh.xyz".to_string())
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use crate::loaders::mesh::{gifti_values, inflate, ByteReader, MeshError};
+
+/// The file formats per-vertex scalars can be loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayFormat {
+    /// FreeSurfer curvature files like `lh.curv`, `lh.sulc` or `lh.thickness`.
+    FreeSurferCurv,
+
+    /// FreeSurfer `.mgh` files, which are `.mgz` files if compressed.
+    Mgh,
+
+    /// GIfTI functional and shape files like `lh.func.gii` or `lh.shape.gii`.
+    Gifti,
+}
+
+/// The names of FreeSurfer per-vertex files, which have no file extension.
+const FREESURFER_CURVS: [&str; 8] = [
+    "curv",
+    "avg_curv",
+    "sulc",
+    "thickness",
+    "area",
+    "area.pial",
+    "volume",
+    "jacobian_white",
+];
+
+impl OverlayFormat {
+    /// Returns the format of a file based on its name.
+    ///
+    /// Arguments:
+    ///
+    /// * `file_name`: The name or path of the file.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let name = file_name
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(file_name)
+            .to_ascii_lowercase();
+        if name.ends_with(".mgh") || name.ends_with(".mgz") {
+            return Some(Self::Mgh);
+        }
+        if [".func.gii", ".shape.gii", ".time.gii"]
+            .iter()
+            .any(|extension| name.ends_with(extension))
+        {
+            return Some(Self::Gifti);
+        }
+        if name.ends_with(".curv") {
+            return Some(Self::FreeSurferCurv);
+        }
+        // FreeSurfer names per-vertex files after the hemisphere, like `lh.thickness`.
+        match name.split_once('.') {
+            Some(("lh" | "rh", curv)) if FREESURFER_CURVS.contains(&curv) => {
+                Some(Self::FreeSurferCurv)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Loads one scalar per vertex, picking the format from the file name.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+/// * `file_name`: The name or path of the file.
+pub fn load_overlay(bytes: &[u8], file_name: &str) -> Result<Vec<f32>, MeshError> {
+    match OverlayFormat::from_file_name(file_name)
+        .ok_or_else(|| MeshError::UnknownFormat(file_name.to_string()))?
+    {
+        OverlayFormat::FreeSurferCurv => load_freesurfer_curv(bytes),
+        OverlayFormat::Mgh => load_mgh(bytes),
+        OverlayFormat::Gifti => load_gifti_overlay(bytes),
+    }
+}
+
+/// The magic number of FreeSurfer curvature files in the new format.
+const FREESURFER_CURV_MAGIC: [u8; 3] = [0xff, 0xff, 0xff];
+
+/// Loads a FreeSurfer curvature file in the new binary format.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_freesurfer_curv(bytes: &[u8]) -> Result<Vec<f32>, MeshError> {
+    if !bytes.starts_with(&FREESURFER_CURV_MAGIC) {
+        return Err(MeshError::Unsupported(
+            "the old FreeSurfer curvature format".to_string(),
+        ));
+    }
+    let mut reader = ByteReader::new(bytes, 3, false);
+    let vertex_count = usize::try_from(reader.i32()?)
+        .map_err(|_| MeshError::Invalid("the vertex count is negative".to_string()))?;
+    let _face_count = reader.i32()?;
+    let values_per_vertex = reader.i32()?;
+    if values_per_vertex != 1 {
+        return Err(MeshError::Unsupported(format!(
+            "{} values per vertex",
+            values_per_vertex
+        )));
+    }
+    if vertex_count
+        .checked_mul(4)
+        .is_none_or(|size| size > reader.remaining())
+    {
+        return Err(MeshError::Truncated);
+    }
+    (0..vertex_count).map(|_| reader.f32()).collect()
+}
+
+/// The offset of the data in `.mgh` files.
+const MGH_DATA_OFFSET: usize = 284;
+
+/// Loads the first frame of a FreeSurfer `.mgh` or `.mgz` file.
+///
+/// Surface overlays are stored with one voxel per vertex, so the voxels are returned in order.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_mgh(bytes: &[u8]) -> Result<Vec<f32>, MeshError> {
+    let inflated;
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        inflated = inflate(bytes)?;
+        &inflated[..]
+    } else {
+        bytes
+    };
+
+    let mut reader = ByteReader::new(bytes, 0, false);
+    let version = reader.i32()?;
+    if version != 1 {
+        return Err(MeshError::Invalid(format!(
+            "the MGH version {} is unknown",
+            version
+        )));
+    }
+    let (width, height, depth) = (reader.i32()?, reader.i32()?, reader.i32()?);
+    let _frames = reader.i32()?;
+    let data_type = reader.i32()?;
+    let count = [width, height, depth]
+        .into_iter()
+        .try_fold(1usize, |count, size| {
+            count.checked_mul(size.max(1) as usize)
+        })
+        .ok_or_else(|| MeshError::Invalid("the MGH dimensions are too large".to_string()))?;
+
+    let mut reader = ByteReader::new(bytes, MGH_DATA_OFFSET, false);
+    (0..count)
+        .map(|_| match data_type {
+            0 => Ok(reader.bytes::<1>()?[0] as f32),
+            1 => Ok(reader.i32()? as f32),
+            3 => reader.f32(),
+            4 => Ok(i16::from_le_bytes(reader.bytes()?) as f32),
+            _ => Err(MeshError::Unsupported(format!(
+                "the MGH data type {}",
+                data_type
+            ))),
+        })
+        .collect()
+}
+
+/// Loads the first data array of a GIfTI functional or shape file.
+///
+/// Arguments:
+///
+/// * `bytes`: The contents of the file.
+pub fn load_gifti_overlay(bytes: &[u8]) -> Result<Vec<f32>, MeshError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| MeshError::Invalid("the XML is not UTF-8".to_string()))?;
+    let document =
+        roxmltree::Document::parse(text).map_err(|e| MeshError::Invalid(e.to_string()))?;
+
+    // Geometry arrays can be stored in the same file, they are skipped.
+    let array = document
+        .descendants()
+        .filter(|n| n.has_tag_name("DataArray"))
+        .find(|n| {
+            !matches!(
+                n.attribute("Intent"),
+                Some("NIFTI_INTENT_POINTSET" | "NIFTI_INTENT_TRIANGLE" | "NIFTI_INTENT_VECTOR")
+            )
+        })
+        .ok_or_else(|| MeshError::Invalid("the file has no data array".to_string()))?;
+    Ok(gifti_values(&array)?
+        .into_iter()
+        .map(|value| value as f32)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{load_freesurfer_curv, load_gifti_overlay, load_mgh, load_overlay, OverlayFormat};
+    use crate::loaders::mesh::MeshError;
+
+    /// Encodes a FreeSurfer curvature file in the new format.
+    fn curv_bytes(values: &[f32]) -> Vec<u8> {
+        let mut bytes = vec![0xff, 0xff, 0xff];
+        for count in [values.len() as i32, 8, 1] {
+            bytes.extend_from_slice(&count.to_be_bytes());
+        }
+        for value in values {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Encodes a big endian `.mgh` file with one row of voxels.
+    ///
+    /// Arguments:
+    ///
+    /// * `data_type`: The MGH data type, e.g. `3` for 32 bit floats.
+    /// * `count`: The number of voxels.
+    /// * `data`: The voxels in big endian byte order.
+    fn mgh_bytes(data_type: i32, count: i32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in [1, count, 1, 1, 1, data_type] {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        bytes.resize(284, 0);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn freesurfer_curvature() {
+        let values = [0.25, -1.5, 3.0];
+        let bytes = curv_bytes(&values);
+        assert_eq!(load_overlay(&bytes, "lh.thickness").unwrap(), values);
+        assert_eq!(
+            load_overlay(&bytes, "subject/surf/rh.curv").unwrap(),
+            values
+        );
+
+        assert!(matches!(
+            load_freesurfer_curv(&[0, 0, 3, 0, 0, 0, 0]),
+            Err(MeshError::Unsupported(_))
+        ));
+        let mut two_values = bytes.clone();
+        two_values[14] = 2;
+        assert!(matches!(
+            load_freesurfer_curv(&two_values),
+            Err(MeshError::Unsupported(_))
+        ));
+        assert_eq!(
+            load_freesurfer_curv(&bytes[..bytes.len() - 1]).unwrap_err(),
+            MeshError::Truncated
+        );
+        let mut negative = bytes;
+        negative[3..7].copy_from_slice(&(-1i32).to_be_bytes());
+        assert!(matches!(
+            load_freesurfer_curv(&negative),
+            Err(MeshError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn big_endian_mgh() {
+        let floats: Vec<u8> = [1.5f32, -2.0]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        assert_eq!(load_mgh(&mgh_bytes(3, 2, &floats)).unwrap(), [1.5, -2.0]);
+        let ints: Vec<u8> = [70000i32, -3]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        assert_eq!(load_mgh(&mgh_bytes(1, 2, &ints)).unwrap(), [70000.0, -3.0]);
+        // Shorts are swapped like the other types, -2 would be -257 in little endian order.
+        let shorts: Vec<u8> = [-2i16, 300].iter().flat_map(|v| v.to_be_bytes()).collect();
+        assert_eq!(load_mgh(&mgh_bytes(4, 2, &shorts)).unwrap(), [-2.0, 300.0]);
+        assert_eq!(load_mgh(&mgh_bytes(0, 2, &[7, 255])).unwrap(), [7.0, 255.0]);
+
+        let mut version = mgh_bytes(3, 2, &floats);
+        version[3] = 2;
+        assert!(matches!(load_mgh(&version), Err(MeshError::Invalid(_))));
+        assert!(matches!(
+            load_mgh(&mgh_bytes(2, 2, &floats)),
+            Err(MeshError::Unsupported(_))
+        ));
+        assert_eq!(
+            load_mgh(&mgh_bytes(3, 3, &floats)).unwrap_err(),
+            MeshError::Truncated
+        );
+    }
+
+    #[test]
+    fn compressed_mgz() {
+        let floats: Vec<u8> = [0.5f32, 4.0].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&mgh_bytes(3, 2, &floats)).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(
+            load_overlay(&bytes, "lh.thickness.mgz").unwrap(),
+            [0.5, 4.0]
+        );
+    }
+
+    #[test]
+    fn gifti_func_and_shape() {
+        // The point set is skipped, the first data array that is not geometry is loaded.
+        let func = r#"<?xml version="1.0" encoding="UTF-8"?>
+<GIFTI Version="1.0" NumberOfDataArrays="2">
+  <DataArray Intent="NIFTI_INTENT_POINTSET" DataType="NIFTI_TYPE_FLOAT32"
+             Dimensionality="2" Dim0="1" Dim1="3" Encoding="ASCII">
+    <Data>1 2 3</Data>
+  </DataArray>
+  <DataArray Intent="NIFTI_INTENT_ZSCORE" DataType="NIFTI_TYPE_FLOAT32"
+             Dimensionality="1" Dim0="3" Encoding="ASCII">
+    <Data>0.5 -1.25 2</Data>
+  </DataArray>
+</GIFTI>"#;
+        assert_eq!(
+            load_overlay(func.as_bytes(), "lh.func.gii").unwrap(),
+            [0.5, -1.25, 2.0]
+        );
+
+        // Base64 encoded little endian floats 1.0 and -0.5.
+        let shape = r#"<GIFTI Version="1.0" NumberOfDataArrays="1">
+  <DataArray Intent="NIFTI_INTENT_SHAPE" DataType="NIFTI_TYPE_FLOAT32" Dimensionality="1"
+             Dim0="2" Encoding="Base64Binary" Endian="LittleEndian">
+    <Data>AACAPwAAAL8=</Data>
+  </DataArray>
+</GIFTI>"#;
+        assert_eq!(
+            load_overlay(shape.as_bytes(), "rh.shape.gii").unwrap(),
+            [1.0, -0.5]
+        );
+
+        let geometry = func.replace("NIFTI_INTENT_ZSCORE", "NIFTI_INTENT_TRIANGLE");
+        assert!(matches!(
+            load_gifti_overlay(geometry.as_bytes()),
+            Err(MeshError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn format_from_file_name() {
+        let format = OverlayFormat::from_file_name;
+        assert_eq!(format("lh.sulc"), Some(OverlayFormat::FreeSurferCurv));
+        assert_eq!(
+            format("C:\\subject\\RH.AREA.PIAL"),
+            Some(OverlayFormat::FreeSurferCurv)
+        );
+        assert_eq!(format("lh.thickness.fwhm10.mgz"), Some(OverlayFormat::Mgh));
+        assert_eq!(format("lh.time.gii"), Some(OverlayFormat::Gifti));
+        assert_eq!(format("lh.pial"), None);
+        assert_eq!(format("lh.surf.gii"), None);
+        assert_eq!(
+            load_overlay(&[], "lh.pial").unwrap_err(),
+            MeshError::UnknownFormat("lh.pial".to_string())
+        );
+    }
+}
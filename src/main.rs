@@ -1,8 +1,45 @@
 pub(crate) mod camera_controller;
 
 use camera_controller::CameraController;
-use glam::Vec3;
-use wgpu_shape_renderer::render::{camera::OrbitCamera, state::State};
+use glam::{Mat4, Vec2, Vec3};
+use wgpu_shape_renderer::{
+    data::{
+        cluster::{ClusterReport, Connectivity},
+        label_map::LabelMap,
+        lookup_table::LookupTable,
+        resample::{resample, Interpolation},
+        statistic::{StatThreshold, Statistic, DEFAULT_P_VALUE},
+        time_course::{get_correlation_map, get_time_course_at, Roi},
+        transform::parse_transform,
+        volume::Volume,
+    },
+    exporters::mesh::{export_mesh, MeshFormat},
+    extras::math::vector3::Vector3,
+    loaders::{
+        mesh::load_mesh,
+        overlay::{load_overlay, OverlayFormat},
+    },
+    render::{
+        camera::OrbitCamera,
+        cine::CinePlayer,
+        error::RenderError,
+        geometry::{
+            crosshair::get_crosshair_vertecies, cublets::get_cublet_instances,
+            isosurface::get_isosurface_vertecies, outline::get_outline_vertecies,
+            r#box::get_box_vertecies, slice::get_slice_vertecies,
+        },
+        label_surfaces::LabelSurfaces,
+        layers::{
+            BlendMode, CompareMode, CompareSettings, Comparison, LayerId, LayerSettings,
+            VolumeLayer,
+        },
+        overlay::{get_volume_scalars, Colormap, OverlaySettings},
+        scene::{Material, ObjectId, SceneObject},
+        settings::RenderSettings,
+        state::State,
+        vertex::{Instance, Vertex},
+    },
+};
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -11,10 +48,207 @@ use winit::{
 use futures::executor;
 use loaders::json_loader::{load_json, log_init};
 
+/// The smallest magnitude of a seed correlation that is shown.
+const CORRELATION_THRESHOLD: f32 = 0.3;
+
+/// A 4D volume whose displayed frame colors the object that shows its geometry.
+///
+/// The geometry is extracted once at the threshold, playing only uploads the voxels of the
+/// displayed frame to the volume layer that colors it.
+struct TimeSeries {
+    /// The layer that holds the volume with all frames.
+    layer: LayerId,
+
+    /// The seed correlation map that colors the isosurface, if any.
+    correlation: Option<Volume>,
+}
+
+/// The options that change how the volumes, meshes and overlays after them are shown.
+struct Options {
+    /// The block size of the cublets after `--cublets`, or [None] to show isosurfaces.
+    cublet_size: Option<usize>,
+
+    /// Whether volumes are framed by a wireframe box after `--outline`.
+    outline: bool,
+
+    /// The seed of the correlation maps of 4D volumes after `--seed`, if any.
+    seed: Option<Vec3>,
+
+    /// The voxels that are correlated with the seed after `--mask`, or [None] for all.
+    mask: Option<Roi>,
+
+    /// How the voxels of clusters are connected after `--connectivity`.
+    connectivity: Connectivity,
+
+    /// The lookup table after `--lut` that makes volumes label maps, if any.
+    lookup_table: Option<LookupTable>,
+
+    /// Whether volumes are stacked as layers on slice planes after `--layers`.
+    layered: bool,
+
+    /// The format of exported meshes after `--export`.
+    export_format: MeshFormat,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            cublet_size: None,
+            outline: false,
+            seed: None,
+            mask: None,
+            connectivity: Connectivity::Corners,
+            lookup_table: None,
+            layered: false,
+            export_format: MeshFormat::StlBinary,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the option at the start of the arguments.
+    ///
+    /// Returns the number of arguments taken by the option and its value, or [None] if the
+    /// first argument is not an option.
+    ///
+    /// Arguments:
+    ///
+    /// * `args`: The remaining command line arguments.
+    /// * `label_maps`: The label maps to which atlases are added.
+    fn parse(&mut self, args: &[String], label_maps: &mut Vec<LabelMap>) -> Option<usize> {
+        match args.first()?.as_str() {
+            "--outline" => self.outline = true,
+            "--layers" => self.layered = true,
+            _ => return self.parse_value(args, label_maps).then_some(2),
+        }
+        Some(1)
+    }
+
+    /// Parses an option that is followed by a value. Returns `false` if the first argument is
+    /// not such an option.
+    ///
+    /// Arguments:
+    ///
+    /// * `args`: The remaining command line arguments, starting with the option.
+    /// * `label_maps`: The label maps to which atlases are added.
+    fn parse_value(&mut self, args: &[String], label_maps: &mut Vec<LabelMap>) -> bool {
+        let value = args.get(1).map(String::as_str);
+        match args[0].as_str() {
+            "--cublets" => self.parse_cublets(value),
+            "--export" => self.parse_export(value),
+            "--seed" => self.parse_seed(value),
+            "--connectivity" => self.parse_connectivity(value),
+            "--lut" => self.load_lookup_table(value),
+            "--mask" => self.load_mask(value),
+            "--atlas" => self.load_atlas(value, label_maps),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Parses the block size of `--cublets`.
+    fn parse_cublets(&mut self, value: Option<&str>) {
+        self.cublet_size = value.and_then(|s| s.parse::<usize>().ok());
+    }
+
+    /// Parses the mesh format of `--export`.
+    fn parse_export(&mut self, value: Option<&str>) {
+        match value.and_then(MeshFormat::from_name) {
+            Some(format) => self.export_format = format,
+            None => eprintln!("The export format has to be stl, stl-ascii, obj or ply."),
+        }
+    }
+
+    /// Parses the world position of `--seed`.
+    fn parse_seed(&mut self, value: Option<&str>) {
+        self.seed = value.and_then(parse_position);
+        if self.seed.is_none() {
+            eprintln!("The seed has to be given as x,y,z in millimetres.");
+        }
+    }
+
+    /// Parses the number of neighbors of `--connectivity`.
+    fn parse_connectivity(&mut self, value: Option<&str>) {
+        match value
+            .and_then(|s| s.parse::<usize>().ok())
+            .and_then(Connectivity::from_neighbors)
+        {
+            Some(connectivity) => self.connectivity = connectivity,
+            None => eprintln!("The connectivity has to be 6, 18 or 26."),
+        }
+    }
+
+    /// Loads the FreeSurfer or ITK-SNAP lookup table of `--lut`.
+    fn load_lookup_table(&mut self, value: Option<&str>) {
+        let Some(path) = value else {
+            return;
+        };
+        self.lookup_table = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| LookupTable::parse(&text).map_err(|e| e.to_string()))
+            .map_err(|e| eprintln!("Failed to load {}: {}", path, e))
+            .ok();
+    }
+
+    /// Loads the mask volume of `--mask`.
+    fn load_mask(&mut self, value: Option<&str>) {
+        let Some(path) = value else {
+            return;
+        };
+        self.mask = std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| Volume::from_nifti_bytes(&bytes).map_err(|e| e.to_string()))
+            .map(|volume| Roi::from_mask_volume(&volume))
+            .map_err(|e| eprintln!("Failed to load {}: {}", path, e))
+            .ok();
+    }
+
+    /// Loads the atlas of `--atlas` with the last lookup table without showing it.
+    fn load_atlas(&self, value: Option<&str>, label_maps: &mut Vec<LabelMap>) {
+        let Some(path) = value else {
+            return;
+        };
+        let Some(table) = &self.lookup_table else {
+            eprintln!("The atlas {} does not follow a lookup table.", path);
+            return;
+        };
+        match std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| Volume::from_nifti_bytes(&bytes).map_err(|e| e.to_string()))
+        {
+            Ok(volume) => label_maps.push(LabelMap::new(volume, table.clone())),
+            Err(e) => eprintln!("Failed to load {}: {}", path, e),
+        }
+    }
+}
+
+/// The clusters of a statistical map and the crosshair that marks their peaks.
+struct Clusters {
+    /// The clusters of the thresholded map.
+    report: ClusterReport,
+
+    /// The object of the crosshair, hidden until the first jump.
+    crosshair: ObjectId,
+
+    /// The index of the cluster whose peak is marked, if any.
+    current: Option<usize>,
+}
+
 fn main() {
     //let before = std::time::Instant::now();
     log_init();
     env_logger::init();
+
+    // `--resample <moving> <reference> <transform> <output> [nearest|trilinear|sinc]` writes the
+    // moving volume on the grid of the reference without opening a window.
+    let resample_args: Vec<String> = std::env::args().skip(1).collect();
+    if resample_args.first().is_some_and(|arg| arg == "--resample") {
+        if let Err(e) = resample_volume(&resample_args[1..]) {
+            eprintln!("Failed to resample: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("Effcient wgpu cube")
@@ -37,10 +271,272 @@ fn main() {
     let mut camera_controller = CameraController::new(0.025, 0.6);
 
     // State::new uses async code, so we're going to wait for it to finish
-    let mut state = pollster::block_on(State::new(&window, size.width, size.height, camera));
+    let mut state = match pollster::block_on(State::new(&window, size.width, size.height, camera)) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Volumes, meshes and overlays are shown together, each optionally followed by a threshold.
+    // The options before them are described in the README.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = Options::default();
+    let mut last_object = None;
+    let mut label_maps: Vec<LabelMap> = Vec::new();
+    let mut series: Vec<TimeSeries> = Vec::new();
+    let mut clusters: Option<Clusters> = None;
+    let mut i = 0;
+    while i < args.len() {
+        if let Some(count) = options.parse(&args[i..], &mut label_maps) {
+            i += count;
+            continue;
+        }
+        let path = &args[i];
+        let threshold = args.get(i + 1).and_then(|t| t.parse::<f32>().ok());
+        let p_value = args
+            .get(i + 1)
+            .and_then(|t| t.strip_prefix('p'))
+            .and_then(|p| p.parse::<f32>().ok());
+        i += if threshold.is_some() || p_value.is_some() {
+            2
+        } else {
+            1
+        };
+
+        if options.layered && is_nifti(path) {
+            let volume = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Volume::from_nifti_bytes(&bytes).map_err(|e| e.to_string()));
+            let threshold = p_value
+                .map(StatThreshold::PValue)
+                .or(threshold.map(StatThreshold::Value));
+            match volume {
+                Ok(volume) => {
+                    // The box is replaced by the slice planes through the underlay.
+                    let underlay = state.layers().next().is_none();
+                    if underlay {
+                        state.set_mesh(Vec::new(), Vec::new());
+                        last_object = Some(state.mesh_object());
+                        add_slice_planes(&mut state, &volume);
+                    }
+                    let layer =
+                        volume_layer(volume, options.lookup_table.as_ref(), threshold, underlay);
+                    if let Err(e) = state.add_layer(layer) {
+                        eprintln!("Failed to show {}: {}", path, e);
+                    }
+                }
+                Err(e) => eprintln!("Failed to load {}: {}", path, e),
+            }
+            continue;
+        }
+
+        // Statistical maps after a mesh color it instead of being shown as isosurfaces.
+        if let Some(id) = last_object.filter(|_| is_nifti(path)) {
+            let statistical_map = std::fs::read(path)
+                .ok()
+                .and_then(|bytes| Volume::from_nifti_bytes(&bytes).ok())
+                .and_then(|volume| Some((Statistic::from_header(&volume.header)?, volume)));
+            if let Some((statistic, volume)) = statistical_map {
+                let threshold = p_value
+                    .map(StatThreshold::PValue)
+                    .or(threshold.map(StatThreshold::Value))
+                    .unwrap_or(StatThreshold::PValue(DEFAULT_P_VALUE));
+                let threshold = statistic.threshold_value(threshold);
+                let (min, max) = volume.min_max();
+                println!(
+                    "Thresholding the {} map {} at {} (p = {}).",
+                    statistic.name(),
+                    path,
+                    threshold,
+                    statistic.p_value(threshold)
+                );
+                let object = state.object_mut(id).expect("The object was added before.");
+                let scalars = get_volume_scalars(&volume, object.vertices());
+                if let Err(e) = object.set_scalars(Some(scalars)) {
+                    eprintln!("Failed to color {}: {}", path, e);
+                    continue;
+                }
+                state.set_overlay(OverlaySettings::statistic(
+                    &statistic,
+                    threshold,
+                    max.max(-min),
+                ));
+
+                let report = ClusterReport::new(
+                    &volume,
+                    threshold,
+                    options.connectivity,
+                    statistic.is_signed(),
+                );
+                print!("{}", report.to_csv());
+                let (vertices, indices) = get_crosshair_vertecies(volume.extent());
+                let mut crosshair = SceneObject::new("crosshair", vertices, indices);
+                crosshair.visible = false;
+                crosshair.material = Material {
+                    lit: false,
+                    ..Material::from_color([0.2, 1.0, 0.2, 1.0])
+                };
+                if let Some(clusters) = clusters.take() {
+                    state.remove_object(clusters.crosshair);
+                }
+                clusters = Some(Clusters {
+                    report,
+                    crosshair: state.add_object(crosshair),
+                    current: None,
+                });
+                continue;
+            }
+        }
+
+        if let Some(table) = options.lookup_table.as_ref().filter(|_| is_nifti(path)) {
+            let volume = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Volume::from_nifti_bytes(&bytes).map_err(|e| e.to_string()));
+            match volume {
+                Ok(volume) => {
+                    // The box is hidden behind the first label map.
+                    if last_object.is_none() {
+                        state.set_mesh(Vec::new(), Vec::new());
+                        last_object = Some(state.mesh_object());
+                    }
+                    let label_map = LabelMap::new(volume, table.clone());
+                    let surfaces = LabelSurfaces::add_to_scene(&mut state, &label_map);
+                    println!("Showing {} labels of {}.", surfaces.objects().count(), path);
+                    label_maps.push(label_map);
+                }
+                Err(e) => eprintln!("Failed to load {}: {}", path, e),
+            }
+            continue;
+        }
+
+        if OverlayFormat::from_file_name(path).is_some() {
+            let Some(id) = last_object else {
+                eprintln!("The overlay {} does not follow a mesh.", path);
+                continue;
+            };
+            let overlay = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| load_overlay(&bytes, path).map_err(|e| e.to_string()))
+                .and_then(|values| {
+                    // Curvature is shown in gray, everything else like a statistical map.
+                    let colormap = if path.ends_with("curv") || path.ends_with("sulc") {
+                        Colormap::Gray
+                    } else {
+                        Colormap::Hot
+                    };
+                    let overlay = OverlaySettings {
+                        threshold,
+                        ..OverlaySettings::fit(colormap, &values)
+                    };
+                    state
+                        .object_mut(id)
+                        .expect("The object was added before.")
+                        .set_scalars(Some(values))
+                        .map_err(|e| e.to_string())?;
+                    Ok(overlay)
+                });
+            match overlay {
+                Ok(overlay) => state.set_overlay(overlay),
+                Err(e) => eprintln!("Failed to load {}: {}", path, e),
+            }
+            continue;
+        }
+
+        let mesh = std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                if !is_nifti(path) {
+                    let (vertices, indices) = load_mesh(&bytes, path).map_err(|e| e.to_string())?;
+                    return Ok((vertices, indices, None, None));
+                }
+                let volume = Volume::from_nifti_bytes(&bytes).map_err(|e| e.to_string())?;
+                let (min, max) = volume.series_min_max();
+                let threshold = threshold.unwrap_or((min + max) / 2.0);
+                let (vertices, indices, instances) =
+                    volume_geometry(&volume, threshold, options.cublet_size);
+                Ok((vertices, indices, instances, Some((volume, threshold))))
+            });
+        match mesh {
+            Ok((vertices, indices, instances, volume)) => {
+                // The first mesh replaces the box, the others are added to the scene.
+                let id = match last_object {
+                    None => {
+                        state.set_mesh(vertices, indices);
+                        state.mesh_object()
+                    }
+                    Some(_) => state.add_object(SceneObject::new(path, vertices, indices)),
+                };
+                let object = state.object_mut(id).expect("The object was added before.");
+                object.name = path.clone();
+                object.material = Material::default();
+                object.set_instances(instances);
+                last_object = Some(id);
+
+                let Some((volume, threshold)) = volume else {
+                    continue;
+                };
+                if options.outline {
+                    let (vertices, indices) = get_outline_vertecies(&volume);
+                    let mut object = SceneObject::new(path, vertices, indices);
+                    object.transform = volume.box_transform();
+                    object.material = Material {
+                        lit: false,
+                        ..Material::from_color([1.0, 1.0, 0.6, 1.0])
+                    };
+                    state.add_object(object);
+                }
+                if volume.frame_count > 1 {
+                    let correlation = options.seed.and_then(|seed| {
+                        get_time_course_at(&volume, seed)
+                            .and_then(|course| {
+                                get_correlation_map(&volume, &course, options.mask.as_ref())
+                            })
+                            .map_err(|e| eprintln!("Failed to correlate {}: {}", path, e))
+                            .ok()
+                    });
+                    let settings = LayerSettings::time_series(&volume, threshold);
+                    let layer = match state.add_layer(VolumeLayer::new(volume, settings)) {
+                        Ok(layer) => layer,
+                        Err(e) => {
+                            eprintln!("Failed to play {}: {}", path, e);
+                            continue;
+                        }
+                    };
+                    let object = state.object_mut(id).expect("The object was added before.");
+                    object.material.layered = true;
+                    if let Some(correlation) = &correlation {
+                        if let Err(e) = color_correlation(object, correlation) {
+                            eprintln!("Failed to color {}: {}", path, e);
+                        }
+                        state.set_overlay(OverlaySettings::symmetric(
+                            Colormap::BlueRed,
+                            1.0,
+                            CORRELATION_THRESHOLD,
+                        ));
+                    }
+                    series.push(TimeSeries { layer, correlation });
+                }
+            }
+            Err(e) => eprintln!("Failed to load {}: {}", path, e),
+        }
+    }
+    if last_object.is_some() {
+        state.fit_camera(&[]);
+    }
 
     // println!("Setup done in {:.2?}.", before.elapsed());
 
+    // All time series are played together, at the repetition time of the first one.
+    let mut player = series
+        .first()
+        .and_then(|series| state.layer(series.layer))
+        .map(|layer| CinePlayer::for_volume(&layer.volume));
+    let mut last_tick = std::time::Instant::now();
+    let mut cursor = Vec2::ZERO;
+    let mut dragging_curtain = false;
+
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
         match event {
@@ -58,6 +554,174 @@ fn main() {
                         },
                     ..
                 } => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F12),
+                            ..
+                        },
+                    ..
+                } => save_screenshot(&mut state),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F10),
+                            ..
+                        },
+                    ..
+                } => save_mesh(&state, options.export_format),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F9),
+                            ..
+                        },
+                    ..
+                } => save_correlation_maps(&series),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F8),
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(clusters) = &clusters {
+                        save_cluster_report(&clusters.report);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::C),
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(clusters) = clusters.as_mut() {
+                        clusters.next(&mut state);
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::B),
+                            ..
+                        },
+                    ..
+                } => {
+                    cycle_blend_mode(&mut state);
+                    window.request_redraw();
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::K),
+                            ..
+                        },
+                    ..
+                } => {
+                    cycle_compare_mode(&mut state);
+                    window.request_redraw();
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode:
+                                Some(key @ (VirtualKeyCode::LBracket | VirtualKeyCode::RBracket)),
+                            ..
+                        },
+                    ..
+                } => {
+                    let factor = match key {
+                        VirtualKeyCode::LBracket => 0.5,
+                        _ => 2.0,
+                    };
+                    if let Some(comparison) = state.comparison().copied() {
+                        let tile_size = comparison.settings.tile_size * factor;
+                        state.set_comparison(Some(Comparison {
+                            settings: CompareSettings {
+                                tile_size,
+                                ..comparison.settings
+                            },
+                            ..comparison
+                        }));
+                        println!("Checkerboard tiles of {} mm.", tile_size);
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::G),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.set_settings(RenderSettings {
+                        orientation_gizmo: !state.settings().orientation_gizmo,
+                        ..*state.settings()
+                    });
+                    window.request_redraw();
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode:
+                                Some(
+                                    key @ (VirtualKeyCode::Space
+                                    | VirtualKeyCode::Left
+                                    | VirtualKeyCode::Right),
+                                ),
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(player) = player.as_mut() {
+                        match key {
+                            VirtualKeyCode::Space => player.toggle(),
+                            VirtualKeyCode::Left => player.step(-1),
+                            _ => player.step(1),
+                        }
+                        last_tick = std::time::Instant::now();
+                        show_frame(&mut state, &series, player.frame());
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    cursor = Vec2::new(position.x as f32, position.y as f32);
+                    if dragging_curtain {
+                        move_curtain(&mut state, cursor.x);
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state: button_state,
+                    button: MouseButton::Middle,
+                    ..
+                } => {
+                    dragging_curtain = *button_state == ElementState::Pressed;
+                    if dragging_curtain {
+                        move_curtain(&mut state, cursor.x);
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Right,
+                    ..
+                } => print_labels_at(&state, &label_maps, cursor),
                 WindowEvent::Resized(physical_size) => {
                     state.resize(physical_size.width, physical_size.height);
                     window.request_redraw();
@@ -68,6 +732,23 @@ fn main() {
                 }
                 _ => {}
             },
+            // This is the last event of an iteration, so the wait is not reset afterwards.
+            Event::RedrawEventsCleared => {
+                let Some(player) = player.as_mut().filter(|player| player.is_playing()) else {
+                    return;
+                };
+                let now = std::time::Instant::now();
+                let elapsed = now.duration_since(last_tick).as_secs_f32();
+                last_tick = now;
+                if let Some(frame) = player.advance(elapsed) {
+                    show_frame(&mut state, &series, frame);
+                    window.request_redraw();
+                }
+                if let Some(wait) = player.time_to_next_frame() {
+                    *control_flow =
+                        ControlFlow::WaitUntil(now + std::time::Duration::from_secs_f32(wait));
+                }
+            }
             Event::DeviceEvent { ref event, .. } => {
                 camera_controller.process_events(event, &window, &mut state.camera);
             }
@@ -94,3 +775,498 @@ fn main() {
         }
     });
 }
+
+/// Returns the geometry that shows the displayed frame of a volume.
+///
+/// Arguments:
+///
+/// * `volume`: The volume to show.
+/// * `threshold`: The intensity of the isosurface or the smallest intensity of a cublet.
+/// * `cublet_size`: The block size of the cublets, or [None] to show an isosurface.
+fn volume_geometry(
+    volume: &Volume,
+    threshold: f32,
+    cublet_size: Option<usize>,
+) -> (Vec<Vertex>, Vec<u32>, Option<Vec<Instance>>) {
+    let Some(block_size) = cublet_size else {
+        let (vertices, indices) = get_isosurface_vertecies(volume, threshold);
+        return (vertices, indices, None);
+    };
+    // The cubes are colored from the threshold to the largest intensity of all frames.
+    let (_, max) = volume.series_min_max();
+    let range = (max - threshold).max(f32::EPSILON);
+    let instances = get_cublet_instances(volume, threshold, block_size, |value| {
+        let [r, g, b] = Colormap::Hot.color((value - threshold) / range);
+        [r, g, b, 1.0]
+    });
+    let (vertices, indices) = get_box_vertecies(0, Vec3::ZERO, Vec3::ONE, Vec3::ZERO);
+    (vertices, indices, Some(instances))
+}
+
+/// Shows a frame of every time series.
+///
+/// Only the voxels of the frame are uploaded to the layers, the geometry is kept.
+fn show_frame(state: &mut State, series: &[TimeSeries], frame: usize) {
+    for time_series in series {
+        state.set_layer_frame(time_series.layer, frame);
+    }
+}
+
+/// Colors the isosurface of a time series with its correlation map.
+///
+/// Cublets are not colored, as their vertices belong to a single cube.
+///
+/// Arguments:
+///
+/// * `object`: The object that shows the isosurface.
+/// * `correlation`: The seed correlation map.
+fn color_correlation(object: &mut SceneObject, correlation: &Volume) -> Result<(), RenderError> {
+    if object.instances().is_some() {
+        return Ok(());
+    }
+    let scalars = get_volume_scalars(correlation, object.vertices());
+    object.set_scalars(Some(scalars))
+}
+
+impl Clusters {
+    /// Moves the crosshair and the camera target to the peak of the next cluster.
+    ///
+    /// The clusters are visited from the largest to the smallest and then from the largest again.
+    fn next(&mut self, state: &mut State) {
+        let count = self.report.clusters.len();
+        if count == 0 {
+            println!(
+                "No cluster exceeds the threshold {}.",
+                self.report.threshold
+            );
+            return;
+        }
+        let index = self.current.map_or(0, |current| (current + 1) % count);
+        self.current = Some(index);
+        let cluster = &self.report.clusters[index];
+        let peak = Vec3::from(cluster.peak);
+        if let Some(crosshair) = state.object_mut(self.crosshair) {
+            crosshair.transform = Mat4::from_translation(peak);
+            crosshair.visible = true;
+        }
+        state.camera.target = Vector3::from_vec3(peak);
+        println!(
+            "Cluster {} of {}: {} voxels, peak {} at {:?}.",
+            cluster.index, count, cluster.voxel_count, cluster.peak_value, cluster.peak
+        );
+    }
+}
+
+/// Adds three unlit slice planes through the center of a volume, which show the volume layers.
+fn add_slice_planes(state: &mut State, volume: &Volume) {
+    for axis in 0..3 {
+        let center = (volume.dims[axis] as f32 - 1.0) / 2.0;
+        let (vertices, indices) = get_slice_vertecies(volume, axis, center.round());
+        let mut object = SceneObject::new("slice", vertices, indices);
+        object.transform = volume.affine;
+        object.material = Material {
+            lit: false,
+            layered: true,
+            ..Material::from_color([0.0, 0.0, 0.0, 1.0])
+        };
+        state.add_object(object);
+    }
+}
+
+/// Creates the volume layer of a volume given after `--layers`.
+///
+/// Label maps are colored by the lookup table and statistical maps are thresholded like on
+/// surfaces. Other volumes are windowed to their intensities, in gray for the underlay.
+///
+/// Arguments:
+///
+/// * `volume`: The volume of the layer.
+/// * `lookup_table`: The lookup table that makes the volume a label map, if any.
+/// * `threshold`: The threshold given after the path of the volume, if any.
+/// * `underlay`: Whether the layer is the bottom one.
+fn volume_layer(
+    volume: Volume,
+    lookup_table: Option<&LookupTable>,
+    threshold: Option<StatThreshold>,
+    underlay: bool,
+) -> VolumeLayer {
+    if let Some(table) = lookup_table {
+        return VolumeLayer::from_label_map(LabelMap::new(volume, table.clone()));
+    }
+    let Some(statistic) = Statistic::from_header(&volume.header) else {
+        let colormap = if underlay {
+            Colormap::Gray
+        } else {
+            Colormap::Hot
+        };
+        let settings = LayerSettings {
+            // P-values only apply to statistical maps.
+            threshold: match threshold {
+                Some(StatThreshold::Value(value)) => Some(value),
+                _ => None,
+            },
+            ..LayerSettings::fit(colormap, &volume)
+        };
+        return VolumeLayer::new(volume, settings);
+    };
+
+    let threshold =
+        statistic.threshold_value(threshold.unwrap_or(StatThreshold::PValue(DEFAULT_P_VALUE)));
+    let (min, max) = volume.min_max();
+    let limit = max.max(-min).max(threshold);
+    // Signed statistics are centered on zero, so negative values are blue.
+    let (colormap, min) = if statistic.is_signed() {
+        (Colormap::BlueRed, -limit)
+    } else {
+        (Colormap::RedYellow, threshold)
+    };
+    let settings = LayerSettings {
+        colormap,
+        min,
+        max: limit,
+        threshold: Some(threshold),
+        absolute_threshold: true,
+        ..Default::default()
+    };
+    VolumeLayer::new(volume, settings)
+}
+
+/// Blends the top volume layer with the next mode, from alpha over additive to max.
+fn cycle_blend_mode(state: &mut State) {
+    let Some((id, layer)) = state.layers().last() else {
+        return;
+    };
+    let settings = layer.settings;
+    let blend = match settings.blend {
+        BlendMode::Alpha => BlendMode::Additive,
+        BlendMode::Additive => BlendMode::Max,
+        BlendMode::Max => BlendMode::Alpha,
+    };
+    state.set_layer_settings(id, LayerSettings { blend, ..settings });
+    println!("Blending the top layer with {:?}.", blend);
+}
+
+/// Compares the two bottom volume layers with the next mode, from a checkerboard over a curtain
+/// and their difference to no comparison.
+fn cycle_compare_mode(state: &mut State) {
+    let mut layers = state.layers();
+    let (Some((reference, layer)), Some((moving, _))) = (layers.next(), layers.next()) else {
+        return;
+    };
+    // A difference of half the window of the reference saturates the colormap.
+    let initial = CompareSettings {
+        difference_limit: (layer.settings.max - layer.settings.min).abs() * 0.5,
+        ..Default::default()
+    };
+    drop(layers);
+    let current = state.comparison().map(|comparison| comparison.settings);
+    let mode = match current.map(|settings| settings.mode) {
+        None => Some(CompareMode::Checkerboard),
+        Some(CompareMode::Checkerboard) => Some(CompareMode::Curtain),
+        Some(CompareMode::Curtain) => Some(CompareMode::Difference),
+        Some(CompareMode::Difference) => None,
+    };
+    let settings = current.unwrap_or(initial);
+    let comparison = mode.map(|mode| Comparison {
+        reference,
+        moving,
+        settings: CompareSettings { mode, ..settings },
+    });
+    state.set_comparison(comparison);
+    match mode {
+        Some(mode) => println!("Comparing the bottom layers with {:?}.", mode),
+        None => println!("Not comparing layers."),
+    }
+}
+
+/// Moves the curtain divider of the comparison to the cursor.
+///
+/// Arguments:
+///
+/// * `state`: The state whose comparison is changed.
+/// * `x`: The horizontal cursor position in pixels from the left edge of the window.
+fn move_curtain(state: &mut State, x: f32) {
+    let Some(comparison) = state.comparison().copied() else {
+        return;
+    };
+    let divider = (x / state.width as f32).clamp(0.0, 1.0);
+    state.set_comparison(Some(Comparison {
+        settings: CompareSettings {
+            divider,
+            ..comparison.settings
+        },
+        ..comparison
+    }));
+}
+
+/// Prints the world position of the surface under the cursor and the labels there.
+///
+/// Arguments:
+///
+/// * `state`: The state whose scene is picked.
+/// * `label_maps`: The label maps whose labels are looked up.
+/// * `cursor`: The cursor position in pixels from the top left corner of the window.
+fn print_labels_at(state: &State, label_maps: &[LabelMap], cursor: Vec2) {
+    let Some(position) = state.pick(cursor.x, cursor.y) else {
+        return;
+    };
+    let names: Vec<&str> = label_maps
+        .iter()
+        .filter_map(|label_map| label_map.entry_at(position))
+        .map(|label| label.name.as_str())
+        .collect();
+    println!(
+        "{:.1}, {:.1}, {:.1} mm: {}",
+        position.x,
+        position.y,
+        position.z,
+        names.join(", ")
+    );
+}
+
+/// Returns whether a path names a NIfTI volume, a `.nii` or gzip compressed `.nii.gz` file.
+fn is_nifti(path: &str) -> bool {
+    path.ends_with(".nii") || path.ends_with(".nii.gz")
+}
+
+/// Parses a position given as `x,y,z`.
+fn parse_position(text: &str) -> Option<Vec3> {
+    let values: Vec<f32> = text
+        .split(',')
+        .map(|value| value.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match values[..] {
+        [x, y, z] => Some(Vec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+/// Saves the seed correlation maps of the time series as NIfTI volumes in the working directory.
+fn save_correlation_maps(series: &[TimeSeries]) {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let maps = series
+        .iter()
+        .filter_map(|series| series.correlation.as_ref());
+    for (i, map) in maps.enumerate() {
+        let path = format!("correlation-{}-{}.nii", timestamp, i);
+        match std::fs::write(&path, map.to_nifti_bytes()) {
+            Ok(_) => println!("Saved correlation map to {}.", path),
+            Err(e) => eprintln!("Failed to save correlation map to {}: {}", path, e),
+        }
+    }
+}
+
+/// Resamples a moving volume through an FSL or ITK transform onto the grid of a reference and
+/// saves it as NIfTI volume.
+///
+/// Arguments:
+///
+/// * `args`: The paths of the moving volume, the reference, the transform and the output
+///   followed by the interpolation, trilinear by default.
+fn resample_volume(args: &[String]) -> Result<(), String> {
+    let [moving_path, reference_path, transform_path, output_path, ..] = args else {
+        return Err("The moving volume, reference, transform and output are needed.".to_string());
+    };
+    let interpolation = match args.get(4) {
+        Some(name) => Interpolation::from_name(name)
+            .ok_or_else(|| format!("The interpolation {} is unknown.", name))?,
+        None => Interpolation::Trilinear,
+    };
+    let load = |path: &str| {
+        std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| Volume::from_nifti_bytes(&bytes).map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", path, e))
+    };
+    let moving = load(moving_path)?;
+    let reference = load(reference_path)?;
+    let transform = std::fs::read_to_string(transform_path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| {
+            parse_transform(&text, transform_path, &moving, &reference).map_err(|e| e.to_string())
+        })
+        .map_err(|e| format!("{}: {}", transform_path, e))?;
+
+    let resampled = resample(&moving, &reference, transform, interpolation);
+    std::fs::write(output_path, resampled.to_nifti_bytes())
+        .map_err(|e| format!("{}: {}", output_path, e))?;
+    println!("Saved the resampled volume to {}.", output_path);
+    Ok(())
+}
+
+/// Saves a cluster report as CSV and JSON in the working directory.
+fn save_cluster_report(report: &ClusterReport) {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let json = report.to_json().map_err(|e| e.to_string());
+    for (extension, contents) in [("csv", Ok(report.to_csv())), ("json", json)] {
+        let path = format!("clusters-{}.{}", timestamp, extension);
+        let saved = contents
+            .and_then(|contents| std::fs::write(&path, contents).map_err(|e| e.to_string()));
+        match saved {
+            Ok(_) => println!("Saved cluster report to {}.", path),
+            Err(e) => eprintln!("Failed to save cluster report to {}: {}", path, e),
+        }
+    }
+}
+
+/// Saves the current view as PNG in the working directory.
+///
+/// The screenshot is rendered at twice the window resolution.
+fn save_screenshot(state: &mut State) {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let path = format!("screenshot-{}.png", timestamp);
+
+    state.update();
+    match state.capture_png(state.width * 2, state.height * 2) {
+        Ok(png) => match std::fs::write(&path, png) {
+            Ok(_) => println!("Saved screenshot to {}.", path),
+            Err(e) => eprintln!("Failed to save screenshot to {}: {}", path, e),
+        },
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+/// Saves the drawn mesh in the working directory, with the overlay colors if the format has
+/// vertex colors.
+fn save_mesh(state: &State, format: MeshFormat) {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let path = format!("mesh-{}.{}", timestamp, format.extension());
+
+    let (vertices, indices) = state.mesh();
+    let colors = state.mesh_colors();
+    match export_mesh(format, vertices, indices, colors.as_deref())
+        .and_then(|bytes| std::fs::write(&path, bytes))
+    {
+        Ok(_) => println!("Saved mesh to {}.", path),
+        Err(e) => eprintln!("Failed to save mesh to {}: {}", path, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use wgpu_shape_renderer::{data::cluster::Connectivity, exporters::mesh::MeshFormat};
+
+    use super::{resample_volume, Options, Volume};
+
+    /// Parses all arguments as options and returns them with the number of arguments each took,
+    /// stopping at the first argument that is not an option.
+    fn parse_options(args: &[&str]) -> (Options, Vec<usize>) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut options = Options::default();
+        let mut counts = Vec::new();
+        let mut i = 0;
+        while let Some(count) = args
+            .get(i..)
+            .and_then(|args| options.parse(args, &mut Vec::new()))
+        {
+            counts.push(count);
+            i += count;
+        }
+        (options, counts)
+    }
+
+    #[test]
+    fn options_take_their_values() {
+        let (options, counts) = parse_options(&[
+            "--cublets",
+            "4",
+            "--outline",
+            "--seed",
+            "-2,-54.5,26",
+            "--connectivity",
+            "6",
+            "--export",
+            "obj",
+            "--layers",
+            "T1.nii",
+            "--outline",
+        ]);
+        assert_eq!(counts, [2, 1, 2, 2, 2, 1]);
+        assert_eq!(options.cublet_size, Some(4));
+        assert!(options.outline);
+        assert_eq!(options.seed, Some(Vec3::new(-2.0, -54.5, 26.0)));
+        assert_eq!(options.connectivity, Connectivity::Faces);
+        assert_eq!(options.export_format, MeshFormat::Obj);
+        assert!(options.layered);
+    }
+
+    #[test]
+    fn invalid_option_values_keep_the_defaults() {
+        let (options, counts) = parse_options(&[
+            "--seed",
+            "1,2",
+            "--connectivity",
+            "8",
+            "--export",
+            "vtk",
+            "--lut",
+            "missing.txt",
+            "--atlas",
+            "atlas.nii",
+            "--mask",
+        ]);
+        assert_eq!(counts, [2, 2, 2, 2, 2, 2]);
+        assert_eq!(options.seed, None);
+        assert_eq!(options.connectivity, Connectivity::Corners);
+        assert_eq!(options.export_format, MeshFormat::StlBinary);
+        assert!(options.lookup_table.is_none());
+        assert!(options.mask.is_none());
+    }
+
+    #[test]
+    fn resample_volume_with_fsl_transform() {
+        let moving_path = concat!(env!("CARGO_MANIFEST_DIR"), "/minimal.nii");
+        let directory =
+            std::env::temp_dir().join(format!("mri_cublet_resample_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        // A translation of 3 mm along y in FSL's scaled voxel coordinates, one voxel of
+        // minimal.nii.
+        let transform_path = directory.join("shift.mat");
+        std::fs::write(&transform_path, "1 0 0 0\n0 1 0 3\n0 0 1 0\n0 0 0 1\n").unwrap();
+        let output_path = directory.join("resampled.nii");
+        let args = [
+            moving_path,
+            moving_path,
+            transform_path.to_str().unwrap(),
+            output_path.to_str().unwrap(),
+            "nearest",
+        ]
+        .map(String::from);
+
+        resample_volume(&args).unwrap();
+        let moving = Volume::from_nifti_bytes(&std::fs::read(moving_path).unwrap()).unwrap();
+        let resampled = Volume::from_nifti_bytes(&std::fs::read(&output_path).unwrap()).unwrap();
+        assert!(resample_volume(&args[..3]).is_err());
+        let mut unknown = args.clone();
+        unknown[4] = "cubic".to_string();
+        assert!(resample_volume(&unknown).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(resampled.dims, moving.dims);
+        assert!(moving.data.iter().any(|&value| value != 0.0));
+        let [nx, ny, nz] = moving.dims;
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let expected = if y == 0 { 0.0 } else { moving.get(x, y - 1, z) };
+                    assert_eq!(resampled.get(x, y, z), expected, "({}, {}, {})", x, y, z);
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,37 @@
 #![cfg(target_arch = "wasm32")]
-use crate::render::{camera::OrbitCamera, state::State};
+use crate::data::{
+    cluster::{ClusterReport, Connectivity},
+    label_map::LabelMap,
+    lookup_table::LookupTable,
+    resample::{resample, Interpolation},
+    statistic::{StatThreshold, Statistic},
+    time_course::{
+        get_correlation_map, get_frame_times, get_mean_time_course, get_time_course,
+        get_time_course_at, Roi,
+    },
+    transform::parse_transform,
+    volume::Volume,
+};
+use crate::exporters::mesh::{export_mesh, MeshFormat};
+use crate::extras::math::vector3::Vector3;
+use crate::loaders::{mesh::load_mesh, overlay::load_overlay};
+use crate::render::{
+    camera::OrbitCamera,
+    cine::{CinePlayer, DEFAULT_FRAME_INTERVAL},
+    geometry::{
+        crosshair::get_crosshair_vertecies, cublets::get_cublet_instances,
+        isosurface::get_isosurface_vertecies, outline::get_outline_vertecies,
+        r#box::get_box_vertecies, slice::get_slice_vertecies,
+    },
+    label_surfaces::LabelSurfaces,
+    layers::{CompareSettings, Comparison, LayerId, LayerSettings, VolumeLayer},
+    overlay::{get_volume_scalars, Colormap, OverlaySettings},
+    scene::{Material, ObjectId, SceneObject},
+    settings::RenderSettings,
+    state::State,
+    vertex::{Instance, Vertex},
+};
+use std::sync::atomic::{AtomicU32, Ordering};
 use wasm_bindgen::{prelude::*, JsCast};
 use js_sys::{Array, Reflect, Float32Array};
 
@@ -11,60 +43,66 @@ extern "C" {
     fn log(s: &str);
 }
 
+/// The next numeric id that is assigned to a canvas via its `data-raw-handle` attribute.
+///
+/// The id `0` is reserved by `raw-window-handle`, so counting starts at `1`.
+static NEXT_RAW_HANDLE_ID: AtomicU32 = AtomicU32::new(1);
+
 /// Renders to a canvas using the `wgpu` **WebGL2** backend.
+///
+/// Every renderer owns its own [State], so several independent renderers can be placed on
+/// one page.
 #[wasm_bindgen]
 pub struct WebGLRenderer {
     state: State,
-}
 
-#[wasm_bindgen]
-impl WebGLRenderer {
-    /// Create a new [WebGLRenderer] instance for the given canvas id.
-    #[wasm_bindgen(constructor)]
-    pub fn create(canvas_id: &str, width: u32, height: u32) -> Self {
-        // Log rust panics to browser console. Debug only!
-        console_error_panic_hook::set_once();
+    /// The 4D volume shown by the mesh object, if any.
+    series: Option<TimeSeries>,
 
-        // Get the browser window.
-        let window = web_sys::window().expect("Can not get browser window.");
+    /// The statistical map that colors the mesh object with its threshold, if any.
+    statistical_map: Option<(Volume, Statistic, f32)>,
 
-        // Get the dom document.
-        let document = window.document().expect("Can not get html document.");
+    /// The crosshair object, created by the first [WebGLRenderer::set_crosshair].
+    crosshair: Option<ObjectId>,
 
-        // Get the canvas with the given id.
-        let canvas = document
-            .get_element_by_id(canvas_id)
-            .expect("The given canvas id was not found in the document.");
+    /// The label map shown by [WebGLRenderer::show_label_map] and its surfaces, if any.
+    label_map: Option<(LabelMap, LabelSurfaces)>,
 
-        // Canvas need to be of type HtmlCanvasElement.
-        let canvas: web_sys::HtmlCanvasElement = canvas
-            .dyn_into::<web_sys::HtmlCanvasElement>()
-            .expect("Failed to convert element to canvas.");
+    /// The label map loaded by [WebGLRenderer::load_atlas] to look up region names, if any.
+    atlas: Option<LabelMap>,
+}
 
-        // The numeric id for the `raw-window_handle` is retrieved from the id of
-        // the canvas minus the first 4 chars.
-        let mut id = canvas.id();
-        id.replace_range(0..4, "");
+/// A 4D volume whose displayed frame colors the mesh object.
+///
+/// The geometry is extracted once at the threshold, playing only uploads the voxels of the
+/// displayed frame to the volume layer that colors it.
+struct TimeSeries {
+    /// The layer that holds the volume with all frames.
+    layer: LayerId,
 
-        // The numeric id in the `raw_window_handle::web::WebHandle` has to be set
-        // inside the `data-raw-handle` attribute of the canvas.
-        canvas
-            .set_attribute("data-raw-handle", id.as_str())
-            .expect("Failed to assign numeric raw-window-handle id to canvas.");
+    /// The playback of the frames.
+    player: CinePlayer,
 
-        let id: u32 = id
-            .parse()
-            .expect("Failed to get numeric sequence from canvas id.");
-
-        let mut window_handle = raw_window_handle::WebWindowHandle::empty();
-        window_handle.id = id;
+    /// The seed correlation map that colors the isosurface, if any.
+    correlation: Option<Volume>,
+}
 
-        let display_handle = raw_window_handle::WebDisplayHandle::empty();
+#[wasm_bindgen]
+impl WebGLRenderer {
+    /// Create a new [WebGLRenderer] instance for the given canvas.
+    ///
+    /// The returned `Promise` resolves once the graphics adapter and device were acquired and
+    /// is rejected if no compatible adapter is available.
+    #[wasm_bindgen]
+    pub async fn create(
+        canvas: web_sys::HtmlCanvasElement,
+        width: u32,
+        height: u32,
+    ) -> Result<WebGLRenderer, JsError> {
+        // Log rust panics to browser console. Debug only!
+        console_error_panic_hook::set_once();
 
-        let wgpu_canvas = WgpuCanvas {
-            display_handle,
-            window_handle
-        };
+        let wgpu_canvas = WgpuCanvas::from_canvas(&canvas)?;
 
         let mut camera = OrbitCamera::new(
             2.0,
@@ -75,10 +113,26 @@ impl WebGLRenderer {
         );
         camera.bounds.min_distance = Some(1.1);
 
-        // State::new uses async code, so we're going to wait for it to finish
-        let state = pollster::block_on(State::new(&wgpu_canvas, width, height, camera));
+        let state = State::new(&wgpu_canvas, width, height, camera).await?;
+
+        Ok(Self {
+            state,
+            series: None,
+            statistical_map: None,
+            crosshair: None,
+            label_map: None,
+            atlas: None,
+        })
+    }
 
-        Self { state }
+    /// Create a new [WebGLRenderer] instance for the canvas with the given id.
+    #[wasm_bindgen]
+    pub async fn create_from_id(
+        canvas_id: String,
+        width: u32,
+        height: u32,
+    ) -> Result<WebGLRenderer, JsError> {
+        Self::create(find_canvas(&canvas_id)?, width, height).await
     }
 
     #[wasm_bindgen]
@@ -92,9 +146,779 @@ impl WebGLRenderer {
         self.state.update_box_size(vec);
     }
 
+    /// Replaces the drawn geometry with the isosurface of a NIfTI volume.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file.
+    /// * `threshold`: The intensity at which the surface is placed.
+    #[wasm_bindgen]
+    pub fn show_isosurface(&mut self, bytes: &[u8], threshold: f32) -> Result<(), JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let (vertices, indices) = get_isosurface_vertecies(&volume, threshold);
+        self.set_untextured_mesh(vertices, indices);
+        self.show_series(volume, threshold)
+    }
+
+    /// Replaces the drawn geometry with one cube per block of voxels of a NIfTI volume.
+    ///
+    /// The cubes are colored with the hot colormap from the threshold to the largest intensity.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file.
+    /// * `threshold`: The smallest intensity of a block that is shown.
+    /// * `block_size`: The number of voxels along each axis that are combined into one cube.
+    #[wasm_bindgen]
+    pub fn show_cublets(
+        &mut self,
+        bytes: &[u8],
+        threshold: f32,
+        block_size: usize,
+    ) -> Result<(), JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let instances = hot_cublet_instances(&volume, threshold, block_size);
+        let (vertices, indices) =
+            get_box_vertecies(0, glam::Vec3::ZERO, glam::Vec3::ONE, glam::Vec3::ZERO);
+
+        let id = self.state.mesh_object();
+        self.state.set_mesh(vertices, indices);
+        let object = self.state.object_mut(id).expect("The mesh object always exists.");
+        object.material = Material::default();
+        object.set_instances(Some(instances));
+        self.state.fit_camera(&[id]);
+        self.show_series(volume, threshold)
+    }
+
+    /// Loads a surface mesh and shows it instead of the current geometry.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a GIfTI, FreeSurfer, OBJ, STL or PLY file.
+    /// * `file_name`: The name of the file, which selects the format.
+    #[wasm_bindgen]
+    pub fn show_mesh(&mut self, bytes: &[u8], file_name: &str) -> Result<(), JsError> {
+        let (vertices, indices) = load_mesh(bytes, file_name)?;
+        self.set_untextured_mesh(vertices, indices);
+        self.clear_series();
+        Ok(())
+    }
+
+    /// Loads a surface mesh and adds it to the scene next to the current geometry.
+    ///
+    /// Returns the id of the new object.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a GIfTI, FreeSurfer, OBJ, STL or PLY file.
+    /// * `file_name`: The name of the file, which selects the format.
+    #[wasm_bindgen]
+    pub fn add_mesh(&mut self, bytes: &[u8], file_name: &str) -> Result<u32, JsError> {
+        let (vertices, indices) = load_mesh(bytes, file_name)?;
+        let id = self
+            .state
+            .add_object(SceneObject::new(file_name, vertices, indices));
+        Ok(id.0)
+    }
+
+    /// Adds a wireframe box with tick marks in millimetres around a NIfTI volume in world space.
+    ///
+    /// Returns the id of the new object.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file.
+    #[wasm_bindgen]
+    pub fn add_outline(&mut self, bytes: &[u8]) -> Result<u32, JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let (vertices, indices) = get_outline_vertecies(&volume);
+        let mut object = SceneObject::new("outline", vertices, indices);
+        object.transform = volume.box_transform();
+        object.material = Material {
+            lit: false,
+            ..Material::from_color([1.0, 1.0, 0.6, 1.0])
+        };
+        Ok(self.state.add_object(object).0)
+    }
+
+    /// Returns the number of frames of the volume shown by [WebGLRenderer::show_isosurface] or
+    /// [WebGLRenderer::show_cublets], which is `1` for 3D volumes and meshes.
+    #[wasm_bindgen]
+    pub fn frame_count(&self) -> usize {
+        self.series
+            .as_ref()
+            .map_or(1, |series| series.player.frame_count())
+    }
+
+    /// Returns the index of the displayed frame of a 4D volume.
+    #[wasm_bindgen]
+    pub fn frame(&self) -> usize {
+        self.series
+            .as_ref()
+            .map_or(0, |series| series.player.frame())
+    }
+
+    /// Shows another frame of a 4D volume.
+    ///
+    /// Only the voxels of the frame are uploaded, the geometry of the mesh object is kept.
+    ///
+    /// Arguments:
+    ///
+    /// * `frame`: The index of the frame, which is clamped to the frame count.
+    #[wasm_bindgen]
+    pub fn set_frame(&mut self, frame: usize) {
+        if let Some(series) = self.series.as_mut() {
+            series.player.set_frame(frame);
+            series.show(&mut self.state);
+        }
+    }
+
+    /// Returns the time between two frames in seconds, taken from the repetition time of the
+    /// file.
+    #[wasm_bindgen]
+    pub fn frame_interval(&self) -> f32 {
+        self.series
+            .as_ref()
+            .map_or(DEFAULT_FRAME_INTERVAL, |series| series.player.interval)
+    }
+
+    /// Starts playing the frames of a 4D volume, see [WebGLRenderer::advance].
+    #[wasm_bindgen]
+    pub fn play(&mut self) {
+        if let Some(series) = self.series.as_mut() {
+            series.player.play();
+        }
+    }
+
+    /// Stops playing the frames of a 4D volume and keeps the displayed one.
+    #[wasm_bindgen]
+    pub fn pause(&mut self) {
+        if let Some(series) = self.series.as_mut() {
+            series.player.pause();
+        }
+    }
+
+    /// Returns whether the frames of a 4D volume are played.
+    #[wasm_bindgen]
+    pub fn is_playing(&self) -> bool {
+        self.series
+            .as_ref()
+            .is_some_and(|series| series.player.is_playing())
+    }
+
+    /// Advances the playback of a 4D volume, e.g. from a `requestAnimationFrame` callback.
+    ///
+    /// Returns `true` if another frame is shown and the canvas has to be rendered again.
+    ///
+    /// Arguments:
+    ///
+    /// * `seconds`: The time that passed since the last call in seconds.
+    #[wasm_bindgen]
+    pub fn advance(&mut self, seconds: f32) -> bool {
+        let Some(series) = self.series.as_mut() else {
+            return false;
+        };
+        if series.player.advance(seconds).is_none() {
+            return false;
+        }
+        series.show(&mut self.state);
+        true
+    }
+
+    /// Returns the time of each frame of the shown 4D volume in seconds, e.g. for the x axis of
+    /// a time course plot.
+    ///
+    /// Volumes without a repetition time return the frame indices instead.
+    #[wasm_bindgen]
+    pub fn frame_times(&self) -> Result<Float32Array, JsError> {
+        let times = get_frame_times(self.series_volume()?);
+        Ok(Float32Array::from(times.as_slice()))
+    }
+
+    /// Returns the intensities of a voxel of the shown 4D volume in every frame.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`, `z`: The voxel index.
+    #[wasm_bindgen]
+    pub fn time_course(&self, x: usize, y: usize, z: usize) -> Result<Float32Array, JsError> {
+        let values = get_time_course(self.series_volume()?, [x, y, z])?;
+        Ok(Float32Array::from(values.as_slice()))
+    }
+
+    /// Returns the intensities of the voxel of the shown 4D volume closest to a position in
+    /// every frame.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`, `z`: The position in world coordinates in millimetres.
+    #[wasm_bindgen]
+    pub fn time_course_at(&self, x: f32, y: f32, z: f32) -> Result<Float32Array, JsError> {
+        let position = glam::Vec3::new(x, y, z);
+        let values = get_time_course_at(self.series_volume()?, position)?;
+        Ok(Float32Array::from(values.as_slice()))
+    }
+
+    /// Returns the mean intensity of the voxels of the shown 4D volume within a sphere in
+    /// every frame.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`, `z`: The center of the sphere in world coordinates in millimetres.
+    /// * `radius`: The radius of the sphere in millimetres.
+    #[wasm_bindgen]
+    pub fn sphere_time_course(
+        &self,
+        x: f32,
+        y: f32,
+        z: f32,
+        radius: f32,
+    ) -> Result<Float32Array, JsError> {
+        let roi = Roi::Sphere {
+            center: glam::Vec3::new(x, y, z),
+            radius,
+        };
+        let values = get_mean_time_course(self.series_volume()?, &roi)?;
+        Ok(Float32Array::from(values.as_slice()))
+    }
+
+    /// Returns the mean intensity of the voxels of the shown 4D volume within a mask in every
+    /// frame.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file on the voxel grid of the 4D volume, whose
+    ///   voxels that are not zero belong to the mask.
+    #[wasm_bindgen]
+    pub fn mask_time_course(&self, bytes: &[u8]) -> Result<Float32Array, JsError> {
+        let roi = Roi::from_mask_volume(&Volume::from_nifti_bytes(bytes)?);
+        let values = get_mean_time_course(self.series_volume()?, &roi)?;
+        Ok(Float32Array::from(values.as_slice()))
+    }
+
+    /// Colors the isosurface of the shown 4D volume by the Pearson correlation of every voxel
+    /// with a seed voxel, using a diverging colormap from -1 to 1.
+    ///
+    /// Cublets are not colored, but the map can still be exported with
+    /// [WebGLRenderer::correlation_map].
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`, `z`: The position of the seed voxel in world coordinates in millimetres.
+    /// * `threshold`: The smallest magnitude of a correlation that is shown.
+    /// * `mask`: The contents of a `.nii` file on the voxel grid of the 4D volume whose voxels
+    ///   that are not zero are correlated, or `undefined` to correlate every voxel.
+    #[wasm_bindgen]
+    pub fn show_seed_correlation(
+        &mut self,
+        x: f32,
+        y: f32,
+        z: f32,
+        threshold: f32,
+        mask: Option<Vec<u8>>,
+    ) -> Result<(), JsError> {
+        let mask = match mask {
+            Some(bytes) => Some(Roi::from_mask_volume(&Volume::from_nifti_bytes(&bytes)?)),
+            None => None,
+        };
+        let volume = self.series_volume()?;
+        let seed = get_time_course_at(volume, glam::Vec3::new(x, y, z))?;
+        let correlation = get_correlation_map(volume, &seed, mask.as_ref())?;
+        let id = self.state.mesh_object();
+        let object = self.state.object_mut(id).expect("The mesh object always exists.");
+        if object.instances().is_none() {
+            let scalars = get_volume_scalars(&correlation, object.vertices());
+            object.set_scalars(Some(scalars))?;
+        }
+        if let Some(series) = self.series.as_mut() {
+            series.correlation = Some(correlation);
+        }
+        self.state.set_overlay(OverlaySettings::symmetric(
+            Colormap::BlueRed,
+            1.0,
+            threshold,
+        ));
+        Ok(())
+    }
+
+    /// Returns the seed correlation map of [WebGLRenderer::show_seed_correlation] as the
+    /// contents of a `.nii` file, e.g. to offer it as a download.
+    #[wasm_bindgen]
+    pub fn correlation_map(&self) -> Result<Vec<u8>, JsError> {
+        self.series
+            .as_ref()
+            .and_then(|series| series.correlation.as_ref())
+            .map(|map| map.to_nifti_bytes())
+            .ok_or_else(|| JsError::new("No seed correlation was calculated."))
+    }
+
+    /// Resamples a moving volume through an FSL `.mat` or ITK `.txt`/`.tfm` transform onto the
+    /// grid of a reference and returns the contents of a `.nii` file.
+    ///
+    /// Arguments:
+    ///
+    /// * `moving`: The contents of the `.nii` file that is resampled.
+    /// * `reference`: The contents of the `.nii` file whose grid the result has.
+    /// * `transform`: The contents of the transform file.
+    /// * `file_name`: The name of the transform file, which tells its format.
+    /// * `interpolation`: `nearest`, `trilinear` or `sinc`.
+    #[wasm_bindgen]
+    pub fn resample(
+        moving: &[u8],
+        reference: &[u8],
+        transform: &str,
+        file_name: &str,
+        interpolation: &str,
+    ) -> Result<Vec<u8>, JsError> {
+        let interpolation = Interpolation::from_name(interpolation).ok_or_else(|| {
+            JsError::new(&format!("The interpolation {} is unknown.", interpolation))
+        })?;
+        let moving = Volume::from_nifti_bytes(moving)?;
+        let reference = Volume::from_nifti_bytes(reference)?;
+        let transform = parse_transform(transform, file_name, &moving, &reference)?;
+        Ok(resample(&moving, &reference, transform, interpolation).to_nifti_bytes())
+    }
+
+    /// Colors the mesh object with a NIfTI t, z, F or correlation map given by its intent code.
+    ///
+    /// Positive values are colored red to yellow and negative values blue to light blue, both
+    /// above the same threshold on the magnitude. Returns the threshold as value of the
+    /// statistic.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file in the world space of the mesh.
+    /// * `threshold`: The smallest magnitude of the statistic that is shown, or a p-value.
+    /// * `p_value`: Whether `threshold` is a one-sided p-value, which is converted with the
+    ///   degrees of freedom of the map.
+    #[wasm_bindgen]
+    pub fn show_statistical_map(
+        &mut self,
+        bytes: &[u8],
+        threshold: f32,
+        p_value: bool,
+    ) -> Result<f32, JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let statistic = Statistic::from_header(&volume.header).ok_or_else(|| {
+            JsError::new(&format!(
+                "The NIfTI intent code {} is not a supported statistic.",
+                volume.header.intent_code
+            ))
+        })?;
+        let threshold = statistic.threshold_value(if p_value {
+            StatThreshold::PValue(threshold)
+        } else {
+            StatThreshold::Value(threshold)
+        });
+
+        let id = self.state.mesh_object();
+        let object = self.state.object_mut(id).expect("The mesh object always exists.");
+        let scalars = get_volume_scalars(&volume, object.vertices());
+        object.set_scalars(Some(scalars))?;
+        let (min, max) = volume.min_max();
+        self.state.set_overlay(OverlaySettings::statistic(
+            &statistic,
+            threshold,
+            max.max(-min),
+        ));
+        self.statistical_map = Some((volume, statistic, threshold));
+        Ok(threshold)
+    }
+
+    /// Returns the clusters of the map shown by [WebGLRenderer::show_statistical_map] above its
+    /// threshold, with their size, peak and center of mass in world coordinates.
+    ///
+    /// Arguments:
+    ///
+    /// * `connectivity`: The number of neighbors of a voxel that belong to the same cluster,
+    ///   `6`, `18` or `26`.
+    /// * `format`: Either `"csv"` or `"json"`.
+    #[wasm_bindgen]
+    pub fn cluster_report(&self, connectivity: usize, format: &str) -> Result<String, JsError> {
+        let (volume, statistic, threshold) = self
+            .statistical_map
+            .as_ref()
+            .ok_or_else(|| JsError::new("No statistical map is shown."))?;
+        let connectivity = Connectivity::from_neighbors(connectivity)
+            .ok_or_else(|| JsError::new("The connectivity has to be 6, 18 or 26."))?;
+        let report = ClusterReport::new(volume, *threshold, connectivity, statistic.is_signed());
+        match format {
+            "csv" => Ok(report.to_csv()),
+            "json" => Ok(report.to_json()?),
+            _ => Err(JsError::new(&format!("Unknown report format {}.", format))),
+        }
+    }
+
+    /// Moves a crosshair and the camera target to a position, e.g. the peak of a cluster from
+    /// [WebGLRenderer::cluster_report].
+    ///
+    /// The crosshair spans the bounding box of the mesh object.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`, `z`: The position in world coordinates in millimetres.
+    #[wasm_bindgen]
+    pub fn set_crosshair(&mut self, x: f32, y: f32, z: f32) {
+        let position = glam::Vec3::new(x, y, z);
+        // The crosshair is created again if it was removed with [WebGLRenderer::remove_object].
+        let id = match self.crosshair.filter(|&id| self.state.object(id).is_some()) {
+            Some(id) => id,
+            None => {
+                let extent = self
+                    .state
+                    .object(self.state.mesh_object())
+                    .and_then(|object| object.bounding_box())
+                    .map_or(glam::Vec3::ONE, |(min, max)| max - min);
+                let (vertices, indices) = get_crosshair_vertecies(extent);
+                let mut object = SceneObject::new("crosshair", vertices, indices);
+                object.material = Material {
+                    lit: false,
+                    ..Material::from_color([0.2, 1.0, 0.2, 1.0])
+                };
+                let id = self.state.add_object(object);
+                self.crosshair = Some(id);
+                id
+            }
+        };
+        if let Some(object) = self.state.object_mut(id) {
+            object.transform = glam::Mat4::from_translation(position);
+            object.visible = true;
+        }
+        self.state.camera.target = Vector3::from_vec3(position);
+    }
+
+    /// Hides the crosshair shown by [WebGLRenderer::set_crosshair].
+    #[wasm_bindgen]
+    pub fn hide_crosshair(&mut self) {
+        if let Some(object) = self.crosshair.and_then(|id| self.state.object_mut(id)) {
+            object.visible = false;
+        }
+    }
+
+    /// Shows a label map, e.g. a segmentation or an atlas, as one surface per label colored
+    /// from its lookup table. A label map shown before is removed.
+    ///
+    /// Returns the labels that are shown.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file with integer labels.
+    /// * `lookup_table`: The contents of a FreeSurfer color table like `FreeSurferColorLUT.txt`
+    ///   or an ITK-SNAP label description file.
+    #[wasm_bindgen]
+    pub fn show_label_map(
+        &mut self,
+        bytes: &[u8],
+        lookup_table: &str,
+    ) -> Result<Vec<u32>, JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let label_map = LabelMap::new(volume, LookupTable::parse(lookup_table)?);
+        if let Some((_, surfaces)) = self.label_map.take() {
+            surfaces.remove(&mut self.state);
+        }
+        let surfaces = LabelSurfaces::add_to_scene(&mut self.state, &label_map);
+        let labels = surfaces.objects().map(|(label, _)| label).collect();
+        self.label_map = Some((label_map, surfaces));
+        Ok(labels)
+    }
+
+    /// Returns the lookup table of the label map as a plain object, e.g. `{ labels: { 17: {
+    /// name: "Left-Hippocampus", color: [220, 216, 20], opacity: 1, visible: true } } }`.
+    #[wasm_bindgen]
+    pub fn label_table(&self) -> Result<JsValue, JsError> {
+        let (label_map, _) = self
+            .label_map
+            .as_ref()
+            .ok_or_else(|| JsError::new("No label map is shown."))?;
+        serde_wasm_bindgen::to_value(&label_map.table).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Shows or hides a label of the label map. Returns `false` if there is no such label.
+    #[wasm_bindgen]
+    pub fn set_label_visible(&mut self, label: u32, visible: bool) -> bool {
+        let Some((label_map, surfaces)) = self.label_map.as_mut() else {
+            return false;
+        };
+        let found = label_map.table.set_visible(label, visible);
+        surfaces.apply(&mut self.state, &label_map.table);
+        found
+    }
+
+    /// Changes the opacity of a label of the label map from `0.0` for transparent to `1.0` for
+    /// opaque. Returns `false` if there is no such label.
+    #[wasm_bindgen]
+    pub fn set_label_opacity(&mut self, label: u32, opacity: f32) -> bool {
+        let Some((label_map, surfaces)) = self.label_map.as_mut() else {
+            return false;
+        };
+        let found = label_map.table.set_opacity(label, opacity);
+        surfaces.apply(&mut self.state, &label_map.table);
+        found
+    }
+
+    /// Loads an atlas whose region names are looked up by [WebGLRenderer::atlas_label] without
+    /// showing it. The atlas may have another grid than the displayed volumes.
+    ///
+    /// Returns the number of labels of the lookup table.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file with integer labels.
+    /// * `lookup_table`: The contents of a FreeSurfer color table or an ITK-SNAP label
+    ///   description file.
+    #[wasm_bindgen]
+    pub fn load_atlas(&mut self, bytes: &[u8], lookup_table: &str) -> Result<usize, JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let atlas = LabelMap::new(volume, LookupTable::parse(lookup_table)?);
+        let count = atlas.table.labels.len();
+        self.atlas = Some(atlas);
+        Ok(count)
+    }
+
+    /// Returns the name of the region at a world position, e.g. `"Left-Hippocampus"`.
+    ///
+    /// The atlas loaded by [WebGLRenderer::load_atlas] is used, or the label map shown by
+    /// [WebGLRenderer::show_label_map] if no atlas was loaded. Returns `undefined` outside of
+    /// the atlas and for labels that are not in its lookup table.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`, `z`: The position in world coordinates in millimetres, e.g. from
+    ///   [WebGLRenderer::pick].
+    #[wasm_bindgen]
+    pub fn atlas_label(&self, x: f32, y: f32, z: f32) -> Option<String> {
+        let atlas = self
+            .atlas
+            .as_ref()
+            .or(self.label_map.as_ref().map(|(label_map, _)| label_map))?;
+        atlas
+            .entry_at(glam::Vec3::new(x, y, z))
+            .map(|label| label.name.clone())
+    }
+
+    /// Returns the world position `[x, y, z]` of the closest visible surface under a pixel of
+    /// the canvas, or `undefined` if no object is hit.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`: The pixel position from the top left corner of the canvas, e.g. `offsetX`
+    ///   and `offsetY` of a mouse event scaled by `devicePixelRatio`.
+    #[wasm_bindgen]
+    pub fn pick(&self, x: f32, y: f32) -> Option<Vec<f32>> {
+        self.state
+            .pick(x, y)
+            .map(|position| position.to_array().to_vec())
+    }
+
+    /// Adds a volume on top of the volume layers and returns the id of the layer.
+    ///
+    /// The layers are drawn on slice planes, see [WebGLRenderer::show_slices], at most four
+    /// layers are shown.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file.
+    /// * `settings`: The layer settings as a plain object, for example
+    ///   `{ colormap: "hot", min: 2, max: 8, threshold: 2.3, opacity: 0.7, blend: "additive" }`.
+    ///   If `undefined` the window spans the intensities, in gray for the first layer.
+    #[wasm_bindgen]
+    pub fn add_layer(&mut self, bytes: &[u8], settings: JsValue) -> Result<u32, JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let settings = if settings.is_undefined() {
+            let colormap = if self.state.layers().next().is_none() {
+                Colormap::Gray
+            } else {
+                Colormap::Hot
+            };
+            LayerSettings::fit(colormap, &volume)
+        } else {
+            serde_wasm_bindgen::from_value(settings).map_err(|e| JsError::new(&e.to_string()))?
+        };
+        let id = self.state.add_layer(VolumeLayer::new(volume, settings))?;
+        Ok(id.0)
+    }
+
+    /// Adds a label map on top of the volume layers and returns the id of the layer.
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a `.nii` file with integer labels.
+    /// * `lookup_table`: The contents of a FreeSurfer color table or an ITK-SNAP label
+    ///   description file.
+    #[wasm_bindgen]
+    pub fn add_label_layer(&mut self, bytes: &[u8], lookup_table: &str) -> Result<u32, JsError> {
+        let volume = Volume::from_nifti_bytes(bytes)?;
+        let label_map = LabelMap::new(volume, LookupTable::parse(lookup_table)?);
+        let id = self
+            .state
+            .add_layer(VolumeLayer::from_label_map(label_map))?;
+        Ok(id.0)
+    }
+
+    /// Replaces the settings of a volume layer given as a plain object. Missing fields are
+    /// taken from the defaults. Returns `false` if there is no such layer.
+    #[wasm_bindgen]
+    pub fn set_layer_settings(&mut self, id: u32, settings: JsValue) -> Result<bool, JsError> {
+        let settings: LayerSettings =
+            serde_wasm_bindgen::from_value(settings).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(self.state.set_layer_settings(LayerId(id), settings))
+    }
+
+    /// Returns the settings of a volume layer as a plain object.
+    #[wasm_bindgen]
+    pub fn layer_settings(&self, id: u32) -> Result<JsValue, JsError> {
+        let layer = self
+            .state
+            .layer(LayerId(id))
+            .ok_or_else(|| JsError::new(&format!("There is no layer {}.", id)))?;
+        serde_wasm_bindgen::to_value(&layer.settings).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Moves a volume layer to another position in the stack, `0` for the bottom. Returns
+    /// `false` if there is no such layer.
     #[wasm_bindgen]
-    pub fn render(&mut self) {
-        self.state.render().expect("Failed to render.");
+    pub fn move_layer(&mut self, id: u32, index: usize) -> bool {
+        self.state.move_layer(LayerId(id), index)
+    }
+
+    /// Removes a volume layer. Returns `false` if there is no such layer.
+    #[wasm_bindgen]
+    pub fn remove_layer(&mut self, id: u32) -> bool {
+        self.state.remove_layer(LayerId(id)).is_some()
+    }
+
+    /// Compares two volume layers, e.g. a registered image with its reference. Returns `false`
+    /// if the layers are missing or the same.
+    ///
+    /// Arguments:
+    ///
+    /// * `reference`: The layer the moving layer is compared with.
+    /// * `moving`: The layer that is compared with the reference.
+    /// * `settings`: The compare settings as a plain object, for example
+    ///   `{ mode: "checkerboard", tile_size: 10 }`, `{ mode: "curtain", divider: 0.5 }` or
+    ///   `{ mode: "difference", difference_limit: 100 }`.
+    #[wasm_bindgen]
+    pub fn set_comparison(
+        &mut self,
+        reference: u32,
+        moving: u32,
+        settings: JsValue,
+    ) -> Result<bool, JsError> {
+        let settings: CompareSettings =
+            serde_wasm_bindgen::from_value(settings).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(self.state.set_comparison(Some(Comparison {
+            reference: LayerId(reference),
+            moving: LayerId(moving),
+            settings,
+        })))
+    }
+
+    /// Ends the comparison of two volume layers.
+    #[wasm_bindgen]
+    pub fn clear_comparison(&mut self) {
+        self.state.set_comparison(None);
+    }
+
+    /// Moves the curtain divider of the comparison, e.g. while it is dragged. Returns `false`
+    /// if no layers are compared.
+    ///
+    /// Arguments:
+    ///
+    /// * `divider`: The position of the divider from `0.0` at the left to `1.0` at the right
+    ///   edge of the canvas.
+    #[wasm_bindgen]
+    pub fn set_curtain(&mut self, divider: f32) -> bool {
+        let Some(comparison) = self.state.comparison().copied() else {
+            return false;
+        };
+        self.state.set_comparison(Some(Comparison {
+            settings: CompareSettings {
+                divider: divider.clamp(0.0, 1.0),
+                ..comparison.settings
+            },
+            ..comparison
+        }))
+    }
+
+    /// Adds three unlit slice planes through the center of the bottom volume layer, which show
+    /// the volume layers, and returns their object ids.
+    #[wasm_bindgen]
+    pub fn show_slices(&mut self) -> Result<Vec<u32>, JsError> {
+        let (_, underlay) = self
+            .state
+            .layers()
+            .next()
+            .ok_or_else(|| JsError::new("No volume layer is shown."))?;
+        let planes: Vec<SceneObject> = (0..3)
+            .map(|axis| {
+                let volume = &underlay.volume;
+                let center = ((volume.dims[axis] as f32 - 1.0) / 2.0).round();
+                let (vertices, indices) = get_slice_vertecies(volume, axis, center);
+                let mut object = SceneObject::new("slice", vertices, indices);
+                object.transform = volume.affine;
+                object.material = Material {
+                    lit: false,
+                    layered: true,
+                    ..Material::from_color([0.0, 0.0, 0.0, 1.0])
+                };
+                object
+            })
+            .collect();
+        Ok(planes
+            .into_iter()
+            .map(|object| self.state.add_object(object).0)
+            .collect())
+    }
+
+    /// Shows or hides an object of the scene.
+    #[wasm_bindgen]
+    pub fn set_object_visible(&mut self, id: u32, visible: bool) {
+        if let Some(object) = self.state.object_mut(ObjectId(id)) {
+            object.visible = visible;
+        }
+    }
+
+    /// Removes an object from the scene. Returns `false` if there is no such object.
+    #[wasm_bindgen]
+    pub fn remove_object(&mut self, id: u32) -> bool {
+        self.state.remove_object(ObjectId(id)).is_some()
+    }
+
+    /// Moves the camera so that all visible objects are shown.
+    #[wasm_bindgen]
+    pub fn fit_camera(&mut self) {
+        self.state.fit_camera(&[]);
+    }
+
+    /// Exports the drawn mesh so that it can be offered as a download.
+    ///
+    /// OBJ and PLY files contain the overlay colors of the vertices.
+    ///
+    /// Arguments:
+    ///
+    /// * `format`: One of `"stl"`, `"stl-ascii"`, `"obj"` or `"ply"`.
+    #[wasm_bindgen]
+    pub fn export_mesh(&self, format: &str) -> Result<Vec<u8>, JsError> {
+        let format = MeshFormat::from_name(format)
+            .ok_or_else(|| JsError::new(&format!("Unknown mesh format: {}", format)))?;
+        let (vertices, indices) = self.state.mesh();
+        let colors = self.state.mesh_colors();
+        Ok(export_mesh(format, vertices, indices, colors.as_deref())?)
+    }
+
+    /// Draws the scene to the canvas.
+    ///
+    /// A lost surface is configured again and drawn by the next call, other errors like an
+    /// exhausted device memory are returned.
+    #[wasm_bindgen]
+    pub fn render(&mut self) -> Result<(), JsError> {
+        match self.state.render() {
+            Err(wgpu::SurfaceError::Lost) => {
+                self.state.resize(self.state.width, self.state.height);
+                Ok(())
+            }
+            result => Ok(result?),
+        }
     }
 
     #[wasm_bindgen]
@@ -103,6 +927,99 @@ impl WebGLRenderer {
         self.state.resize(new_width, new_height);
     }
 
+    /// Renders the scene at the given resolution and returns it as PNG bytes.
+    #[wasm_bindgen]
+    pub fn capture(&mut self, width: u32, height: u32) -> Result<Vec<u8>, JsError> {
+        Ok(self.state.capture_png(width, height)?)
+    }
+
+    /// Applies render settings given as a plain object, for example
+    /// `{ background: { type: "color", color: [0, 0, 0, 1] }, shininess: 16 }`.
+    ///
+    /// Missing fields are taken from the defaults.
+    #[wasm_bindgen]
+    pub fn set_render_settings(&mut self, settings: JsValue) -> Result<(), JsError> {
+        let settings: RenderSettings = serde_wasm_bindgen::from_value(settings)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        self.state.set_settings(settings);
+        Ok(())
+    }
+
+    /// Returns the current render settings as a plain object.
+    #[wasm_bindgen]
+    pub fn render_settings(&self) -> Result<JsValue, JsError> {
+        serde_wasm_bindgen::to_value(self.state.settings())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Loads per-vertex scalars and colors the drawn mesh with them.
+    ///
+    /// The colormap range is fitted to the scalars and can be changed with [WebGLRenderer::set_overlay].
+    ///
+    /// Arguments:
+    ///
+    /// * `bytes`: The contents of a FreeSurfer curvature, `.mgh`/`.mgz` or GIfTI func/shape file.
+    /// * `file_name`: The name of the file, which selects the format.
+    #[wasm_bindgen]
+    pub fn show_overlay(&mut self, bytes: &[u8], file_name: &str) -> Result<(), JsError> {
+        let scalars = load_overlay(bytes, file_name)?;
+        let colormap = if file_name.ends_with("curv") || file_name.ends_with("sulc") {
+            Colormap::Gray
+        } else {
+            Colormap::Hot
+        };
+        let overlay = OverlaySettings::fit(colormap, &scalars);
+        self.state.set_scalars(Some(scalars))?;
+        self.state.set_overlay(overlay);
+        Ok(())
+    }
+
+    /// Applies overlay settings given as a plain object, for example
+    /// `{ colormap: "red_yellow", min: 2.3, max: 5, threshold: 2.3 }`.
+    ///
+    /// Missing fields are taken from the defaults.
+    #[wasm_bindgen]
+    pub fn set_overlay(&mut self, overlay: JsValue) -> Result<(), JsError> {
+        let overlay: OverlaySettings = serde_wasm_bindgen::from_value(overlay)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        self.state.set_overlay(overlay);
+        Ok(())
+    }
+
+    /// Returns the current overlay settings as a plain object.
+    #[wasm_bindgen]
+    pub fn overlay(&self) -> Result<JsValue, JsError> {
+        serde_wasm_bindgen::to_value(self.state.overlay())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Enables or disables multisample anti-aliasing, `undefined` restores the detected default.
+    #[wasm_bindgen]
+    pub fn set_msaa(&mut self, enabled: Option<bool>) {
+        self.state.set_msaa(enabled);
+    }
+
+    /// Returns whether multisample anti-aliasing is currently used.
+    #[wasm_bindgen]
+    pub fn msaa(&self) -> bool {
+        self.state.msaa()
+    }
+
+    /// Enables or disables indexed drawing, `undefined` restores the detected default.
+    ///
+    /// WebGL2 always reports full support, so browsers whose indexed draws misbehave, like
+    /// Safari, have to disable it with `false`.
+    #[wasm_bindgen]
+    pub fn set_indexed(&mut self, enabled: Option<bool>) {
+        self.state.set_indexed(enabled);
+    }
+
+    /// Returns whether the geometry is drawn with an index buffer.
+    #[wasm_bindgen]
+    pub fn indexed(&self) -> bool {
+        self.state.indexed()
+    }
+
     #[wasm_bindgen]
     pub fn add_distance(&mut self, delta: f32) {
         self.state.camera.add_distance(delta);
@@ -119,6 +1036,98 @@ impl WebGLRenderer {
     }
 }
 
+impl WebGLRenderer {
+    /// Replaces the geometry of the mesh object and drops the texture of the initial box, whose
+    /// texture coordinates do not apply to other meshes.
+    ///
+    /// Arguments:
+    ///
+    /// * `vertices`: The vertices of the new mesh.
+    /// * `indices`: The indices of the triangle list.
+    fn set_untextured_mesh(&mut self, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        let id = self.state.mesh_object();
+        self.state.set_mesh(vertices, indices);
+        if let Some(object) = self.state.object_mut(id) {
+            object.material = Material::default();
+        }
+    }
+
+    /// Returns the 4D volume shown by the mesh object.
+    fn series_volume(&self) -> Result<&Volume, JsError> {
+        self.series
+            .as_ref()
+            .and_then(|series| self.state.layer(series.layer))
+            .map(|layer| &layer.volume)
+            .ok_or_else(|| JsError::new("No 4D volume is shown."))
+    }
+
+    /// Replaces the time series by a volume shown by the mesh object, which is only kept if it
+    /// has several frames.
+    ///
+    /// Arguments:
+    ///
+    /// * `volume`: The volume whose geometry the mesh object shows.
+    /// * `threshold`: The intensity of the isosurface or the smallest intensity of a cublet.
+    fn show_series(&mut self, volume: Volume, threshold: f32) -> Result<(), JsError> {
+        self.clear_series();
+        self.series = TimeSeries::new(&mut self.state, volume, threshold)?;
+        Ok(())
+    }
+
+    /// Drops the time series and removes its volume layer.
+    fn clear_series(&mut self) {
+        if let Some(series) = self.series.take() {
+            self.state.remove_layer(series.layer);
+        }
+    }
+}
+
+impl TimeSeries {
+    /// Adds the volume as layer that colors the mesh object with the hot colormap from the
+    /// threshold to the largest intensity of all frames.
+    ///
+    /// Returns a paused time series, or [None] if the volume has a single frame.
+    ///
+    /// Arguments:
+    ///
+    /// * `state`: The state whose mesh object shows the geometry of the volume.
+    /// * `volume`: The volume with all frames.
+    /// * `threshold`: Voxels below the threshold leave the mesh object uncolored.
+    fn new(state: &mut State, volume: Volume, threshold: f32) -> Result<Option<Self>, JsError> {
+        if volume.frame_count < 2 {
+            return Ok(None);
+        }
+        let player = CinePlayer::for_volume(&volume);
+        let settings = LayerSettings::time_series(&volume, threshold);
+        let layer = state.add_layer(VolumeLayer::new(volume, settings))?;
+        let id = state.mesh_object();
+        if let Some(object) = state.object_mut(id) {
+            object.material.layered = true;
+        }
+        Ok(Some(Self {
+            layer,
+            player,
+            correlation: None,
+        }))
+    }
+
+    /// Uploads the frame of the player to the volume layer.
+    fn show(&self, state: &mut State) {
+        state.set_layer_frame(self.layer, self.player.frame());
+    }
+}
+
+/// Returns the cublets of the displayed frame of a volume, colored with the hot colormap from
+/// the threshold to the largest intensity of all frames.
+fn hot_cublet_instances(volume: &Volume, threshold: f32, block_size: usize) -> Vec<Instance> {
+    let (_, max) = volume.series_min_max();
+    let range = (max - threshold).max(f32::EPSILON);
+    get_cublet_instances(volume, threshold, block_size, |value| {
+        let [r, g, b] = Colormap::Hot.color((value - threshold) / range);
+        [r, g, b, 1.0]
+    })
+}
+
 /// Implement [raw_window_handle::HasRawWindowHandle] and
 /// [raw_window_handle::HasRawDisplayHandle] for [web_sys::HtmlCanvasElement].
 struct WgpuCanvas {
@@ -127,6 +1136,31 @@ struct WgpuCanvas {
     window_handle: raw_window_handle::WebWindowHandle,
 }
 
+impl WgpuCanvas {
+    /// Binds the given canvas to a unique raw window handle.
+    ///
+    /// Arguments:
+    ///
+    /// * `canvas`: The canvas that will be rendered to.
+    fn from_canvas(canvas: &web_sys::HtmlCanvasElement) -> Result<Self, JsError> {
+        let id = NEXT_RAW_HANDLE_ID.fetch_add(1, Ordering::Relaxed);
+
+        // The numeric id in the `raw_window_handle::web::WebHandle` has to be set
+        // inside the `data-raw-handle` attribute of the canvas.
+        canvas
+            .set_attribute("data-raw-handle", &id.to_string())
+            .map_err(|_| JsError::new("Failed to assign numeric raw-window-handle id to canvas."))?;
+
+        let mut window_handle = raw_window_handle::WebWindowHandle::empty();
+        window_handle.id = id;
+
+        Ok(Self {
+            display_handle: raw_window_handle::WebDisplayHandle::empty(),
+            window_handle,
+        })
+    }
+}
+
 unsafe impl raw_window_handle::HasRawWindowHandle for WgpuCanvas {
     fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
         raw_window_handle::RawWindowHandle::Web(self.window_handle)
@@ -138,3 +1172,28 @@ unsafe impl raw_window_handle::HasRawDisplayHandle for WgpuCanvas {
         raw_window_handle::RawDisplayHandle::Web(self.display_handle)
     }
 }
+
+/// Looks up the canvas with the given id in the dom document.
+///
+/// Arguments:
+///
+/// * `canvas_id`: The id of the canvas in the dom document.
+fn find_canvas(canvas_id: &str) -> Result<web_sys::HtmlCanvasElement, JsError> {
+    // Get the browser window.
+    let window = web_sys::window().ok_or_else(|| JsError::new("Can not get browser window."))?;
+
+    // Get the dom document.
+    let document = window
+        .document()
+        .ok_or_else(|| JsError::new("Can not get html document."))?;
+
+    // Get the canvas with the given id.
+    let canvas = document
+        .get_element_by_id(canvas_id)
+        .ok_or_else(|| JsError::new("The given canvas id was not found in the document."))?;
+
+    // Canvas need to be of type HtmlCanvasElement.
+    canvas
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| JsError::new("Failed to convert element to canvas."))
+}
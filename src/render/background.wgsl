@@ -0,0 +1,31 @@
+// Vertex shader
+
+struct Background {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> background: Background;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) height: f32,
+};
+
+// Draws a single triangle that covers the whole viewport.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 1.0, 1.0);
+    out.height = uv.y;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return mix(background.bottom, background.top, in.height);
+}
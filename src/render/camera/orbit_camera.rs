@@ -160,13 +160,12 @@ impl OrbitCamera {
         self.set_yaw(self.yaw + delta);
     }
 
-    /// Updates the camera after changing `distance`, `pitch` or `yaw`.
+    /// Updates the camera after changing `distance`, `pitch`, `yaw` or `target`.
     fn update(&mut self) {
-        self.eye = Vector3::from_vec3(calculate_cartesian_eye_position(
-            self.pitch,
-            self.yaw,
-            self.distance,
-        ));
+        self.eye = Vector3::from_vec3(
+            self.target.to_vec3()
+                + calculate_cartesian_eye_position(self.pitch, self.yaw, self.distance),
+        );
     }
 }
 
@@ -0,0 +1,217 @@
+use crate::data::volume::Volume;
+
+/// The time between two frames in seconds if the file does not specify a repetition time.
+pub const DEFAULT_FRAME_INTERVAL: f32 = 1.0;
+
+/// Plays the frames of a time series one after another.
+///
+/// The player does not measure time itself, the caller passes the elapsed time to
+/// [CinePlayer::advance] so that it works with any clock, e.g. `std::time::Instant` natively
+/// or `requestAnimationFrame` timestamps on the web.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CinePlayer {
+    /// The number of frames that are played.
+    frame_count: usize,
+
+    /// The index of the displayed frame.
+    frame: usize,
+
+    /// The time between two frames in seconds.
+    pub interval: f32,
+
+    /// Whether the frames are played.
+    playing: bool,
+
+    /// The time since the displayed frame was reached in seconds.
+    elapsed: f32,
+}
+
+impl CinePlayer {
+    /// Creates a paused player that displays the first frame.
+    ///
+    /// Arguments:
+    ///
+    /// * `frame_count`: The number of frames of the time series.
+    /// * `interval`: The time between two frames in seconds.
+    pub fn new(frame_count: usize, interval: f32) -> Self {
+        Self {
+            frame_count: frame_count.max(1),
+            frame: 0,
+            interval,
+            playing: false,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Creates a paused player for a volume that plays its frames at the repetition time.
+    ///
+    /// Volumes without a repetition time are played at [DEFAULT_FRAME_INTERVAL].
+    ///
+    /// Arguments:
+    ///
+    /// * `volume`: The time series to play.
+    pub fn for_volume(volume: &Volume) -> Self {
+        let interval = volume
+            .header
+            .repetition_time()
+            .unwrap_or(DEFAULT_FRAME_INTERVAL);
+        Self {
+            frame: volume.frame(),
+            ..Self::new(volume.frame_count, interval)
+        }
+    }
+
+    /// Returns the number of frames that are played.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Returns the index of the displayed frame.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Displays a frame without changing whether the frames are played.
+    ///
+    /// Arguments:
+    ///
+    /// * `frame`: The index of the frame, which is clamped to the frame count.
+    pub fn set_frame(&mut self, frame: usize) {
+        self.frame = frame.min(self.frame_count - 1);
+        self.elapsed = 0.0;
+    }
+
+    /// Moves a number of frames forward or backward and wraps around at the ends.
+    ///
+    /// Arguments:
+    ///
+    /// * `delta`: The number of frames to move, negative values move backward.
+    pub fn step(&mut self, delta: isize) {
+        let count = self.frame_count as isize;
+        self.set_frame((self.frame as isize + delta).rem_euclid(count) as usize);
+    }
+
+    /// Returns whether the frames are played.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Starts playing the frames from the displayed one.
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    /// Stops playing the frames and keeps the displayed one.
+    pub fn pause(&mut self) {
+        self.playing = false;
+        self.elapsed = 0.0;
+    }
+
+    /// Starts playing the frames if they are paused and pauses them otherwise.
+    pub fn toggle(&mut self) {
+        if self.playing {
+            self.pause();
+        } else {
+            self.play();
+        }
+    }
+
+    /// Advances the playback by the time that passed since the last call.
+    ///
+    /// Returns the new frame if it changed, the playback loops after the last frame.
+    ///
+    /// Arguments:
+    ///
+    /// * `seconds`: The time that passed since the last call in seconds.
+    pub fn advance(&mut self, seconds: f32) -> Option<usize> {
+        if !self.playing || self.frame_count < 2 {
+            return None;
+        }
+        let interval = self.interval.max(f32::EPSILON);
+        self.elapsed += seconds.max(0.0);
+        let frames = (self.elapsed / interval).floor();
+        if frames < 1.0 {
+            return None;
+        }
+        self.elapsed -= frames * interval;
+        let count = self.frame_count;
+        self.frame = (self.frame + frames as usize % count) % count;
+        Some(self.frame)
+    }
+
+    /// Returns the time until the next frame is due in seconds, or [None] if the player is
+    /// paused.
+    ///
+    /// This can be used to schedule the next redraw.
+    pub fn time_to_next_frame(&self) -> Option<f32> {
+        (self.playing && self.frame_count > 1).then(|| (self.interval - self.elapsed).max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Mat4;
+
+    use super::{CinePlayer, DEFAULT_FRAME_INTERVAL};
+    use crate::data::volume::tests::test_volume;
+
+    #[test]
+    fn advance_wraps_after_the_last_frame() {
+        let mut player = CinePlayer::new(3, 0.5);
+        assert_eq!(player.advance(1.0), None);
+
+        player.play();
+        assert_eq!(player.advance(0.25), None);
+        assert_eq!(player.time_to_next_frame(), Some(0.25));
+        assert_eq!(player.advance(0.25), Some(1));
+        assert_eq!(player.advance(1.0), Some(0));
+        // Several periods at once keep the remainder of the elapsed time.
+        assert_eq!(player.advance(3.75), Some(1));
+        assert_eq!(player.time_to_next_frame(), Some(0.25));
+
+        player.pause();
+        assert_eq!(player.advance(1.0), None);
+        assert_eq!(player.time_to_next_frame(), None);
+    }
+
+    #[test]
+    fn step_wraps_in_both_directions() {
+        let mut player = CinePlayer::new(4, 1.0);
+        player.step(-1);
+        assert_eq!(player.frame(), 3);
+        player.step(2);
+        assert_eq!(player.frame(), 1);
+        player.step(-9);
+        assert_eq!(player.frame(), 0);
+        player.set_frame(10);
+        assert_eq!(player.frame(), 3);
+    }
+
+    #[test]
+    fn interval_is_the_repetition_time_in_seconds() {
+        let mut volume = test_volume([1, 1, 1], Mat4::IDENTITY, &[0.0, 1.0, 2.0]);
+        assert_eq!(CinePlayer::for_volume(&volume).interval, 2.0);
+
+        // NIFTI_UNITS_MSEC
+        volume.header.xyzt_units = 2 | 16;
+        volume.header.pixdim[4] = 2500.0;
+        assert_eq!(CinePlayer::for_volume(&volume).interval, 2.5);
+
+        // NIFTI_UNITS_USEC
+        volume.header.xyzt_units = 2 | 24;
+        volume.header.pixdim[4] = 800_000.0;
+        assert!((CinePlayer::for_volume(&volume).interval - 0.8).abs() < 1e-6);
+
+        // NIFTI_UNITS_HZ is not a time.
+        volume.header.xyzt_units = 2 | 32;
+        assert_eq!(
+            CinePlayer::for_volume(&volume).interval,
+            DEFAULT_FRAME_INTERVAL
+        );
+
+        let volume = test_volume([1, 1, 1], Mat4::IDENTITY, &[0.0]);
+        let player = CinePlayer::for_volume(&volume);
+        assert_eq!(player.frame_count(), 1);
+        assert_eq!(player.interval, DEFAULT_FRAME_INTERVAL);
+    }
+}
@@ -0,0 +1,279 @@
+use glam::{Vec3, Vec4Swizzles};
+
+use crate::{
+    data::volume::Volume,
+    render::camera::{Camera, OrbitCamera},
+};
+
+/// How the samples along a ray are combined into a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compositing {
+    /// Maximum intensity projection.
+    Mip,
+
+    /// Front-to-back alpha compositing with a linear grayscale transfer function.
+    Alpha,
+}
+
+/// A pure CPU implementation of the volume renderer.
+///
+/// It uses the same camera and box proxy as [crate::render::state::State]: the volume fills a
+/// box centered at the origin whose size is [Volume::extent]. Since it needs no adapter it can
+/// be used for golden-image tests and for rendering thumbnails without a GPU.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuRenderer {
+    /// How the samples along a ray are combined.
+    pub compositing: Compositing,
+
+    /// The intensities mapped to black and white. If [None] the range of the volume is used.
+    pub window: Option<(f32, f32)>,
+
+    /// The number of samples taken per voxel along each ray.
+    pub samples_per_voxel: f32,
+
+    /// The opacity of a fully white sample per voxel when using [Compositing::Alpha].
+    pub opacity: f32,
+
+    /// The linear RGBA color behind the volume.
+    pub background: [f32; 4],
+}
+
+impl Default for CpuRenderer {
+    fn default() -> Self {
+        Self {
+            compositing: Compositing::Mip,
+            window: None,
+            samples_per_voxel: 2.0,
+            opacity: 0.05,
+            background: [0.1, 0.2, 0.3, 1.0],
+        }
+    }
+}
+
+impl CpuRenderer {
+    /// Renders a volume as seen by a camera.
+    ///
+    /// Arguments:
+    ///
+    /// * `volume`: The volume that is rendered.
+    /// * `camera`: The camera, its aspect ratio is replaced by the one of the image.
+    /// * `width`: The width of the image in pixels.
+    /// * `height`: The height of the image in pixels.
+    pub fn render(
+        &self,
+        volume: &Volume,
+        camera: &OrbitCamera,
+        width: u32,
+        height: u32,
+    ) -> image::RgbaImage {
+        let mut camera = *camera;
+        camera.aspect = width as f32 / height as f32;
+        let inverse_view_proj = camera.build_view_projection_matrix().inverse();
+
+        let size = volume.extent();
+        let half_size = size * 0.5;
+        let voxel_size = volume.header.voxel_size();
+        let step = voxel_size.min_element() / self.samples_per_voxel.max(f32::EPSILON);
+        let (low, high) = self.window.unwrap_or_else(|| volume.min_max());
+        let range = (high - low).max(f32::EPSILON);
+        let dims = Vec3::new(
+            volume.dims[0] as f32,
+            volume.dims[1] as f32,
+            volume.dims[2] as f32,
+        );
+
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            // The ray through the pixel center from the near to the far plane. The depth range
+            // of wgpu is `[0, 1]`.
+            let ndc_x = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let ndc_y = 1.0 - (y as f32 + 0.5) / height as f32 * 2.0;
+            let near = inverse_view_proj * glam::Vec4::new(ndc_x, ndc_y, 0.0, 1.0);
+            let far = inverse_view_proj * glam::Vec4::new(ndc_x, ndc_y, 1.0, 1.0);
+            let origin = near.xyz() / near.w;
+            let direction = (far.xyz() / far.w - origin).normalize();
+
+            let color = match intersect_box(origin, direction, half_size) {
+                Some((t_enter, t_exit)) => {
+                    let mut samples = (0..)
+                        .map(|i| t_enter + (i as f32 + 0.5) * step)
+                        .take_while(|&t| t <= t_exit)
+                        .map(|t| {
+                            // Map the box to voxel coordinates, voxel centers are at `i + 0.5`.
+                            let texture_coords = (origin + direction * t + half_size) / size;
+                            let value = volume.sample_trilinear(texture_coords * dims - 0.5);
+                            ((value - low) / range).clamp(0.0, 1.0)
+                        });
+                    self.composite(&mut samples, step / voxel_size.min_element())
+                }
+                None => [0.0; 4],
+            };
+
+            // Blend over the background and convert from linear to sRGB like an sRGB surface.
+            let alpha = color[3];
+            let mut pixel = [0u8; 4];
+            for i in 0..3 {
+                let linear = color[i] + (1.0 - alpha) * self.background[i];
+                pixel[i] = (linear_to_srgb(linear) * 255.0).round() as u8;
+            }
+            pixel[3] = ((alpha + (1.0 - alpha) * self.background[3]).clamp(0.0, 1.0) * 255.0)
+                .round() as u8;
+            image::Rgba(pixel)
+        })
+    }
+
+    /// Combines normalized samples into a premultiplied linear RGBA color.
+    ///
+    /// Arguments:
+    ///
+    /// * `samples`: The normalized intensities along the ray, front to back.
+    /// * `step_in_voxels`: The distance between two samples in voxels.
+    fn composite(&self, samples: &mut dyn Iterator<Item = f32>, step_in_voxels: f32) -> [f32; 4] {
+        match self.compositing {
+            Compositing::Mip => {
+                let max = samples.fold(0.0f32, f32::max);
+                [max, max, max, 1.0]
+            }
+            Compositing::Alpha => {
+                let mut color = 0.0;
+                let mut alpha = 0.0;
+                for value in samples {
+                    // Correct the opacity for the distance between samples.
+                    let sample_alpha = 1.0 - (1.0 - value * self.opacity).powf(step_in_voxels);
+                    color += (1.0 - alpha) * sample_alpha * value;
+                    alpha += (1.0 - alpha) * sample_alpha;
+                    if alpha > 0.99 {
+                        break;
+                    }
+                }
+                [color, color, color, alpha]
+            }
+        }
+    }
+}
+
+/// Intersects a ray with an axis aligned box centered at the origin.
+///
+/// Returns the distances along the ray at which it enters and exits the box.
+///
+/// Arguments:
+///
+/// * `origin`: The start of the ray.
+/// * `direction`: The normalized direction of the ray.
+/// * `half_size`: Half of the size of the box.
+fn intersect_box(origin: Vec3, direction: Vec3, half_size: Vec3) -> Option<(f32, f32)> {
+    let inverse = direction.recip();
+    let t0 = (-half_size - origin) * inverse;
+    let t1 = (half_size - origin) * inverse;
+    let t_enter = t0.min(t1).max_element().max(0.0);
+    let t_exit = t0.max(t1).min_element();
+    (t_enter <= t_exit).then_some((t_enter, t_exit))
+}
+
+/// Converts a linear color component to sRGB.
+fn linear_to_srgb(linear: f32) -> f32 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{Compositing, CpuRenderer};
+    use crate::{
+        data::volume::{tests::test_volume, Volume},
+        render::camera::OrbitCamera,
+    };
+
+    /// Returns a volume with anisotropic voxels holding a bright ellipsoid whose intensity
+    /// increases along x, next to a dim block.
+    fn phantom() -> Volume {
+        let dims = [24, 16, 12];
+        let mut data = Vec::new();
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for x in 0..dims[0] {
+                    let p = Vec3::new(x as f32 - 9.0, y as f32 - 8.0, z as f32 - 6.0)
+                        / Vec3::new(7.0, 6.0, 4.0);
+                    let value = if p.length() < 1.0 {
+                        100.0 + 5.0 * x as f32
+                    } else if x >= 18 && (4..12).contains(&y) && (3..9).contains(&z) {
+                        60.0
+                    } else {
+                        0.0
+                    };
+                    data.push(value);
+                }
+            }
+        }
+        test_volume(dims, Mat4::from_scale(Vec3::new(1.0, 1.5, 2.0)), &data)
+    }
+
+    /// Renders the phantom at an oblique angle.
+    fn render(compositing: Compositing) -> image::RgbaImage {
+        let camera = OrbitCamera::new(40.0, 0.4, 0.7, Vec3::ZERO, 1.0);
+        let renderer = CpuRenderer {
+            compositing,
+            opacity: 0.2,
+            ..Default::default()
+        };
+        renderer.render(&phantom(), &camera, 64, 48)
+    }
+
+    /// Compares an image with a golden image in `src/render/testdata`, allowing a difference
+    /// of one per channel for rounding. Set `UPDATE_GOLDEN=1` to write the image instead.
+    fn assert_golden(image: &image::RgbaImage, name: &str) {
+        let path = format!(
+            "{}/src/render/testdata/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            image.save(&path).unwrap();
+            return;
+        }
+        let golden = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), golden.dimensions());
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let expected = golden.get_pixel(x, y);
+            let matches = (0..4).all(|i| pixel[i].abs_diff(expected[i]) <= 1);
+            assert!(
+                matches,
+                "{} differs at ({}, {}): {:?} instead of {:?}",
+                name, x, y, pixel, expected
+            );
+        }
+    }
+
+    #[test]
+    fn mip_matches_golden_image() {
+        assert_golden(&render(Compositing::Mip), "cpu_mip.png");
+    }
+
+    #[test]
+    fn alpha_compositing_matches_golden_image() {
+        assert_golden(&render(Compositing::Alpha), "cpu_alpha.png");
+    }
+
+    #[test]
+    fn mip_of_single_voxel() {
+        let mut data = vec![0.0; 27];
+        data[13] = 10.0;
+        let volume = test_volume([3, 3, 3], Mat4::IDENTITY, &data);
+        let camera = OrbitCamera::new(6.0, 0.0, 0.0, Vec3::ZERO, 1.0);
+        let renderer = CpuRenderer {
+            samples_per_voxel: 1.0,
+            ..Default::default()
+        };
+        let image = renderer.render(&volume, &camera, 9, 9);
+
+        // The ray through the center pixel samples the center of the bright voxel.
+        assert_eq!(image.get_pixel(4, 4).0, [255, 255, 255, 255]);
+        // The corners miss the volume and show the background, converted to sRGB.
+        assert_eq!(image.get_pixel(0, 0).0, [89, 124, 149, 255]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+
+/// The errors that can occur while setting up the renderer.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The surface for the window or canvas could not be created.
+    CreateSurface(wgpu::CreateSurfaceError),
+
+    /// Neither a hardware nor a software fallback adapter is available.
+    NoAdapter,
+
+    /// The adapter refused to provide a device with the requested limits.
+    RequestDevice(wgpu::RequestDeviceError),
+
+    /// The surface does not support any texture format on the chosen adapter.
+    NoSurfaceFormat,
+
+    /// An embedded texture could not be decoded.
+    Texture(image::ImageError),
+
+    /// A captured image was requested with a size the device can not render.
+    CaptureSize { width: u32, height: u32, max: u32 },
+
+    /// A captured image was requested from a surface whose format is not 8 bit RGBA or BGRA.
+    CaptureFormat(wgpu::TextureFormat),
+
+    /// A buffer could not be mapped to read back rendered pixels.
+    BufferMap(wgpu::BufferAsyncError),
+
+    /// The device was lost before the rendered pixels were read back.
+    DeviceLost,
+
+    /// A captured image could not be encoded.
+    EncodeImage(image::ImageError),
+
+    /// The number of overlay scalars does not match the number of vertices of the mesh.
+    ScalarCount { expected: usize, actual: usize },
+
+    /// A volume layer was added while the maximum number of layers is shown.
+    LayerCount { max: usize },
+
+    /// A volume layer has more voxels along an axis than a 3D texture can hold.
+    VolumeSize { dims: [usize; 3], max: usize },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateSurface(e) => write!(f, "Failed to create surface: {}", e),
+            Self::NoAdapter => write!(f, "No compatible graphics adapter was found."),
+            Self::RequestDevice(e) => write!(f, "Failed to request device: {}", e),
+            Self::NoSurfaceFormat => write!(f, "The surface does not support any texture format."),
+            Self::Texture(e) => write!(f, "Failed to decode texture: {}", e),
+            Self::CaptureSize { width, height, max } => write!(
+                f,
+                "Can not capture an image of {} x {} pixels, the maximum size is {} pixels.",
+                width, height, max
+            ),
+            Self::CaptureFormat(format) => {
+                write!(f, "Can not capture images of the texture format {:?}.", format)
+            }
+            Self::BufferMap(e) => write!(f, "Failed to read back the rendered image: {}", e),
+            Self::DeviceLost => write!(f, "The device was lost while reading back the image."),
+            Self::EncodeImage(e) => write!(f, "Failed to encode image: {}", e),
+            Self::ScalarCount { expected, actual } => write!(
+                f,
+                "The overlay has {} scalars, but the mesh has {} vertices.",
+                actual, expected
+            ),
+            Self::LayerCount { max } => {
+                write!(f, "Can not show more than {} volume layers.", max)
+            }
+            Self::VolumeSize { dims, max } => write!(
+                f,
+                "Can not show a volume of {} x {} x {} voxels as layer, the maximum size is {} voxels.",
+                dims[0], dims[1], dims[2], max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CreateSurface(e) => Some(e),
+            Self::RequestDevice(e) => Some(e),
+            Self::Texture(e) => Some(e),
+            Self::BufferMap(e) => Some(e),
+            Self::EncodeImage(e) => Some(e),
+            Self::NoAdapter
+            | Self::NoSurfaceFormat
+            | Self::CaptureSize { .. }
+            | Self::CaptureFormat(_)
+            | Self::DeviceLost
+            | Self::ScalarCount { .. }
+            | Self::LayerCount { .. }
+            | Self::VolumeSize { .. } => None,
+        }
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for RenderError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        Self::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for RenderError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(e)
+    }
+}
+
+impl From<image::ImageError> for RenderError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Texture(e)
+    }
+}
@@ -0,0 +1,29 @@
+use glam::Vec3;
+
+use crate::render::{geometry::r#box::get_box_vertecies, vertex::Vertex};
+
+/// The width of the lines relative to the largest extent of the crosshair.
+const LINE_WIDTH: f32 = 0.003;
+
+/// Calculates the vertecies of a 3D crosshair, three thin bars along the axes which cross at
+/// the origin.
+///
+/// The crosshair is moved to a position with the transform of its scene object, e.g.
+/// `Mat4::from_translation(peak)` to mark the peak of a cluster.
+///
+/// Arguments:
+///
+/// * `extent`: The length of the bars along the x, y and z axes, e.g. the extent of a volume.
+pub fn get_crosshair_vertecies(extent: Vec3) -> (Vec<Vertex>, Vec<u32>) {
+    let width = LINE_WIDTH * extent.max_element();
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    for axis in 0..3 {
+        let mut size = Vec3::splat(width);
+        size[axis] = extent[axis];
+        let (bar_vertices, bar_indices) =
+            get_box_vertecies(vertices.len() as u32, Vec3::ZERO, size, Vec3::ZERO);
+        vertices.extend(bar_vertices);
+        indices.extend(bar_indices);
+    }
+    (vertices, indices)
+}
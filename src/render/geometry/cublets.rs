@@ -0,0 +1,135 @@
+use glam::Vec3;
+
+use crate::{data::volume::Volume, render::vertex::Instance};
+
+/// The fraction of a block that its cube fills, which leaves a gap between neighbouring cubes.
+const CUBLET_FILL: f32 = 0.9;
+
+/// Calculates one instance of a unit box per block of voxels whose value reaches a threshold.
+///
+/// The volume is downsampled into blocks of `block_size`³ voxels and each block takes the
+/// largest value of its voxels. The instances are placed in world space with the affine of the
+/// volume, so they are drawn with the mesh returned by
+/// [crate::render::geometry::r#box::get_box_vertecies] for a box of size one at the origin.
+/// Label maps should use a block size of one so that labels are not mixed.
+///
+/// Arguments:
+///
+/// * `volume`: The volume from which the cublets are created.
+/// * `threshold`: The smallest value of a block that is shown.
+/// * `block_size`: The number of voxels along each axis that are combined into one cube.
+/// * `color`: Returns the RGBA color of a cube for the value of its block.
+pub fn get_cublet_instances<F>(
+    volume: &Volume,
+    threshold: f32,
+    block_size: usize,
+    color: F,
+) -> Vec<Instance>
+where
+    F: Fn(f32) -> [f32; 4],
+{
+    let block_size = block_size.max(1);
+    let blocks = volume.dims.map(|d| d.div_ceil(block_size));
+
+    // A mirroring affine is moved into the scale by glam, which is dropped so that the
+    // winding of the box stays intact.
+    let (voxel_size, rotation, _) = volume.affine.to_scale_rotation_translation();
+    let rotation = rotation.normalize();
+    let scale = voxel_size.abs() * block_size as f32 * CUBLET_FILL;
+
+    let mut instances = Vec::new();
+    for bz in 0..blocks[2] {
+        for by in 0..blocks[1] {
+            for bx in 0..blocks[0] {
+                let start = [bx * block_size, by * block_size, bz * block_size];
+                let end = [0, 1, 2].map(|k| (start[k] + block_size).min(volume.dims[k]));
+                let mut value = f32::NEG_INFINITY;
+                for z in start[2]..end[2] {
+                    for y in start[1]..end[1] {
+                        for x in start[0]..end[0] {
+                            value = value.max(volume.get(x, y, z));
+                        }
+                    }
+                }
+                if value < threshold {
+                    continue;
+                }
+
+                let center = Vec3::new(
+                    (start[0] + end[0]) as f32 - 1.0,
+                    (start[1] + end[1]) as f32 - 1.0,
+                    (start[2] + end[2]) as f32 - 1.0,
+                ) * 0.5;
+                instances.push(Instance {
+                    position: volume.affine.transform_point3(center).into(),
+                    scale: scale.into(),
+                    rotation: rotation.to_array(),
+                    color: color(value),
+                });
+            }
+        }
+    }
+    instances
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Quat, Vec3};
+
+    use super::get_cublet_instances;
+    use crate::data::volume::tests::test_volume;
+
+    /// Returns the red channel of the color for a value.
+    fn red(value: f32) -> [f32; 4] {
+        [value, 0.0, 0.0, 1.0]
+    }
+
+    #[test]
+    fn one_instance_per_block_above_the_threshold() {
+        // 2 mm voxels shifted by 10 mm along x.
+        let affine =
+            Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0)) * Mat4::from_scale(Vec3::splat(2.0));
+        let mut data = vec![0.0; 4 * 4 * 2];
+        data[0] = 5.0; // (0, 0, 0)
+        data[2] = 0.5; // (2, 0, 0), below the threshold
+        data[31] = 1.0; // (3, 3, 1)
+        let volume = test_volume([4, 4, 2], affine, &data);
+
+        let instances = get_cublet_instances(&volume, 1.0, 2, red);
+        let found: Vec<([f32; 3], f32)> = instances
+            .iter()
+            .map(|instance| (instance.position, instance.color[0]))
+            .collect();
+        assert_eq!(found, [([11.0, 1.0, 1.0], 5.0), ([15.0, 5.0, 1.0], 1.0)]);
+        for instance in &instances {
+            assert_eq!(instance.scale, [3.6; 3]);
+            assert_eq!(instance.rotation, Quat::IDENTITY.to_array());
+        }
+
+        // Single voxel blocks are centered on their voxels.
+        let instances = get_cublet_instances(&volume, 0.5, 1, red);
+        let positions: Vec<[f32; 3]> = instances.iter().map(|instance| instance.position).collect();
+        assert_eq!(
+            positions,
+            [[10.0, 0.0, 0.0], [14.0, 0.0, 0.0], [16.0, 6.0, 2.0]]
+        );
+    }
+
+    #[test]
+    fn partial_blocks_and_mirrored_affines() {
+        // The x axis is mirrored, so voxel x lies at -x millimetres.
+        let affine = Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+        let volume = test_volume([3, 1, 1], affine, &[0.0, 0.0, 1.0]);
+
+        let instances = get_cublet_instances(&volume, 1.0, 2, red);
+        assert_eq!(instances.len(), 1);
+        // The last block only covers the voxel x = 2.
+        assert_eq!(instances[0].position, [-2.0, 0.0, 0.0]);
+        // The mirroring does not turn the cube inside out.
+        assert!(instances[0].scale.iter().all(|&s| s > 0.0));
+
+        // A block size of zero is treated as one.
+        assert_eq!(get_cublet_instances(&volume, 1.0, 0, red).len(), 1);
+        assert!(get_cublet_instances(&volume, 2.0, 1, red).is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use glam::{Mat4, Quat, Vec2, Vec3};
+
+use crate::render::{geometry::r#box::get_box_vertecies, vertex::Vertex};
+
+/// The width of the strokes relative to the height of a letter.
+const STROKE_WIDTH: f32 = 0.12;
+
+/// Returns the strokes of a letter as line segments within a cell that is `0.6` wide and `1.0`
+/// high, or [None] if the letter has no strokes.
+///
+/// Arguments:
+///
+/// * `letter`: One of the letters of the anatomical directions `R`, `L`, `A`, `P`, `S` and `I`.
+fn strokes(letter: char) -> Option<&'static [[f32; 4]]> {
+    let strokes: &[[f32; 4]] = match letter.to_ascii_uppercase() {
+        'R' => &[
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.45, 1.0],
+            [0.45, 1.0, 0.6, 0.85],
+            [0.6, 0.85, 0.6, 0.65],
+            [0.6, 0.65, 0.45, 0.5],
+            [0.45, 0.5, 0.0, 0.5],
+            [0.3, 0.5, 0.6, 0.0],
+        ],
+        'L' => &[[0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 0.6, 0.0]],
+        'A' => &[
+            [0.0, 0.0, 0.3, 1.0],
+            [0.3, 1.0, 0.6, 0.0],
+            [0.12, 0.4, 0.48, 0.4],
+        ],
+        'P' => &[
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.45, 1.0],
+            [0.45, 1.0, 0.6, 0.85],
+            [0.6, 0.85, 0.6, 0.65],
+            [0.6, 0.65, 0.45, 0.5],
+            [0.45, 0.5, 0.0, 0.5],
+        ],
+        'S' => &[
+            [0.6, 0.85, 0.45, 1.0],
+            [0.45, 1.0, 0.15, 1.0],
+            [0.15, 1.0, 0.0, 0.85],
+            [0.0, 0.85, 0.0, 0.65],
+            [0.0, 0.65, 0.15, 0.5],
+            [0.15, 0.5, 0.45, 0.5],
+            [0.45, 0.5, 0.6, 0.35],
+            [0.6, 0.35, 0.6, 0.15],
+            [0.6, 0.15, 0.45, 0.0],
+            [0.45, 0.0, 0.15, 0.0],
+            [0.15, 0.0, 0.0, 0.15],
+        ],
+        'I' => &[
+            [0.3, 0.0, 0.3, 1.0],
+            [0.1, 1.0, 0.5, 1.0],
+            [0.1, 0.0, 0.5, 0.0],
+        ],
+        _ => return None,
+    };
+    Some(strokes)
+}
+
+/// Calculates the vertecies of a letter made of flat strokes.
+///
+/// The letter lies in the xy plane and is read from the z axis. Only the letters of the
+/// anatomical directions `R`, `L`, `A`, `P`, `S` and `I` are available, other letters have no
+/// vertices.
+///
+/// Arguments:
+///
+/// * `position`: The position of the center of the letter.
+/// * `size`: The height of the letter.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   letter around its center.
+/// * `letter`: The letter.
+pub fn get_letter_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: f32,
+    rotation: Vec3,
+    letter: char,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    let Some(strokes) = strokes(letter) else {
+        return (vertices, indices);
+    };
+
+    let transform = Mat4::from_scale_rotation_translation(
+        Vec3::splat(size),
+        Quat::from_euler(glam::EulerRot::XYZ, rotation.x, rotation.y, rotation.z),
+        position,
+    );
+    // The strokes are centered on the middle of the cell.
+    let center = Vec2::new(0.3, 0.5);
+    for [x0, y0, x1, y1] in strokes {
+        let (start, end) = (Vec2::new(*x0, *y0) - center, Vec2::new(*x1, *y1) - center);
+        let direction = end - start;
+        let (stroke_vertices, stroke_indices) = get_box_vertecies(
+            index_offset + vertices.len() as u32,
+            ((start + end) / 2.0).extend(0.0),
+            // The strokes overlap at the joints.
+            Vec3::new(
+                direction.length() + STROKE_WIDTH,
+                STROKE_WIDTH,
+                STROKE_WIDTH,
+            ),
+            Vec3::new(0.0, 0.0, direction.y.atan2(direction.x)),
+        );
+        vertices.extend(stroke_vertices.into_iter().map(|vertex| {
+            Vertex {
+                position: transform
+                    .transform_point3(Vec3::from(vertex.position))
+                    .to_array(),
+                normal: transform
+                    .transform_vector3(Vec3::from(vertex.normal))
+                    .normalize()
+                    .to_array(),
+                ..vertex
+            }
+        }));
+        indices.extend(stroke_indices);
+    }
+
+    (vertices, indices)
+}
@@ -0,0 +1,142 @@
+use glam::Vec3;
+
+use crate::{
+    data::volume::Volume,
+    render::{geometry::r#box::get_box_vertecies, vertex::Vertex},
+};
+
+/// The width of the edges relative to the largest extent of the volume.
+const EDGE_WIDTH: f32 = 0.004;
+
+/// The length of the minor and major tick marks relative to the largest extent of the volume.
+const TICK_LENGTHS: [f32; 2] = [0.015, 0.03];
+
+/// The number of minor tick intervals between two major tick marks.
+const MAJOR_TICK_INTERVAL: usize = 5;
+
+/// Returns the distance between minor tick marks in millimetres.
+///
+/// The distance is a power of ten so that there are at least ten intervals along the largest
+/// extent, every fifth tick mark is a major one.
+///
+/// Arguments:
+///
+/// * `volume`: The volume whose outline is drawn.
+pub fn tick_spacing(volume: &Volume) -> f32 {
+    let extent = volume.extent().max_element().max(f32::EPSILON);
+    10.0f32.powf((extent / 10.0).log10().floor())
+}
+
+/// Calculates the vertecies of a wireframe outline of a volume with tick marks in millimetres.
+///
+/// The outline is placed like the box passed to [crate::render::state::State::update_box_size],
+/// centered on the origin with the extent of the volume taken from `pixdim` and `xyzt_units`.
+/// [Volume::box_transform] places it in world space instead. The tick marks start at the
+/// corners of the box and point away from it, their distance is [tick_spacing].
+///
+/// Arguments:
+///
+/// * `volume`: The volume whose outline is drawn.
+pub fn get_outline_vertecies(volume: &Volume) -> (Vec<Vertex>, Vec<u32>) {
+    let extent = volume.extent();
+    let half = extent / 2.0;
+    let scale = extent.max_element();
+    let width = EDGE_WIDTH * scale;
+    let spacing = tick_spacing(volume);
+
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    let mut push_bar = |center: Vec3, size: Vec3| {
+        let (bar_vertices, bar_indices) =
+            get_box_vertecies(vertices.len() as u32, center, size, Vec3::ZERO);
+        vertices.extend(bar_vertices);
+        indices.extend(bar_indices);
+    };
+
+    for axis in 0..3 {
+        // The two other axes pick one of the four parallel edges.
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            let mut center = Vec3::ZERO;
+            center[u] = su * half[u];
+            center[v] = sv * half[v];
+            let mut size = Vec3::splat(width);
+            size[axis] = extent[axis] + width;
+            push_bar(center, size);
+
+            // The tick marks point away from the box along the first of the other axes.
+            let count = (extent[axis] / spacing).floor() as usize;
+            for k in 0..=count {
+                let length = TICK_LENGTHS[usize::from(k % MAJOR_TICK_INTERVAL == 0)] * scale;
+                let mut tick = center;
+                tick[axis] = -half[axis] + k as f32 * spacing;
+                tick[u] += su * length / 2.0;
+                let mut size = Vec3::splat(width);
+                size[u] = length;
+                push_bar(tick, size);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{get_outline_vertecies, tick_spacing};
+    use crate::{data::volume::tests::test_volume, render::geometry::r#box::get_box_vertecies};
+
+    #[test]
+    fn ticks_are_millimetres_in_the_unit_of_the_file() {
+        // 10 mm ticks from 10 cm on, 1 mm ticks below.
+        let volume = test_volume([100, 1, 1], Mat4::IDENTITY, &[0.0; 100]);
+        assert_eq!(tick_spacing(&volume), 10.0);
+        let volume = test_volume([50, 1, 1], Mat4::IDENTITY, &[0.0; 50]);
+        assert_eq!(tick_spacing(&volume), 1.0);
+
+        // A `pixdim` of 2000 spans 10 m in millimetres but 10 cm in microns.
+        let mut volume = test_volume(
+            [50, 1, 1],
+            Mat4::from_scale(Vec3::splat(2000.0)),
+            &[0.0; 50],
+        );
+        assert_eq!(tick_spacing(&volume), 10000.0);
+        volume.header.xyzt_units = 3 | 8; // NIFTI_UNITS_MICRON | NIFTI_UNITS_SEC
+        assert_eq!(volume.extent().x, 100.0);
+        assert_eq!(tick_spacing(&volume), 10.0);
+    }
+
+    #[test]
+    fn every_fifth_tick_is_longer() {
+        let volume = test_volume([100, 50, 20], Mat4::IDENTITY, &vec![0.0; 100 * 50 * 20]);
+        let (vertices, indices) = get_outline_vertecies(&volume);
+
+        let (box_vertices, box_indices) = get_box_vertecies(0, Vec3::ZERO, Vec3::ONE, Vec3::ZERO);
+        // 12 edges and 4 edges with 11, 6 and 3 tick marks along x, y and z.
+        let bars = 12 + 4 * (11 + 6 + 3);
+        assert_eq!(vertices.len(), bars * box_vertices.len());
+        assert_eq!(indices.len(), bars * box_indices.len());
+
+        // The first edge runs along x at y = z = -extent / 2, its ticks follow it and point
+        // along -y.
+        let bar = |i: usize| {
+            let positions = vertices[i * box_vertices.len()..(i + 1) * box_vertices.len()]
+                .iter()
+                .map(|vertex| Vec3::from(vertex.position));
+            let min = positions
+                .clone()
+                .fold(Vec3::splat(f32::INFINITY), Vec3::min);
+            let max = positions.fold(Vec3::splat(f32::NEG_INFINITY), Vec3::max);
+            (min, max)
+        };
+        for k in 0..=10 {
+            let (min, max) = bar(1 + k);
+            let center = (min + max) / 2.0;
+            assert!((center.x - (-50.0 + 10.0 * k as f32)).abs() < 1e-4);
+            let length = if k % 5 == 0 { 3.0 } else { 1.5 };
+            assert!((max.y - min.y - length).abs() < 1e-4);
+            assert!(max.y <= -25.0 + 1e-4);
+        }
+    }
+}
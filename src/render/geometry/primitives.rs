@@ -0,0 +1,707 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use glam::{Mat3, Mat4, Quat, Vec3};
+
+use crate::render::vertex::Vertex;
+
+/// The radius of the shaft of an arrow relative to the radius of its head.
+const ARROW_SHAFT_RADIUS: f32 = 0.4;
+
+/// The length of the head of an arrow relative to the length of the arrow.
+const ARROW_HEAD_LENGTH: f32 = 0.35;
+
+/// Calculates the vertecies of a sphere made of rings of latitude and longitude.
+///
+/// The poles lie on the y axis and the texture coordinates wrap once around the sphere.
+///
+/// Arguments:
+///
+/// * `index_offset`: The offset added to every index, e.g. the number of vertices of the mesh
+///   the shape is appended to.
+/// * `position`: The position of the center of the sphere.
+/// * `size`: The outer dimensions of the sphere, which is its diameter along each axis.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   sphere around its center.
+/// * `segments`: The number of segments around the y axis, at least three.
+/// * `rings`: The number of rings from pole to pole, at least two.
+pub fn get_uv_sphere_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+    segments: u32,
+    rings: u32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+
+    let mut vertices = Vec::new();
+    for i in 0..=rings {
+        let v = i as f32 / rings as f32;
+        let theta = PI * v;
+        for j in 0..=segments {
+            let u = j as f32 / segments as f32;
+            let phi = 2.0 * PI * u;
+            let normal = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            vertices.push(Vertex {
+                position: (normal * 0.5).to_array(),
+                tex_coords: [u, v],
+                normal: normal.to_array(),
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    let row = segments + 1;
+    for i in 0..rings {
+        for j in 0..segments {
+            let (a, c) = (i * row + j, i * row + j + 1);
+            let (b, d) = (a + row, c + row);
+            // The triangles touching the poles are degenerate and skipped.
+            if i != 0 {
+                indices.extend([a, c, d]);
+            }
+            if i != rings - 1 {
+                indices.extend([a, d, b]);
+            }
+        }
+    }
+
+    transform_shape(vertices, indices, index_offset, position, size, rotation)
+}
+
+/// Calculates the vertecies of a sphere made of evenly sized triangles.
+///
+/// The sphere is an icosahedron whose triangles are subdivided. The texture coordinates are
+/// mapped like the ones of [get_uv_sphere_vertecies], vertices on the seam are duplicated.
+///
+/// Arguments:
+///
+/// * `index_offset`: The offset added to every index, e.g. the number of vertices of the mesh
+///   the shape is appended to.
+/// * `position`: The position of the center of the sphere.
+/// * `size`: The outer dimensions of the sphere, which is its diameter along each axis.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   sphere around its center.
+/// * `subdivisions`: How often each triangle is split into four, which is `0` for an
+///   icosahedron.
+pub fn get_icosphere_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+    subdivisions: u32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut points: Vec<Vec3> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .iter()
+    .map(|p| Vec3::from(*p).normalize())
+    .collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // Edges are shared by two triangles, so their midpoints are reused.
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push((points[a as usize] + points[b as usize]).normalize());
+                points.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let tex_coords = |p: Vec3| {
+        let u = p.z.atan2(p.x) / (2.0 * PI);
+        [
+            if u < 0.0 { u + 1.0 } else { u },
+            p.y.clamp(-1.0, 1.0).acos() / PI,
+        ]
+    };
+    let mut vertices: Vec<Vertex> = points
+        .iter()
+        .map(|p| Vertex {
+            position: (*p * 0.5).to_array(),
+            tex_coords: tex_coords(*p),
+            normal: p.to_array(),
+        })
+        .collect();
+
+    // Triangles crossing the seam get copies of their vertices with `u` beyond one and vertices
+    // on the poles take the `u` of the triangle they belong to.
+    let is_pole = |p: Vec3| p.x.abs() < 1.0e-6 && p.z.abs() < 1.0e-6;
+    let mut copies: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(triangles.len() * 3);
+    for triangle in triangles {
+        let mut us = triangle.map(|i| vertices[i as usize].tex_coords[0]);
+        let poles = triangle.map(|i| is_pole(points[i as usize]));
+        let (min, max) = (0..3)
+            .filter(|&k| !poles[k])
+            .fold((1.0f32, 0.0f32), |(min, max), k| {
+                (min.min(us[k]), max.max(us[k]))
+            });
+        if max - min > 0.5 {
+            for k in 0..3 {
+                if !poles[k] && us[k] < 0.5 {
+                    us[k] += 1.0;
+                }
+            }
+        }
+        if let Some(pole) = (0..3).find(|&k| poles[k]) {
+            us[pole] = (0..3).filter(|&k| k != pole).map(|k| us[k]).sum::<f32>() / 2.0;
+        }
+
+        for k in 0..3 {
+            let index = triangle[k];
+            if us[k] == vertices[index as usize].tex_coords[0] {
+                indices.push(index);
+                continue;
+            }
+            let copy = *copies.entry((index, us[k].to_bits())).or_insert_with(|| {
+                let mut vertex = vertices[index as usize];
+                vertex.tex_coords[0] = us[k];
+                vertices.push(vertex);
+                vertices.len() as u32 - 1
+            });
+            indices.push(copy);
+        }
+    }
+
+    transform_shape(vertices, indices, index_offset, position, size, rotation)
+}
+
+/// Calculates the vertecies of a closed cylinder.
+///
+/// The axis of the cylinder is the y axis.
+///
+/// Arguments:
+///
+/// * `index_offset`: The offset added to every index, e.g. the number of vertices of the mesh
+///   the shape is appended to.
+/// * `position`: The position of the center of the cylinder.
+/// * `size`: The outer dimensions of the cylinder, which are its diameter along x and z and its
+///   height along y.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   cylinder around its center.
+/// * `segments`: The number of segments around the axis, at least three.
+pub fn get_cylinder_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+    segments: u32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    push_tube(
+        &mut vertices,
+        &mut indices,
+        [0.5, 0.5],
+        [0.5, -0.5],
+        segments,
+    );
+    push_disk(&mut vertices, &mut indices, 0.5, 0.5, true, segments);
+    push_disk(&mut vertices, &mut indices, 0.5, -0.5, false, segments);
+
+    transform_shape(vertices, indices, index_offset, position, size, rotation)
+}
+
+/// Calculates the vertecies of a closed cone.
+///
+/// The tip of the cone points along the y axis.
+///
+/// Arguments:
+///
+/// * `index_offset`: The offset added to every index, e.g. the number of vertices of the mesh
+///   the shape is appended to.
+/// * `position`: The position of the center of the cone's bounding box.
+/// * `size`: The outer dimensions of the cone, which are the diameter of its base along x and
+///   z and its height along y.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   cone around its center.
+/// * `segments`: The number of segments around the axis, at least three.
+pub fn get_cone_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+    segments: u32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    push_tube(
+        &mut vertices,
+        &mut indices,
+        [0.0, 0.5],
+        [0.5, -0.5],
+        segments,
+    );
+    push_disk(&mut vertices, &mut indices, 0.5, -0.5, false, segments);
+
+    transform_shape(vertices, indices, index_offset, position, size, rotation)
+}
+
+/// Calculates the vertecies of an arrow made of a cylindrical shaft and a conical head.
+///
+/// The arrow points along the y axis.
+///
+/// Arguments:
+///
+/// * `index_offset`: The offset added to every index, e.g. the number of vertices of the mesh
+///   the shape is appended to.
+/// * `position`: The position of the center of the arrow's bounding box.
+/// * `size`: The outer dimensions of the arrow, which are the diameter of its head along x and
+///   z and its length along y.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   arrow around its center.
+/// * `segments`: The number of segments around the axis, at least three.
+pub fn get_arrow_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+    segments: u32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let shaft_radius = 0.5 * ARROW_SHAFT_RADIUS;
+    let head_start = 0.5 - ARROW_HEAD_LENGTH;
+
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    push_tube(
+        &mut vertices,
+        &mut indices,
+        [shaft_radius, head_start],
+        [shaft_radius, -0.5],
+        segments,
+    );
+    push_disk(
+        &mut vertices,
+        &mut indices,
+        shaft_radius,
+        -0.5,
+        false,
+        segments,
+    );
+    push_tube(
+        &mut vertices,
+        &mut indices,
+        [0.0, 0.5],
+        [0.5, head_start],
+        segments,
+    );
+    push_disk(
+        &mut vertices,
+        &mut indices,
+        0.5,
+        head_start,
+        false,
+        segments,
+    );
+
+    transform_shape(vertices, indices, index_offset, position, size, rotation)
+}
+
+/// Calculates the vertecies of a square plane.
+///
+/// The plane lies in the xz plane and faces along the y axis. It is only visible from the front.
+///
+/// Arguments:
+///
+/// * `index_offset`: The offset added to every index, e.g. the number of vertices of the mesh
+///   the shape is appended to.
+/// * `position`: The position of the center of the plane.
+/// * `size`: The outer dimensions of the plane along x and z.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   plane around its center.
+pub fn get_plane_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = [[-0.5, -0.5], [-0.5, 0.5], [0.5, -0.5], [0.5, 0.5]]
+        .iter()
+        .map(|[x, z]| Vertex {
+            position: [*x, 0.0, *z],
+            tex_coords: [x + 0.5, z + 0.5],
+            normal: [0.0, 1.0, 0.0],
+        })
+        .collect();
+    let indices = vec![0, 1, 3, 0, 3, 2];
+
+    transform_shape(vertices, indices, index_offset, position, size, rotation)
+}
+
+/// Calculates the vertecies of a torus.
+///
+/// The ring of the torus lies in the xz plane around the y axis.
+///
+/// Arguments:
+///
+/// * `index_offset`: The offset added to every index, e.g. the number of vertices of the mesh
+///   the shape is appended to.
+/// * `position`: The position of the center of the torus.
+/// * `size`: The outer dimensions of the torus along x and z. The tube is scaled along y.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   torus around its center.
+/// * `thickness`: The radius of the tube relative to the radius of the ring.
+/// * `segments`: The number of segments around the ring, at least three.
+/// * `sides`: The number of segments around the tube, at least three.
+pub fn get_torus_vertecies(
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+    thickness: f32,
+    segments: u32,
+    sides: u32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let sides = sides.max(3);
+    // The ring and the tube together span the unit box along x and z.
+    let ring_radius = 0.5 / (1.0 + thickness);
+    let tube_radius = ring_radius * thickness;
+
+    let mut vertices = Vec::new();
+    for i in 0..=sides {
+        let v = i as f32 / sides as f32;
+        let theta = 2.0 * PI * v;
+        for j in 0..=segments {
+            let u = j as f32 / segments as f32;
+            let phi = 2.0 * PI * u;
+            let center = Vec3::new(phi.cos(), 0.0, phi.sin()) * ring_radius;
+            let normal = Vec3::new(
+                theta.cos() * phi.cos(),
+                theta.sin(),
+                theta.cos() * phi.sin(),
+            );
+            vertices.push(Vertex {
+                position: (center + normal * tube_radius).to_array(),
+                tex_coords: [u, v],
+                normal: normal.to_array(),
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    let row = segments + 1;
+    for i in 0..sides {
+        for j in 0..segments {
+            let (a, c) = (i * row + j, i * row + j + 1);
+            let (b, d) = (a + row, c + row);
+            indices.extend([a, d, c, a, b, d]);
+        }
+    }
+
+    transform_shape(vertices, indices, index_offset, position, size, rotation)
+}
+
+/// Adds the side of a cylinder whose top and bottom can have different radii.
+///
+/// Arguments:
+///
+/// * `vertices`, `indices`: The mesh the side is added to.
+/// * `top`: The radius and height of the top ring.
+/// * `bottom`: The radius and height of the bottom ring.
+/// * `segments`: The number of segments around the y axis.
+fn push_tube(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    top: [f32; 2],
+    bottom: [f32; 2],
+    segments: u32,
+) {
+    let offset = vertices.len() as u32;
+    // The normals lean towards the narrower end.
+    let slope = (bottom[0] - top[0]) / (top[1] - bottom[1]);
+    for (v, [radius, height]) in [(0.0, top), (1.0, bottom)] {
+        for j in 0..=segments {
+            let u = j as f32 / segments as f32;
+            let phi = 2.0 * PI * u;
+            let direction = Vec3::new(phi.cos(), 0.0, phi.sin());
+            vertices.push(Vertex {
+                position: (direction * radius + Vec3::Y * height).to_array(),
+                tex_coords: [u, v],
+                normal: (direction + Vec3::Y * slope).normalize().to_array(),
+            });
+        }
+    }
+
+    let row = segments + 1;
+    for j in 0..segments {
+        let (a, c) = (offset + j, offset + j + 1);
+        let (b, d) = (a + row, c + row);
+        // The upper triangle is degenerate for the tip of a cone.
+        if top[0] > 0.0 {
+            indices.extend([a, c, d]);
+        }
+        indices.extend([a, d, b]);
+    }
+}
+
+/// Adds a disk in the xz plane.
+///
+/// Arguments:
+///
+/// * `vertices`, `indices`: The mesh the disk is added to.
+/// * `radius`: The radius of the disk.
+/// * `height`: The y coordinate of the disk.
+/// * `up`: Whether the disk faces along the y axis or against it.
+/// * `segments`: The number of segments around the y axis.
+fn push_disk(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    radius: f32,
+    height: f32,
+    up: bool,
+    segments: u32,
+) {
+    let offset = vertices.len() as u32;
+    let normal = if up {
+        [0.0, 1.0, 0.0]
+    } else {
+        [0.0, -1.0, 0.0]
+    };
+    vertices.push(Vertex {
+        position: [0.0, height, 0.0],
+        tex_coords: [0.5, 0.5],
+        normal,
+    });
+    for j in 0..=segments {
+        let phi = 2.0 * PI * j as f32 / segments as f32;
+        vertices.push(Vertex {
+            position: [phi.cos() * radius, height, phi.sin() * radius],
+            tex_coords: [0.5 + 0.5 * phi.cos(), 0.5 + 0.5 * phi.sin()],
+            normal,
+        });
+    }
+
+    for j in 0..segments {
+        let (a, b) = (offset + 1 + j, offset + 2 + j);
+        if up {
+            indices.extend([offset, b, a]);
+        } else {
+            indices.extend([offset, a, b]);
+        }
+    }
+}
+
+/// Places a shape defined within the unit box like [crate::render::geometry::r#box::get_box_vertecies]
+/// places a box.
+///
+/// Arguments:
+///
+/// * `vertices`, `indices`: The mesh of the shape.
+/// * `index_offset`: The offset added to every index.
+/// * `position`: The position of the center of the shape.
+/// * `size`: The outer dimensions of the shape.
+/// * `rotation`: The `XYZ` - Euler angles which represent the rotation of the
+///   shape around its center.
+fn transform_shape(
+    mut vertices: Vec<Vertex>,
+    mut indices: Vec<u32>,
+    index_offset: u32,
+    position: Vec3,
+    size: Vec3,
+    rotation: Vec3,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let rotation = Quat::from_euler(glam::EulerRot::XYZ, rotation.x, rotation.y, rotation.z);
+    let transform = Mat4::from_scale_rotation_translation(size, rotation, position);
+
+    // Normals are scaled inversely. A flat dimension like the thickness of a plane keeps them.
+    let inverse_size = Vec3::select(size.cmpeq(Vec3::ZERO), Vec3::ONE, size.recip());
+    let normal_matrix = Mat3::from_quat(rotation) * Mat3::from_diagonal(inverse_size);
+    for vertex in &mut vertices {
+        vertex.position = transform
+            .transform_point3(Vec3::from(vertex.position))
+            .to_array();
+        vertex.normal = (normal_matrix * Vec3::from(vertex.normal))
+            .normalize()
+            .to_array();
+    }
+
+    // A mirroring size flips the winding of the triangles.
+    if size.x * size.y * size.z < 0.0 {
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+    for index in &mut indices {
+        *index += index_offset;
+    }
+
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{EulerRot, Quat, Vec3};
+
+    use super::{
+        get_arrow_vertecies, get_cone_vertecies, get_cylinder_vertecies, get_icosphere_vertecies,
+        get_plane_vertecies, get_torus_vertecies, get_uv_sphere_vertecies,
+    };
+    use crate::render::vertex::Vertex;
+
+    const OFFSET: u32 = 7;
+    const POSITION: Vec3 = Vec3::new(1.0, -2.0, 3.0);
+    const SIZE: Vec3 = Vec3::new(2.0, 3.0, 4.0);
+    const ROTATION: Vec3 = Vec3::new(0.3, -0.2, 0.1);
+
+    /// Checks that the indices address the vertices after [OFFSET], that the normals have unit
+    /// length and face the same side as the counter-clockwise triangles and that the texture
+    /// coordinates lie within the texture.
+    ///
+    /// Arguments:
+    ///
+    /// * `vertices`, `indices`: The mesh of the shape.
+    /// * `max_u`: The largest `u` texture coordinate, beyond one for copies of vertices on a
+    ///   seam that wrap around.
+    fn check_mesh(vertices: &[Vertex], indices: &[u32], max_u: f32) {
+        assert!(!vertices.is_empty());
+        assert_eq!(indices.len() % 3, 0);
+        let end = OFFSET + vertices.len() as u32;
+        assert!(indices.iter().all(|&index| (OFFSET..end).contains(&index)));
+
+        for vertex in vertices {
+            assert!((Vec3::from(vertex.normal).length() - 1.0).abs() < 1e-5);
+            let [u, v] = vertex.tex_coords;
+            assert!((0.0..=max_u).contains(&u) && (0.0..=1.0).contains(&v));
+        }
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| &vertices[(triangle[i] - OFFSET) as usize]);
+            let [pa, pb, pc] = [a, b, c].map(|v| Vec3::from(v.position));
+            let face = (pb - pa).cross(pc - pa);
+            // The triangles at the tip of a cone are degenerate.
+            if face.length() < 1e-6 {
+                continue;
+            }
+            let normal = Vec3::from(a.normal) + Vec3::from(b.normal) + Vec3::from(c.normal);
+            assert!(face.dot(normal) > 0.0, "{:?} is wound inwards", triangle);
+        }
+    }
+
+    /// Returns the y axis rotated by [ROTATION], along which arrows point and planes face.
+    fn rotated_y() -> Vec3 {
+        Quat::from_euler(EulerRot::XYZ, ROTATION.x, ROTATION.y, ROTATION.z) * Vec3::Y
+    }
+
+    /// Checks that every normal points away from the center of a convex shape.
+    fn check_outward(vertices: &[Vertex]) {
+        for vertex in vertices {
+            let outward = Vec3::from(vertex.position) - POSITION;
+            assert!(Vec3::from(vertex.normal).dot(outward) > 0.0, "{:?}", vertex);
+        }
+    }
+
+    #[test]
+    fn spheres() {
+        let (vertices, indices) = get_uv_sphere_vertecies(OFFSET, POSITION, SIZE, ROTATION, 12, 8);
+        assert_eq!(vertices.len(), 13 * 9);
+        check_mesh(&vertices, &indices, 1.0);
+        check_outward(&vertices);
+
+        let (vertices, indices) = get_icosphere_vertecies(OFFSET, POSITION, SIZE, ROTATION, 2);
+        assert_eq!(indices.len(), 20 * 16 * 3);
+        // Triangles crossing the seam continue the texture beyond its right edge.
+        check_mesh(&vertices, &indices, 1.5);
+        check_outward(&vertices);
+    }
+
+    #[test]
+    fn cylinder_and_cone() {
+        let (vertices, indices) = get_cylinder_vertecies(OFFSET, POSITION, SIZE, ROTATION, 16);
+        check_mesh(&vertices, &indices, 1.0);
+        check_outward(&vertices);
+
+        let (vertices, indices) = get_cone_vertecies(OFFSET, POSITION, SIZE, ROTATION, 16);
+        check_mesh(&vertices, &indices, 1.0);
+        check_outward(&vertices);
+    }
+
+    #[test]
+    fn arrow() {
+        // The base of the head faces backwards, so only the winding is checked.
+        let (vertices, indices) = get_arrow_vertecies(OFFSET, POSITION, SIZE, ROTATION, 16);
+        check_mesh(&vertices, &indices, 1.0);
+        let tip = vertices
+            .iter()
+            .map(|vertex| Vec3::from(vertex.position) - POSITION)
+            .max_by(|a, b| a.dot(rotated_y()).total_cmp(&b.dot(rotated_y())))
+            .unwrap();
+        assert!(tip.abs_diff_eq(rotated_y() * SIZE.y / 2.0, 1e-5));
+    }
+
+    #[test]
+    fn plane() {
+        let (vertices, indices) = get_plane_vertecies(OFFSET, POSITION, SIZE, ROTATION);
+        assert_eq!(indices.len(), 6);
+        check_mesh(&vertices, &indices, 1.0);
+        for vertex in &vertices {
+            assert!(Vec3::from(vertex.normal).abs_diff_eq(rotated_y(), 1e-5));
+        }
+    }
+
+    #[test]
+    fn torus() {
+        let (vertices, indices) =
+            get_torus_vertecies(OFFSET, Vec3::ZERO, Vec3::ONE, Vec3::ZERO, 0.25, 24, 12);
+        assert_eq!(indices.len(), 24 * 12 * 6);
+        check_mesh(&vertices, &indices, 1.0);
+        // The normals point away from the circle through the center of the tube.
+        let ring_radius = 0.5 / 1.25;
+        for vertex in &vertices {
+            let position = Vec3::from(vertex.position);
+            let center = (position * Vec3::new(1.0, 0.0, 1.0)).normalize() * ring_radius;
+            let outward = (position - center).normalize();
+            assert!(Vec3::from(vertex.normal).abs_diff_eq(outward, 1e-5));
+        }
+
+        let (vertices, indices) = get_torus_vertecies(OFFSET, POSITION, SIZE, ROTATION, 0.25, 8, 6);
+        check_mesh(&vertices, &indices, 1.0);
+    }
+}
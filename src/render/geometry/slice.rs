@@ -0,0 +1,38 @@
+use glam::Vec3;
+
+use crate::{data::volume::Volume, render::vertex::Vertex};
+
+/// Calculates the vertecies of a slice plane through a volume, e.g. to draw the volume layers
+/// of a [crate::render::state::State] with a `layered` material.
+///
+/// The plane is given in voxel coordinates and covers the outer edges of the voxels, so
+/// [Volume::affine] places it in world space. Both sides are drawn, each with its own normal.
+///
+/// Arguments:
+///
+/// * `volume`: The volume the slice cuts through.
+/// * `axis`: The axis normal to the slice, `0` for x, `1` for y and `2` for z.
+/// * `position`: The position of the slice along the axis in voxel coordinates.
+pub fn get_slice_vertecies(volume: &Volume, axis: usize, position: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mut normal = Vec3::ZERO;
+    normal[axis] = 1.0;
+
+    let mut vertices = Vec::new();
+    for side in [1.0, -1.0] {
+        for (tu, tv) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let mut corner = Vec3::ZERO;
+            corner[axis] = position;
+            corner[u] = tu * volume.dims[u] as f32 - 0.5;
+            corner[v] = tv * volume.dims[v] as f32 - 0.5;
+            vertices.push(Vertex {
+                position: corner.to_array(),
+                tex_coords: [tu, tv],
+                normal: (normal * side).to_array(),
+            });
+        }
+    }
+    // The front faces the normal of the first side and the back faces the opposite way.
+    let indices = vec![0, 1, 2, 0, 2, 3, 4, 6, 5, 4, 7, 6];
+    (vertices, indices)
+}
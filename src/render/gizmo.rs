@@ -0,0 +1,166 @@
+use glam::{Mat3, Mat4, Quat, Vec3};
+
+use crate::render::{
+    camera::{CameraUniform, OrbitCamera},
+    geometry::{letters::get_letter_vertecies, primitives::get_arrow_vertecies},
+    scene::{Material, SceneObject},
+};
+
+/// The size of the gizmo viewport relative to the smaller dimension of the render target.
+pub const GIZMO_VIEWPORT_FRACTION: f32 = 0.25;
+
+/// The half size of the region around the origin that is shown in the gizmo viewport.
+const GIZMO_EXTENT: f32 = 1.5;
+
+/// The height of the labels at the ends of the axes.
+const LABEL_SIZE: f32 = 0.3;
+
+/// The labels of the negative and positive world axes in RAS+ coordinates.
+const WORLD_LABELS: [[char; 2]; 3] = [['L', 'R'], ['P', 'A'], ['I', 'S']];
+
+/// Returns the anatomical labels of the scene axes.
+///
+/// Each entry holds the label of the positive and of the negative end of the x, y and z axis.
+/// An axis is labeled after the world axis it is closest to, like `aff2axcodes` of nibabel.
+///
+/// Arguments:
+///
+/// * `orientation`: The transform from scene coordinates to RAS+ world coordinates, e.g. the
+///   identity for isosurfaces or [crate::data::volume::Volume::box_transform] for the box proxy.
+pub fn orientation_labels(orientation: Mat4) -> [[char; 2]; 3] {
+    let linear = Mat3::from_mat4(orientation);
+    [0, 1, 2].map(|axis| {
+        let direction = linear.col(axis);
+        let world_axis = (0..3)
+            .max_by(|&a, &b| direction[a].abs().total_cmp(&direction[b].abs()))
+            .unwrap_or(axis);
+        let [negative, positive] = WORLD_LABELS[world_axis];
+        if direction[world_axis] >= 0.0 {
+            [positive, negative]
+        } else {
+            [negative, positive]
+        }
+    })
+}
+
+/// An axes triad with anatomical labels that shows the orientation of the scene.
+///
+/// The gizmo is drawn into a corner of the render target with its own camera, which follows the
+/// rotation of the scene camera.
+pub(crate) struct Gizmo {
+    /// The arrows of the axes followed by the labels.
+    pub(crate) objects: Vec<SceneObject>,
+
+    /// The position of each label, the arrows have none.
+    anchors: Vec<Option<Vec3>>,
+}
+
+impl Gizmo {
+    /// Creates the arrows and labels of the gizmo.
+    ///
+    /// Arguments:
+    ///
+    /// * `orientation`: The transform from scene coordinates to RAS+ world coordinates.
+    pub(crate) fn new(orientation: Mat4) -> Self {
+        let colors = [
+            [0.9, 0.2, 0.2, 1.0],
+            [0.2, 0.8, 0.2, 1.0],
+            [0.3, 0.4, 1.0, 1.0],
+        ];
+        // The rotations turn an arrow along the y axis onto the x, y and z axis.
+        let rotations = [
+            Vec3::new(0.0, 0.0, -std::f32::consts::FRAC_PI_2),
+            Vec3::ZERO,
+            Vec3::new(std::f32::consts::FRAC_PI_2, 0.0, 0.0),
+        ];
+        let labels = orientation_labels(orientation);
+
+        let mut objects = Vec::new();
+        let mut anchors = Vec::new();
+        for axis in 0..3 {
+            let direction = Vec3::AXES[axis];
+            let (vertices, indices) = get_arrow_vertecies(
+                0,
+                direction * 0.5,
+                Vec3::new(0.2, 1.0, 0.2),
+                rotations[axis],
+                16,
+            );
+            let mut arrow = SceneObject::new("gizmo axis", vertices, indices);
+            arrow.material = Material::from_color(colors[axis]);
+            objects.push(arrow);
+            anchors.push(None);
+        }
+        for axis in 0..3 {
+            let direction = Vec3::AXES[axis];
+            // The negative ends have no arrows, so their labels are dimmed.
+            let [r, g, b, a] = colors[axis];
+            let ends = [(1.3, colors[axis]), (-0.8, [r * 0.6, g * 0.6, b * 0.6, a])];
+            for (label, (distance, color)) in labels[axis].iter().zip(ends) {
+                let (vertices, indices) =
+                    get_letter_vertecies(0, Vec3::ZERO, LABEL_SIZE, Vec3::ZERO, *label);
+                let mut letter = SceneObject::new("gizmo label", vertices, indices);
+                letter.material = Material {
+                    lit: false,
+                    ..Material::from_color(color)
+                };
+                objects.push(letter);
+                anchors.push(Some(direction * distance));
+            }
+        }
+
+        Self { objects, anchors }
+    }
+
+    /// Returns the camera uniform of the gizmo viewport.
+    ///
+    /// The gizmo is viewed from the direction of the scene camera with an orthographic
+    /// projection, so it keeps its size while zooming.
+    ///
+    /// Arguments:
+    ///
+    /// * `camera`: The camera of the scene.
+    pub(crate) fn camera_uniform(camera: &OrbitCamera) -> CameraUniform {
+        let (eye, up) = Self::view(camera);
+        let view = Mat4::look_at_rh(eye, Vec3::ZERO, up);
+        let proj = Mat4::orthographic_rh(
+            -GIZMO_EXTENT,
+            GIZMO_EXTENT,
+            -GIZMO_EXTENT,
+            GIZMO_EXTENT,
+            0.1,
+            2.0 * eye.length(),
+        );
+        CameraUniform {
+            view_position: eye.extend(1.0).to_array(),
+            view_proj: (proj * view).to_cols_array_2d(),
+        }
+    }
+
+    /// Turns the labels towards the camera so that they stay readable.
+    ///
+    /// Arguments:
+    ///
+    /// * `camera`: The camera of the scene.
+    pub(crate) fn face_camera(&mut self, camera: &OrbitCamera) {
+        let (eye, up) = Self::view(camera);
+        let view = Mat4::look_at_rh(eye, Vec3::ZERO, up);
+        let rotation = Quat::from_mat4(&view.inverse());
+        for (object, anchor) in self.objects.iter_mut().zip(&self.anchors) {
+            if let Some(anchor) = anchor {
+                object.transform = Mat4::from_rotation_translation(rotation, *anchor);
+            }
+        }
+    }
+
+    /// Returns the eye and the up vector of the gizmo camera.
+    fn view(camera: &OrbitCamera) -> (Vec3, Vec3) {
+        let direction = (camera.eye.to_vec3() - camera.target.to_vec3()).normalize_or_zero();
+        let direction = if direction == Vec3::ZERO {
+            Vec3::Z
+        } else {
+            direction
+        };
+        (direction * 2.0 * GIZMO_EXTENT, camera.up.to_vec3())
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    data::{label_map::LabelMap, lookup_table::LookupTable},
+    render::{
+        geometry::isosurface::get_label_surface_vertecies,
+        scene::{Material, ObjectId, SceneObject},
+        state::State,
+    },
+};
+
+/// The scene objects that show a label map, one surface per label.
+///
+/// Each surface is colored from the lookup table, so the visibility and opacity of a label are
+/// changed without extracting the surface again.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LabelSurfaces {
+    /// The objects by the value of their label.
+    objects: BTreeMap<u32, ObjectId>,
+}
+
+impl LabelSurfaces {
+    /// Extracts the surface of every label of a label map and adds them to the scene.
+    ///
+    /// Labels without an entry in the lookup table are not shown.
+    ///
+    /// Arguments:
+    ///
+    /// * `state`: The state whose scene the surfaces are added to.
+    /// * `label_map`: The label map and its lookup table.
+    pub fn add_to_scene(state: &mut State, label_map: &LabelMap) -> Self {
+        let mut objects = BTreeMap::new();
+        for label in label_map.labels() {
+            let Some(entry) = label_map.table.get(label) else {
+                continue;
+            };
+            let (vertices, indices) = get_label_surface_vertecies(&label_map.volume, label as f32);
+            let object = SceneObject::new(&entry.name, vertices, indices);
+            objects.insert(label, state.add_object(object));
+        }
+        let surfaces = Self { objects };
+        surfaces.apply(state, &label_map.table);
+        surfaces
+    }
+
+    /// Returns the object that shows a label.
+    ///
+    /// Arguments:
+    ///
+    /// * `label`: The value of the label.
+    pub fn object(&self, label: u32) -> Option<ObjectId> {
+        self.objects.get(&label).copied()
+    }
+
+    /// Returns the objects of all labels by the value of their label.
+    pub fn objects(&self) -> impl Iterator<Item = (u32, ObjectId)> + '_ {
+        self.objects.iter().map(|(&label, &id)| (label, id))
+    }
+
+    /// Applies the colors, opacities and visibility of a lookup table to the surfaces.
+    ///
+    /// Arguments:
+    ///
+    /// * `state`: The state whose scene contains the surfaces.
+    /// * `table`: The lookup table of the label map.
+    pub fn apply(&self, state: &mut State, table: &LookupTable) {
+        for (label, &id) in &self.objects {
+            let (Some(entry), Some(object)) = (table.get(*label), state.object_mut(id)) else {
+                continue;
+            };
+            object.material = Material::from_color(entry.rgba());
+            object.visible = entry.visible && entry.opacity > 0.0;
+        }
+    }
+
+    /// Removes the surfaces from the scene.
+    ///
+    /// Arguments:
+    ///
+    /// * `state`: The state whose scene contains the surfaces.
+    pub fn remove(self, state: &mut State) {
+        for id in self.objects.into_values() {
+            state.remove_object(id);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        label_map::{label_value, LabelMap},
+        lookup_table::LookupTable,
+        volume::Volume,
+    },
+    render::overlay::{Colormap, COLORMAP_SIZE},
+};
+
+/// The largest number of volume layers that are drawn together.
+pub const MAX_LAYERS: usize = 4;
+
+/// The largest number of labels a label map layer can color, the first entry of its lookup table
+/// is the transparent background.
+pub const MAX_LAYER_LABELS: usize = COLORMAP_SIZE - 1;
+
+/// Identifies a volume layer that was added to a [crate::render::state::State].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerId(pub(crate) u32);
+
+/// How the color of a layer is combined with the layers below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// The layer is drawn over the layers below it with its opacity.
+    Alpha,
+
+    /// The color of the layer weighted by its opacity is added to the layers below it.
+    Additive,
+
+    /// Every component takes the larger value of the layer and the layers below it.
+    Max,
+}
+
+impl BlendMode {
+    /// Returns the index of the blend mode in the shader.
+    fn index(&self) -> f32 {
+        match self {
+            Self::Alpha => 0.0,
+            Self::Additive => 1.0,
+            Self::Max => 2.0,
+        }
+    }
+}
+
+/// The settings that control how a volume layer is colored and blended.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerSettings {
+    /// Whether the layer is drawn.
+    pub visible: bool,
+
+    /// The colormap applied to the intensities. Label maps use their lookup table instead.
+    pub colormap: Colormap,
+
+    /// The intensity mapped to the start of the colormap.
+    pub min: f32,
+
+    /// The intensity mapped to the end of the colormap.
+    pub max: f32,
+
+    /// Voxels with intensities below the threshold are transparent.
+    pub threshold: Option<f32>,
+
+    /// Whether the magnitude of the intensities is compared with the threshold, e.g. for
+    /// statistical maps with negative values.
+    pub absolute_threshold: bool,
+
+    /// The opacity from `0.0` for transparent to `1.0` for opaque.
+    pub opacity: f32,
+
+    /// How the layer is combined with the layers below it.
+    pub blend: BlendMode,
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            colormap: Colormap::Gray,
+            min: 0.0,
+            max: 1.0,
+            threshold: None,
+            absolute_threshold: false,
+            opacity: 1.0,
+            blend: BlendMode::Alpha,
+        }
+    }
+}
+
+impl LayerSettings {
+    /// Creates settings whose window spans the intensities of a volume.
+    ///
+    /// Arguments:
+    ///
+    /// * `colormap`: The colormap applied to the intensities.
+    /// * `volume`: The volume of the layer, only its displayed frame is used.
+    pub fn fit(colormap: Colormap, volume: &Volume) -> Self {
+        let (min, max) = volume.min_max();
+        let (min, max) = if min <= max { (min, max) } else { (0.0, 1.0) };
+        Self {
+            colormap,
+            min,
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Creates settings that color the intensities of a time series above a threshold with the
+    /// hot colormap. The window spans all frames, so colors keep their meaning while the frames
+    /// are played.
+    ///
+    /// Arguments:
+    ///
+    /// * `volume`: The volume of the layer with all frames.
+    /// * `threshold`: Voxels below the threshold are transparent.
+    pub fn time_series(volume: &Volume, threshold: f32) -> Self {
+        let (_, max) = volume.series_min_max();
+        Self {
+            colormap: Colormap::Hot,
+            min: threshold,
+            max: max.max(threshold),
+            threshold: Some(threshold),
+            ..Default::default()
+        }
+    }
+}
+
+/// How two volume layers are compared, e.g. to check the registration of a moving image to a
+/// reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareMode {
+    /// Cubic tiles of the two layers alternate like the squares of a checkerboard.
+    Checkerboard,
+
+    /// The reference is shown left of a vertical divider and the moving layer right of it.
+    Curtain,
+
+    /// The signed difference of the moving layer minus the reference is colored with a
+    /// diverging colormap.
+    Difference,
+}
+
+impl CompareMode {
+    /// Returns the index of the compare mode in the shader, `0` means no comparison.
+    fn index(&self) -> f32 {
+        match self {
+            Self::Checkerboard => 1.0,
+            Self::Curtain => 2.0,
+            Self::Difference => 3.0,
+        }
+    }
+}
+
+/// The settings that control how two volume layers are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompareSettings {
+    /// How the layers are combined.
+    pub mode: CompareMode,
+
+    /// The edge length of the tiles of the checkerboard in millimetres.
+    pub tile_size: f32,
+
+    /// The position of the curtain divider from `0.0` at the left to `1.0` at the right edge
+    /// of the view.
+    pub divider: f32,
+
+    /// The difference that is mapped to the ends of the colormap, the colormap is centered on
+    /// zero.
+    pub difference_limit: f32,
+
+    /// The diverging colormap of the difference.
+    pub difference_colormap: Colormap,
+}
+
+impl Default for CompareSettings {
+    fn default() -> Self {
+        Self {
+            mode: CompareMode::Checkerboard,
+            tile_size: 10.0,
+            divider: 0.5,
+            difference_limit: 1.0,
+            difference_colormap: Colormap::BlueRed,
+        }
+    }
+}
+
+/// A comparison of two volume layers, which are drawn together at the position of the
+/// reference in the stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    /// The layer the moving layer is compared with, e.g. the fixed image of a registration.
+    pub reference: LayerId,
+
+    /// The layer that is compared with the reference, e.g. the registered moving image.
+    pub moving: LayerId,
+
+    /// How the layers are combined.
+    pub settings: CompareSettings,
+}
+
+/// A volume that colors the objects of the scene whose material is `layered`, e.g. an
+/// anatomical underlay, a statistical overlay or a label map.
+///
+/// The layers are sampled at the world position of every fragment through their own affine, so
+/// volumes on different grids are resampled into the space of the underlay.
+#[derive(Debug, Clone)]
+pub struct VolumeLayer {
+    /// The volume whose displayed frame is drawn.
+    pub volume: Volume,
+
+    /// The colormap, window, threshold, opacity, visibility and blend mode.
+    pub settings: LayerSettings,
+
+    /// The lookup table of a label map, whose labels are never interpolated or windowed.
+    pub table: Option<LookupTable>,
+
+    /// The labels that occur in a label map in ascending order, label `palette[i]` is drawn
+    /// with entry `i + 1` of the lookup table of the layer.
+    palette: Vec<u32>,
+}
+
+impl VolumeLayer {
+    /// Creates a layer of intensities.
+    ///
+    /// Arguments:
+    ///
+    /// * `volume`: The volume whose displayed frame is drawn.
+    /// * `settings`: The colormap, window, threshold, opacity, visibility and blend mode.
+    pub fn new(volume: Volume, settings: LayerSettings) -> Self {
+        Self {
+            volume,
+            settings,
+            table: None,
+            palette: Vec::new(),
+        }
+    }
+
+    /// Creates a layer of a label map, which is colored by its lookup table.
+    ///
+    /// Only the first [MAX_LAYER_LABELS] labels of the volume are drawn.
+    ///
+    /// Arguments:
+    ///
+    /// * `label_map`: The label map and its lookup table.
+    pub fn from_label_map(label_map: LabelMap) -> Self {
+        let mut palette = label_map.labels();
+        palette.truncate(MAX_LAYER_LABELS);
+        Self {
+            volume: label_map.volume,
+            settings: LayerSettings::default(),
+            table: Some(label_map.table),
+            palette,
+        }
+    }
+
+    /// Returns whether the layer is a label map.
+    pub fn is_label_map(&self) -> bool {
+        self.table.is_some()
+    }
+
+    /// Returns the voxels that are uploaded to the 3D texture of the layer, the intensities or
+    /// the entries of the lookup table of label maps.
+    pub(crate) fn voxels(&self) -> Vec<f32> {
+        if self.table.is_none() {
+            return self.volume.data.clone();
+        }
+        self.volume
+            .data
+            .iter()
+            .map(|&value| {
+                label_value(value)
+                    .and_then(|label| self.palette.binary_search(&label).ok())
+                    .map_or(0.0, |i| (i + 1) as f32)
+            })
+            .collect()
+    }
+
+    /// Returns the lookup table of the layer with [COLORMAP_SIZE] sRGB colors, whose alpha is
+    /// the opacity of the labels of label maps.
+    pub(crate) fn lookup_table(&self) -> Vec<[u8; 4]> {
+        let Some(table) = &self.table else {
+            return self.settings.colormap.lookup_table();
+        };
+        let mut entries = vec![[0; 4]; COLORMAP_SIZE];
+        for (entry, &label) in entries[1..].iter_mut().zip(&self.palette) {
+            if let Some(label) = table.get(label).filter(|label| label.visible) {
+                let [r, g, b] = label.color;
+                *entry = [r, g, b, (label.opacity * 255.0).round() as u8];
+            }
+        }
+        entries
+    }
+}
+
+/// The data of a volume layer that is passed to the shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LayerUniform {
+    /// The transform from world coordinates to the voxel coordinates of the layer.
+    world_to_voxel: [[f32; 4]; 4],
+
+    /// The number of voxels along the x, y and z axes followed by the opacity.
+    dims: [f32; 4],
+
+    /// The intensities mapped to the start and end of the colormap and the threshold followed
+    /// by `1.0` if the layer is visible.
+    window: [f32; 4],
+
+    /// The blend mode, `1.0` if the layer is a label map and `1.0` if the threshold applies to
+    /// the magnitude of the intensities, the rest is padding.
+    options: [f32; 4],
+}
+
+impl From<&VolumeLayer> for LayerUniform {
+    fn from(layer: &VolumeLayer) -> Self {
+        let settings = &layer.settings;
+        let flag = |enabled: bool| if enabled { 1.0 } else { 0.0 };
+        let [nx, ny, nz] = layer.volume.dims;
+
+        Self {
+            world_to_voxel: layer.volume.affine.inverse().to_cols_array_2d(),
+            dims: [
+                nx as f32,
+                ny as f32,
+                nz as f32,
+                settings.opacity.clamp(0.0, 1.0),
+            ],
+            window: [
+                settings.min,
+                settings.max,
+                settings.threshold.unwrap_or(f32::MIN),
+                flag(settings.visible),
+            ],
+            options: [
+                settings.blend.index(),
+                flag(layer.is_label_map()),
+                flag(settings.absolute_threshold),
+                0.0,
+            ],
+        }
+    }
+}
+
+/// The volume layers that are passed to the shader, from the bottom to the top.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LayersUniform {
+    layers: [LayerUniform; MAX_LAYERS],
+
+    /// The number of layers, the rest is padding.
+    count: [f32; 4],
+
+    /// The compare mode, `0.0` for none, the indices of the reference and the moving layer and
+    /// the tile size of the checkerboard.
+    comparison: [f32; 4],
+
+    /// The curtain divider and the difference limit, the rest is padding.
+    compare_range: [f32; 4],
+}
+
+impl LayersUniform {
+    /// Creates the uniform of up to [MAX_LAYERS] layers.
+    ///
+    /// Arguments:
+    ///
+    /// * `layers`: The layers from the bottom to the top.
+    /// * `comparison`: The indices of the reference and the moving layer in `layers` and how
+    ///   they are compared, if any.
+    pub(crate) fn new<'a>(
+        layers: impl Iterator<Item = &'a VolumeLayer>,
+        comparison: Option<(usize, usize, &CompareSettings)>,
+    ) -> Self {
+        let mut uniform: Self = bytemuck::Zeroable::zeroed();
+        for (slot, layer) in uniform.layers.iter_mut().zip(layers) {
+            *slot = LayerUniform::from(layer);
+            uniform.count[0] += 1.0;
+        }
+        if let Some((reference, moving, settings)) = comparison {
+            uniform.comparison = [
+                settings.mode.index(),
+                reference as f32,
+                moving as f32,
+                settings.tile_size.max(f32::EPSILON),
+            ];
+            uniform.compare_range = [
+                settings.divider,
+                settings.difference_limit.abs().max(f32::EPSILON),
+                0.0,
+                0.0,
+            ];
+        }
+        uniform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Mat4;
+
+    use super::{LayerSettings, VolumeLayer, MAX_LAYER_LABELS};
+    use crate::{
+        data::{label_map::LabelMap, lookup_table::LookupTable, volume::tests::test_volume},
+        render::overlay::Colormap,
+    };
+
+    /// Returns a label map layer whose lookup table colors every label with its value.
+    ///
+    /// Arguments:
+    ///
+    /// * `data`: The labels of a row of voxels.
+    fn label_layer(data: &[f32]) -> VolumeLayer {
+        let table: String = (0..400)
+            .map(|label| format!("{} Label{} {} 0 0 0\n", label, label, label % 256))
+            .collect();
+        let volume = test_volume([data.len(), 1, 1], Mat4::IDENTITY, data);
+        VolumeLayer::from_label_map(LabelMap::new(volume, LookupTable::parse(&table).unwrap()))
+    }
+
+    #[test]
+    fn labels_are_compacted_into_the_lookup_table() {
+        let layer = label_layer(&[0.0, 300.0, 17.0, 300.0, 5.0]);
+        assert_eq!(layer.voxels(), [0.0, 3.0, 2.0, 3.0, 1.0]);
+
+        let entries = layer.lookup_table();
+        assert_eq!(entries[0], [0; 4]);
+        assert_eq!(entries[1], [5, 0, 0, 255]);
+        assert_eq!(entries[2], [17, 0, 0, 255]);
+        assert_eq!(entries[3], [44, 0, 0, 255]);
+        assert!(entries[4..].iter().all(|&entry| entry == [0; 4]));
+    }
+
+    #[test]
+    fn labels_beyond_the_lookup_table_are_transparent() {
+        let data: Vec<f32> = (1..=300).map(|label| label as f32).collect();
+        let layer = label_layer(&data);
+        let voxels = layer.voxels();
+        assert_eq!(voxels[MAX_LAYER_LABELS - 1], MAX_LAYER_LABELS as f32);
+        assert!(voxels[MAX_LAYER_LABELS..].iter().all(|&voxel| voxel == 0.0));
+        assert_eq!(layer.lookup_table().len(), MAX_LAYER_LABELS + 1);
+    }
+
+    #[test]
+    fn time_series_window_spans_all_frames() {
+        let volume = test_volume([2, 1, 1], Mat4::IDENTITY, &[0.0, 1.0, 5.0, 2.0, 3.0, 9.0]);
+        let settings = LayerSettings::time_series(&volume, 2.0);
+        assert_eq!(settings.colormap, Colormap::Hot);
+        assert_eq!((settings.min, settings.max), (2.0, 9.0));
+        assert_eq!(settings.threshold, Some(2.0));
+
+        let mut layer = VolumeLayer::new(volume, settings);
+        assert_eq!(layer.voxels(), [0.0, 1.0]);
+        layer.volume.set_frame(2);
+        assert_eq!(layer.voxels(), [3.0, 9.0]);
+    }
+}
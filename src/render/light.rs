@@ -1,3 +1,5 @@
+use crate::render::settings::{Lighting, RenderSettings};
+
 /// The light data is used to compute the scenes lighting in the shader.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -9,7 +11,36 @@ pub struct LightUniform {
 
     /// The color of the light.
     ///
-    /// The format is RGB (`[1.0, 1.0, 1.0]` is fully white) and the last item controls the
-    /// strength of the ambient light.
+    /// The format is RGB (`[1.0, 1.0, 1.0]` is fully white) and the last item is unused.
     pub color: [f32; 4],
+
+    /// The ambient, diffuse and specular strengths followed by the shininess exponent.
+    pub material: [f32; 4],
+}
+
+impl LightUniform {
+    /// Creates a [LightUniform] from the lighting part of the [RenderSettings].
+    ///
+    /// Arguments:
+    ///
+    /// * `settings`: The settings from which the light is taken.
+    /// * `eye`: The eye of the camera, used if the light is a headlight.
+    pub fn from_settings(settings: &RenderSettings, eye: [f32; 3]) -> Self {
+        let position = match settings.lighting {
+            Lighting::Headlight => eye,
+            Lighting::World { position } => position,
+        };
+        let [r, g, b] = settings.light_color;
+
+        Self {
+            position: [position[0], position[1], position[2], 1.0],
+            color: [r, g, b, 1.0],
+            material: [
+                settings.ambient,
+                settings.diffuse,
+                settings.specular,
+                settings.shininess,
+            ],
+        }
+    }
 }
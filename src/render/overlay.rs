@@ -0,0 +1,444 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{statistic::Statistic, volume::Volume},
+    render::vertex::Vertex,
+};
+
+/// The scalar of vertices without overlay data. These vertices keep their base color.
+pub const NO_SCALAR: f32 = f32::MIN;
+
+/// Every scalar below this value is treated as [NO_SCALAR].
+pub(crate) const NO_SCALAR_LIMIT: f32 = -1.0e38;
+
+/// The number of entries of the lookup tables created from a [Colormap].
+pub const COLORMAP_SIZE: usize = 256;
+
+/// The colormaps that map overlay scalars to colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    /// Black to white, e.g. for curvature.
+    Gray,
+
+    /// Black over red and yellow to white.
+    Hot,
+
+    /// Cyan to magenta.
+    Cool,
+
+    /// Blue over cyan, yellow and red.
+    Jet,
+
+    /// Red to yellow, e.g. for positive statistics.
+    RedYellow,
+
+    /// Blue to light blue, e.g. for negative statistics.
+    BlueLightblue,
+
+    /// Blue over white to red, e.g. for correlations with a range centered on zero.
+    BlueRed,
+}
+
+impl Colormap {
+    /// Returns the RGB color of the colormap at a position.
+    ///
+    /// Arguments:
+    ///
+    /// * `t`: The position in the colormap between `0.0` and `1.0`.
+    pub fn color(&self, t: f32) -> [f32; 3] {
+        let t = t.clamp(0.0, 1.0);
+        let c = |v: f32| v.clamp(0.0, 1.0);
+        match self {
+            Self::Gray => [t, t, t],
+            Self::Hot => [c(3.0 * t), c(3.0 * t - 1.0), c(3.0 * t - 2.0)],
+            Self::Cool => [t, 1.0 - t, 1.0],
+            Self::Jet => [
+                c(1.5 - (4.0 * t - 3.0).abs()),
+                c(1.5 - (4.0 * t - 2.0).abs()),
+                c(1.5 - (4.0 * t - 1.0).abs()),
+            ],
+            Self::RedYellow => [1.0, t, 0.0],
+            Self::BlueLightblue => [0.0, t, 1.0],
+            Self::BlueRed => [c(2.0 * t), 1.0 - (2.0 * t - 1.0).abs(), c(2.0 - 2.0 * t)],
+        }
+    }
+
+    /// Returns the colormap as a lookup table of sRGB colors with [COLORMAP_SIZE] entries.
+    pub fn lookup_table(&self) -> Vec<[u8; 4]> {
+        (0..COLORMAP_SIZE)
+            .map(|i| {
+                let [r, g, b] = self.color(i as f32 / (COLORMAP_SIZE - 1) as f32);
+                [
+                    (r * 255.0).round() as u8,
+                    (g * 255.0).round() as u8,
+                    (b * 255.0).round() as u8,
+                    255,
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Samples a volume at the vertices of a mesh, e.g. to color an isosurface with a statistical
+/// map.
+///
+/// Vertices outside of the volume get [NO_SCALAR] and keep their base color.
+///
+/// Arguments:
+///
+/// * `volume`: The volume to sample with trilinear interpolation.
+/// * `vertices`: The vertices of the mesh in world coordinates in millimetres.
+pub fn get_volume_scalars(volume: &Volume, vertices: &[Vertex]) -> Vec<f32> {
+    let to_voxel = volume.affine.inverse();
+    let max = Vec3::new(
+        volume.dims[0] as f32,
+        volume.dims[1] as f32,
+        volume.dims[2] as f32,
+    ) - 0.5;
+    vertices
+        .iter()
+        .map(|vertex| {
+            let position = to_voxel.transform_point3(Vec3::from(vertex.position));
+            if position.cmpge(Vec3::splat(-0.5)).all() && position.cmple(max).all() {
+                volume.sample_trilinear(position)
+            } else {
+                NO_SCALAR
+            }
+        })
+        .collect()
+}
+
+/// The settings that control how per-vertex scalars are colored.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    /// Whether the overlay is shown instead of the base color.
+    pub enabled: bool,
+
+    /// The colormap applied to the scalars.
+    pub colormap: Colormap,
+
+    /// The scalar mapped to the start of the colormap.
+    pub min: f32,
+
+    /// The scalar mapped to the end of the colormap.
+    pub max: f32,
+
+    /// Vertices with scalars below the threshold keep their base color.
+    pub threshold: Option<f32>,
+
+    /// Whether the magnitude of the scalars is compared with the threshold, so that negative
+    /// scalars above `-threshold` keep their base color as well.
+    pub absolute_threshold: bool,
+
+    /// The colormap of negative scalars, e.g. for statistical maps.
+    ///
+    /// If set, `colormap` only colors positive scalars and both colormaps span the magnitudes
+    /// from `min` to `max`. The threshold applies to the magnitude of the scalars.
+    pub negative_colormap: Option<Colormap>,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            colormap: Colormap::Hot,
+            min: 0.0,
+            max: 1.0,
+            threshold: None,
+            absolute_threshold: false,
+            negative_colormap: None,
+        }
+    }
+}
+
+impl OverlaySettings {
+    /// Creates settings whose range spans the given scalars.
+    ///
+    /// Arguments:
+    ///
+    /// * `colormap`: The colormap applied to the scalars.
+    /// * `scalars`: The scalars of the overlay, [NO_SCALAR] entries are ignored.
+    pub fn fit(colormap: Colormap, scalars: &[f32]) -> Self {
+        let (min, max) = scalars
+            .iter()
+            .filter(|s| s.is_finite() && **s > NO_SCALAR_LIMIT)
+            .fold((f32::MAX, f32::MIN), |(min, max), &s| {
+                (min.min(s), max.max(s))
+            });
+        let (min, max) = if min <= max { (min, max) } else { (0.0, 1.0) };
+
+        Self {
+            colormap,
+            min,
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Creates settings for signed scalars with a range that is centered on zero and a threshold
+    /// on their magnitude, e.g. for correlations.
+    ///
+    /// Arguments:
+    ///
+    /// * `colormap`: The colormap applied to the scalars, e.g. [Colormap::BlueRed].
+    /// * `limit`: The largest magnitude, which is mapped to the ends of the colormap.
+    /// * `threshold`: The smallest magnitude that is shown.
+    pub fn symmetric(colormap: Colormap, limit: f32, threshold: f32) -> Self {
+        Self {
+            colormap,
+            min: -limit,
+            max: limit,
+            threshold: Some(threshold),
+            absolute_threshold: true,
+            ..Default::default()
+        }
+    }
+
+    /// Creates settings that color positive and negative scalars with separate colormaps, e.g.
+    /// for statistical maps.
+    ///
+    /// Arguments:
+    ///
+    /// * `positive`: The colormap of positive scalars.
+    /// * `negative`: The colormap of negative scalars.
+    /// * `threshold`: The smallest magnitude that is shown, mapped to the start of both
+    ///   colormaps.
+    /// * `max`: The magnitude that is mapped to the end of both colormaps.
+    pub fn dual(positive: Colormap, negative: Colormap, threshold: f32, max: f32) -> Self {
+        Self {
+            colormap: positive,
+            negative_colormap: Some(negative),
+            min: threshold,
+            max: max.max(threshold),
+            threshold: Some(threshold),
+            ..Default::default()
+        }
+    }
+
+    /// Creates settings for a statistical map, whose positive values are colored red to yellow
+    /// and negative values blue to light blue.
+    ///
+    /// Statistics without negative values, like F-statistics, only use the first colormap.
+    ///
+    /// Arguments:
+    ///
+    /// * `statistic`: The statistic of the map.
+    /// * `threshold`: The smallest magnitude of the statistic that is shown.
+    /// * `max`: The largest magnitude of the statistic in the map.
+    pub fn statistic(statistic: &Statistic, threshold: f32, max: f32) -> Self {
+        if statistic.is_signed() {
+            return Self::dual(Colormap::RedYellow, Colormap::BlueLightblue, threshold, max);
+        }
+        Self {
+            colormap: Colormap::RedYellow,
+            min: threshold,
+            max: max.max(threshold),
+            threshold: Some(threshold),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the color the overlay gives a vertex, or [None] if it keeps its base color.
+    ///
+    /// This matches the colors of the shader, e.g. to export the overlay with a mesh.
+    ///
+    /// Arguments:
+    ///
+    /// * `scalar`: The scalar of the vertex.
+    pub fn color(&self, scalar: f32) -> Option<[f32; 3]> {
+        if !self.enabled || !scalar.is_finite() || scalar <= NO_SCALAR_LIMIT {
+            return None;
+        }
+        let dual = self.negative_colormap.is_some();
+        let magnitude = if self.absolute_threshold || dual {
+            scalar.abs()
+        } else {
+            scalar
+        };
+        if magnitude < self.threshold.unwrap_or(NO_SCALAR_LIMIT) {
+            return None;
+        }
+        let value = if dual { magnitude } else { scalar };
+        let t = (value - self.min) / (self.max - self.min).max(1.0e-6);
+        let colormap = match self.negative_colormap {
+            Some(negative) if scalar < 0.0 => negative,
+            _ => self.colormap,
+        };
+        Some(colormap.color(t))
+    }
+
+    /// Returns the sRGB colors of vertices with the overlay applied to their scalars.
+    ///
+    /// Arguments:
+    ///
+    /// * `scalars`: The scalars of the vertices, [NO_SCALAR] entries get the base color.
+    /// * `base_color`: The RGBA color of vertices that the overlay does not color.
+    pub fn vertex_colors(&self, scalars: &[f32], base_color: [f32; 4]) -> Vec<[u8; 4]> {
+        let to_byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        scalars
+            .iter()
+            .map(|&scalar| match self.color(scalar) {
+                Some([r, g, b]) => [to_byte(r), to_byte(g), to_byte(b), 255],
+                None => base_color.map(to_byte),
+            })
+            .collect()
+    }
+}
+
+/// The overlay data that is passed to the shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct OverlayUniform {
+    /// The minimum, maximum and threshold of the scalars followed by `1.0` if the overlay is enabled.
+    range: [f32; 4],
+
+    /// `1.0` if the threshold applies to the magnitude of the scalars and `1.0` if negative
+    /// scalars have their own colormap, the rest is padding.
+    options: [f32; 4],
+}
+
+impl From<&OverlaySettings> for OverlayUniform {
+    fn from(settings: &OverlaySettings) -> Self {
+        Self {
+            range: [
+                settings.min,
+                settings.max,
+                settings.threshold.unwrap_or(NO_SCALAR_LIMIT),
+                if settings.enabled { 1.0 } else { 0.0 },
+            ],
+            options: [
+                if settings.absolute_threshold {
+                    1.0
+                } else {
+                    0.0
+                },
+                if settings.negative_colormap.is_some() {
+                    1.0
+                } else {
+                    0.0
+                },
+                0.0,
+                0.0,
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+
+    use super::{
+        get_volume_scalars, Colormap, OverlaySettings, OverlayUniform, COLORMAP_SIZE, NO_SCALAR,
+        NO_SCALAR_LIMIT,
+    };
+    use crate::{data::volume::tests::test_volume, render::vertex::Vertex};
+
+    #[test]
+    fn colormap_lookup_tables() {
+        let gray = Colormap::Gray.lookup_table();
+        assert_eq!(gray.len(), COLORMAP_SIZE);
+        assert_eq!(gray[0], [0, 0, 0, 255]);
+        assert_eq!(gray[128], [128, 128, 128, 255]);
+        assert_eq!(gray[255], [255; 4]);
+
+        let hot = Colormap::Hot.lookup_table();
+        assert_eq!(hot[0], [0, 0, 0, 255]);
+        assert_eq!(hot[85], [255, 0, 0, 255]);
+        assert_eq!(hot[255], [255; 4]);
+        // The diverging colormap is white at its center.
+        assert_eq!(Colormap::BlueRed.color(0.5), [1.0, 1.0, 1.0]);
+        assert_eq!(Colormap::BlueRed.color(-1.0), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn threshold() {
+        let settings = OverlaySettings {
+            colormap: Colormap::Gray,
+            min: 0.0,
+            max: 4.0,
+            threshold: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(settings.color(1.0), None);
+        assert_eq!(settings.color(3.0), Some([0.75; 3]));
+        assert_eq!(settings.color(-3.0), None);
+        assert_eq!(settings.color(NO_SCALAR), None);
+        assert_eq!(settings.color(f32::NAN), None);
+
+        let absolute = OverlaySettings {
+            absolute_threshold: true,
+            ..settings
+        };
+        assert_eq!(absolute.color(-1.0), None);
+        assert_eq!(absolute.color(-3.0), Some([0.0; 3]));
+
+        let disabled = OverlaySettings {
+            enabled: false,
+            ..settings
+        };
+        assert_eq!(disabled.color(3.0), None);
+    }
+
+    #[test]
+    fn negative_colormap() {
+        let settings =
+            OverlaySettings::dual(Colormap::RedYellow, Colormap::BlueLightblue, 2.0, 4.0);
+        assert_eq!(settings.color(3.0), Some([1.0, 0.5, 0.0]));
+        assert_eq!(settings.color(-3.0), Some([0.0, 0.5, 1.0]));
+        assert_eq!(settings.color(-1.0), None);
+
+        let colors = settings.vertex_colors(&[4.0, -1.0, NO_SCALAR], [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(
+            colors,
+            [
+                [255, 255, 0, 255],
+                [128, 128, 128, 255],
+                [128, 128, 128, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn settings_are_packed_for_the_shader() {
+        let uniform =
+            OverlayUniform::from(&OverlaySettings::symmetric(Colormap::BlueRed, 1.0, 0.3));
+        assert_eq!(uniform.range, [-1.0, 1.0, 0.3, 1.0]);
+        assert_eq!(uniform.options, [1.0, 0.0, 0.0, 0.0]);
+
+        let settings = OverlaySettings {
+            enabled: false,
+            ..OverlaySettings::dual(Colormap::RedYellow, Colormap::BlueLightblue, 2.0, 1.0)
+        };
+        let uniform = OverlayUniform::from(&settings);
+        // The range is never inverted.
+        assert_eq!(uniform.range, [2.0, 2.0, 2.0, 0.0]);
+        assert_eq!(uniform.options, [0.0, 1.0, 0.0, 0.0]);
+
+        let uniform = OverlayUniform::from(&OverlaySettings::default());
+        assert_eq!(uniform.range[2], NO_SCALAR_LIMIT);
+    }
+
+    #[test]
+    fn fit_ignores_vertices_without_scalars() {
+        let settings = OverlaySettings::fit(Colormap::Jet, &[NO_SCALAR, -2.0, 5.0, f32::NAN]);
+        assert_eq!((settings.min, settings.max), (-2.0, 5.0));
+        let settings = OverlaySettings::fit(Colormap::Jet, &[NO_SCALAR]);
+        assert_eq!((settings.min, settings.max), (0.0, 1.0));
+    }
+
+    #[test]
+    fn volume_scalars_at_vertices() {
+        let volume = test_volume([2, 1, 1], Mat4::from_scale(Vec3::splat(2.0)), &[1.0, 3.0]);
+        let vertex = |x: f32| Vertex {
+            position: [x, 0.0, 0.0],
+            tex_coords: [0.0; 2],
+            normal: [0.0; 3],
+        };
+        let scalars = get_volume_scalars(&volume, &[vertex(0.0), vertex(1.0), vertex(3.5)]);
+        assert_eq!(scalars, [1.0, 2.0, NO_SCALAR]);
+    }
+}
@@ -0,0 +1,508 @@
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::render::{
+    error::RenderError,
+    vertex::{Instance, Vertex},
+};
+
+/// Identifies an object that was added to the scene of a [crate::render::state::State].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectId(pub(crate) u32);
+
+/// The appearance of a scene object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// The RGBA color of the object. It is multiplied with the texture if `textured` is set.
+    /// Objects with an alpha below one are blended over the opaque objects of the scene.
+    pub color: [f32; 4],
+
+    /// Whether the diffuse texture is sampled with the texture coordinates of the vertices.
+    pub textured: bool,
+
+    /// Whether the object is shaded by the light. Unlit objects show their plain color.
+    pub lit: bool,
+
+    /// Whether the volume layers of the state are drawn on the object, sampled at the world
+    /// position of every fragment, e.g. on slice planes or on surfaces.
+    pub layered: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 1.0],
+            textured: false,
+            lit: true,
+            layered: false,
+        }
+    }
+}
+
+impl Material {
+    /// Creates a lit material with a plain RGBA color.
+    ///
+    /// Arguments:
+    ///
+    /// * `color`: The RGBA color of the object.
+    pub fn from_color(color: [f32; 4]) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+}
+
+/// An object of the scene with its geometry, transform, material and visibility.
+#[derive(Debug, Clone)]
+pub struct SceneObject {
+    /// A name to identify the object, e.g. the file it was loaded from.
+    pub name: String,
+
+    /// The transform from the coordinates of the vertices to world space.
+    pub transform: Mat4,
+
+    /// The appearance of the object.
+    pub material: Material,
+
+    /// Whether the object is drawn.
+    pub visible: bool,
+
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    scalars: Option<Vec<f32>>,
+    instances: Option<Vec<Instance>>,
+
+    /// Whether the geometry changed since it was uploaded.
+    pub(crate) dirty: bool,
+}
+
+impl SceneObject {
+    /// Creates a visible object with an identity transform and the default material.
+    ///
+    /// Arguments:
+    ///
+    /// * `name`: A name to identify the object.
+    /// * `vertices`: The vertices of the mesh.
+    /// * `indices`: The indices of the triangle list.
+    pub fn new(name: &str, vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self {
+            name: name.to_string(),
+            transform: Mat4::IDENTITY,
+            material: Material::default(),
+            visible: true,
+            vertices,
+            indices,
+            scalars: None,
+            instances: None,
+            dirty: true,
+        }
+    }
+
+    /// Returns the vertices of the mesh.
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    /// Returns the indices of the triangle list.
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Returns the overlay scalars, one per vertex.
+    pub fn scalars(&self) -> Option<&[f32]> {
+        self.scalars.as_deref()
+    }
+
+    /// Returns the instances the mesh is drawn with.
+    pub fn instances(&self) -> Option<&[Instance]> {
+        self.instances.as_deref()
+    }
+
+    /// Draws the mesh once for every instance instead of once at the origin.
+    ///
+    /// The instances are placed within the coordinates of the object, before its transform.
+    ///
+    /// Arguments:
+    ///
+    /// * `instances`: The instances of the mesh, or [None] to draw the mesh once.
+    pub fn set_instances(&mut self, instances: Option<Vec<Instance>>) {
+        self.instances = instances;
+        self.dirty = true;
+    }
+
+    /// Replaces the mesh of the object and removes its overlay scalars and instances.
+    ///
+    /// Arguments:
+    ///
+    /// * `vertices`: The vertices of the mesh.
+    /// * `indices`: The indices of the triangle list.
+    pub fn set_geometry(&mut self, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        self.vertices = vertices;
+        self.indices = indices;
+        self.scalars = None;
+        self.instances = None;
+        self.dirty = true;
+    }
+
+    /// Sets the per-vertex scalars that are colored by the overlay.
+    ///
+    /// Arguments:
+    ///
+    /// * `scalars`: One scalar per vertex, or [None] to remove the overlay.
+    pub fn set_scalars(&mut self, scalars: Option<Vec<f32>>) -> Result<(), RenderError> {
+        if let Some(scalars) = &scalars {
+            if scalars.len() != self.vertices.len() {
+                return Err(RenderError::ScalarCount {
+                    expected: self.vertices.len(),
+                    actual: scalars.len(),
+                });
+            }
+        }
+        self.scalars = scalars;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the world space bounding box of the object, or [None] if it has no vertices.
+    pub fn bounding_box(&self) -> Option<(Vec3, Vec3)> {
+        self.world_transforms()
+            .iter()
+            .flat_map(|transform| {
+                self.vertices
+                    .iter()
+                    .map(|v| transform.transform_point3(Vec3::from(v.position)))
+            })
+            .fold(None, |bounds, p| match bounds {
+                Some((min, max)) => Some((p.min(min), p.max(max))),
+                None => Some((p, p)),
+            })
+    }
+
+    /// Returns the distance along a ray to the closest triangle of the object, or [None] if the
+    /// ray misses it. Both sides of a triangle are hit.
+    ///
+    /// Arguments:
+    ///
+    /// * `origin`: The start of the ray in world coordinates.
+    /// * `direction`: The normalized direction of the ray in world coordinates.
+    pub fn intersect_ray(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        self.world_transforms()
+            .iter()
+            .filter_map(|transform| {
+                // The ray is moved into the coordinates of the vertices, which keeps the
+                // distances along it.
+                let inverse = transform.inverse();
+                let origin = inverse.transform_point3(origin);
+                let direction = inverse.transform_vector3(direction);
+                self.indices
+                    .chunks_exact(3)
+                    .filter_map(|triangle| {
+                        let [a, b, c] = [0, 1, 2]
+                            .map(|i| Vec3::from(self.vertices[triangle[i] as usize].position));
+                        intersect_triangle(origin, direction, a, b, c)
+                    })
+                    .min_by(f32::total_cmp)
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    /// Returns the transforms from the coordinates of the vertices to world space, one per
+    /// instance.
+    fn world_transforms(&self) -> Vec<Mat4> {
+        match &self.instances {
+            Some(instances) => instances
+                .iter()
+                .map(|instance| self.transform * instance.transform())
+                .collect(),
+            None => vec![self.transform],
+        }
+    }
+}
+
+/// An object of a [Scene] together with its id and data such as its uploaded geometry.
+#[derive(Debug)]
+pub(crate) struct SceneEntry<T> {
+    pub(crate) id: ObjectId,
+    pub(crate) object: SceneObject,
+    pub(crate) data: T,
+}
+
+/// The objects of a scene in drawing order.
+///
+/// Every object carries data of its renderer, e.g. the GPU buffers of its geometry, so the
+/// bookkeeping of the objects does not need a device.
+#[derive(Debug)]
+pub(crate) struct Scene<T> {
+    entries: Vec<SceneEntry<T>>,
+    next_id: u32,
+}
+
+impl<T> Scene<T> {
+    /// Creates an empty scene.
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Adds an object after the others and returns its id, which is never reused.
+    ///
+    /// Arguments:
+    ///
+    /// * `object`: The object to add.
+    /// * `data`: The data of the renderer for the object.
+    pub(crate) fn add(&mut self, object: SceneObject, data: T) -> ObjectId {
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+        self.entries.push(SceneEntry { id, object, data });
+        id
+    }
+
+    /// Removes an object and returns it, or [None] if there is no such object.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The object to remove.
+    pub(crate) fn remove(&mut self, id: ObjectId) -> Option<SceneObject> {
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        Some(self.entries.remove(index).object)
+    }
+
+    /// Returns an object.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The object to return.
+    pub(crate) fn get(&self, id: ObjectId) -> Option<&SceneObject> {
+        self.entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| &entry.object)
+    }
+
+    /// Returns an object for modification.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The object to return.
+    pub(crate) fn get_mut(&mut self, id: ObjectId) -> Option<&mut SceneObject> {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.id == id)
+            .map(|entry| &mut entry.object)
+    }
+
+    /// Returns the number of objects.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the objects in drawing order.
+    pub(crate) fn entries(&self) -> std::slice::Iter<'_, SceneEntry<T>> {
+        self.entries.iter()
+    }
+
+    /// Returns the objects in drawing order for modification.
+    pub(crate) fn entries_mut(&mut self) -> std::slice::IterMut<'_, SceneEntry<T>> {
+        self.entries.iter_mut()
+    }
+
+    /// Returns the world space bounding box of some objects, or [None] if they have no
+    /// vertices.
+    ///
+    /// Arguments:
+    ///
+    /// * `ids`: The objects to enclose, all visible objects if it is empty.
+    pub(crate) fn bounding_box(&self, ids: &[ObjectId]) -> Option<(Vec3, Vec3)> {
+        self.entries
+            .iter()
+            .filter(|entry| {
+                if ids.is_empty() {
+                    entry.object.visible
+                } else {
+                    ids.contains(&entry.id)
+                }
+            })
+            .filter_map(|entry| entry.object.bounding_box())
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+    }
+
+    /// Returns the distance along a ray to the closest visible object, or [None] if the ray
+    /// misses all of them.
+    ///
+    /// Arguments:
+    ///
+    /// * `origin`: The start of the ray in world coordinates.
+    /// * `direction`: The normalized direction of the ray in world coordinates.
+    pub(crate) fn intersect_ray(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.object.visible)
+            .filter_map(|entry| entry.object.intersect_ray(origin, direction))
+            .min_by(f32::total_cmp)
+    }
+}
+
+/// Returns the distance along a ray to a triangle with the Möller-Trumbore algorithm, or
+/// [None] if the ray misses it.
+fn intersect_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let (edge_ab, edge_ac) = (b - a, c - a);
+    let p = direction.cross(edge_ac);
+    let determinant = edge_ab.dot(p);
+    // Rays parallel to the triangle miss it.
+    let scale = direction.length() * edge_ab.length() * edge_ac.length();
+    if determinant.abs() <= f32::EPSILON * scale {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(edge_ab);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge_ac.dot(q) * inverse_determinant;
+    (t >= 0.0).then_some(t)
+}
+
+/// The object data that is passed to the shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ObjectUniform {
+    /// The transform from the coordinates of the vertices to world space.
+    model: [[f32; 4]; 4],
+
+    /// The inverse transpose of the model matrix, which transforms the normals.
+    normal: [[f32; 4]; 4],
+
+    /// The RGBA color of the material.
+    color: [f32; 4],
+
+    /// `1.0` if the material is textured, `1.0` if it is lit and `1.0` if it shows the volume
+    /// layers, the rest is unused.
+    flags: [f32; 4],
+}
+
+impl From<&SceneObject> for ObjectUniform {
+    fn from(object: &SceneObject) -> Self {
+        let normal = Mat4::from_mat3(Mat3::from_mat4(object.transform).inverse().transpose());
+        let flag = |enabled: bool| if enabled { 1.0 } else { 0.0 };
+
+        Self {
+            model: object.transform.to_cols_array_2d(),
+            normal: normal.to_cols_array_2d(),
+            color: object.material.color,
+            flags: [
+                flag(object.material.textured),
+                flag(object.material.lit),
+                flag(object.material.layered),
+                0.0,
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjectId, Scene, SceneObject};
+    use crate::render::{error::RenderError, vertex::Vertex};
+    use glam::{Mat4, Vec3};
+
+    /// Returns a triangle in the z = 0 plane.
+    fn triangle() -> SceneObject {
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .map(|position| Vertex {
+                position,
+                tex_coords: [0.0; 2],
+                normal: [0.0, 0.0, 1.0],
+            })
+            .to_vec();
+        SceneObject::new("triangle", vertices, vec![0, 1, 2])
+    }
+
+    #[test]
+    fn one_scalar_per_vertex() {
+        let mut object = triangle();
+        assert!(matches!(
+            object.set_scalars(Some(vec![0.0; 4])),
+            Err(RenderError::ScalarCount {
+                expected: 3,
+                actual: 4
+            })
+        ));
+        assert_eq!(object.scalars(), None);
+
+        object.set_scalars(Some(vec![1.0, 2.0, 3.0])).unwrap();
+        assert_eq!(object.scalars(), Some(&[1.0, 2.0, 3.0][..]));
+        // New geometry drops the scalars of the old vertices.
+        let vertices = object.vertices().to_vec();
+        object.set_geometry(vertices, vec![0, 2, 1]);
+        assert_eq!(object.scalars(), None);
+    }
+
+    /// Returns the triangle moved along x.
+    fn moved_triangle(x: f32) -> SceneObject {
+        let mut object = triangle();
+        object.transform = Mat4::from_translation(Vec3::new(x, 0.0, 0.0));
+        object
+    }
+
+    #[test]
+    fn objects_keep_their_ids_and_order() {
+        let mut scene = Scene::new();
+        let a = scene.add(moved_triangle(0.0), 'a');
+        let b = scene.add(moved_triangle(2.0), 'b');
+        let c = scene.add(moved_triangle(4.0), 'c');
+        assert_eq!(scene.len(), 3);
+
+        assert!(scene.remove(b).is_some());
+        assert!(scene.remove(b).is_none());
+        assert!(scene.get(b).is_none());
+        // Ids are not reused after a removal.
+        let d = scene.add(moved_triangle(6.0), 'd');
+        assert!(![a, b, c].contains(&d));
+
+        let order: Vec<(ObjectId, char)> = scene
+            .entries()
+            .map(|entry| (entry.id, entry.data))
+            .collect();
+        assert_eq!(order, [(a, 'a'), (c, 'c'), (d, 'd')]);
+
+        scene.get_mut(c).unwrap().name = "renamed".to_string();
+        assert_eq!(scene.get(c).unwrap().name, "renamed");
+    }
+
+    #[test]
+    fn hidden_objects_are_neither_framed_nor_picked() {
+        let mut scene = Scene::new();
+        let near = scene.add(moved_triangle(0.0), ());
+        let far = scene.add(moved_triangle(4.0), ());
+
+        let (min, max) = scene.bounding_box(&[]).unwrap();
+        assert_eq!((min, max), (Vec3::ZERO, Vec3::new(5.0, 1.0, 0.0)));
+        let origin = Vec3::new(0.25, 0.25, 1.0);
+        assert_eq!(scene.intersect_ray(origin, Vec3::NEG_Z), Some(1.0));
+
+        scene.get_mut(near).unwrap().visible = false;
+        let (min, max) = scene.bounding_box(&[]).unwrap();
+        assert_eq!(
+            (min, max),
+            (Vec3::new(4.0, 0.0, 0.0), Vec3::new(5.0, 1.0, 0.0))
+        );
+        assert_eq!(scene.intersect_ray(origin, Vec3::NEG_Z), None);
+        let origin = Vec3::new(4.25, 0.25, 2.0);
+        assert_eq!(scene.intersect_ray(origin, Vec3::NEG_Z), Some(2.0));
+
+        // Listed objects are framed even when hidden.
+        let (min, max) = scene.bounding_box(&[near]).unwrap();
+        assert_eq!((min, max), (Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0)));
+
+        scene.get_mut(far).unwrap().visible = false;
+        assert_eq!(scene.bounding_box(&[]), None);
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+
+/// The background that is drawn behind the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Background {
+    /// A single RGBA color.
+    Color { color: [f32; 4] },
+
+    /// A vertical gradient between two RGBA colors.
+    Gradient { top: [f32; 4], bottom: [f32; 4] },
+}
+
+/// Where the light illuminating the scene is placed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Lighting {
+    /// The light follows the eye of the camera.
+    Headlight,
+
+    /// The light is fixed at a position in world space.
+    World { position: [f32; 3] },
+}
+
+/// The settings that control the appearance of the rendered scene.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// The background behind the scene.
+    pub background: Background,
+
+    /// The placement of the light.
+    pub lighting: Lighting,
+
+    /// The RGB color of the light.
+    pub light_color: [f32; 3],
+
+    /// The strength of the ambient light.
+    pub ambient: f32,
+
+    /// The strength of the diffuse light.
+    pub diffuse: f32,
+
+    /// The strength of the specular highlights.
+    pub specular: f32,
+
+    /// The Blinn-Phong exponent controlling the size of the specular highlights.
+    pub shininess: f32,
+
+    /// Whether the axes with anatomical labels are drawn into the lower left corner.
+    pub orientation_gizmo: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            background: Background::Color {
+                color: [0.1, 0.2, 0.3, 1.0],
+            },
+            lighting: Lighting::Headlight,
+            light_color: [1.0, 1.0, 1.0],
+            ambient: 0.1,
+            diffuse: 1.0,
+            specular: 1.0,
+            shininess: 32.0,
+            orientation_gizmo: true,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Serializes the settings to a JSON string.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Deserializes settings from a JSON string.
+    ///
+    /// Missing fields are taken from [RenderSettings::default].
+    ///
+    /// Arguments:
+    ///
+    /// * `json`: The JSON representation of the settings.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Returns the color the render target is cleared with.
+    pub(crate) fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = match self.background {
+            Background::Color { color } => color,
+            Background::Gradient { bottom, .. } => bottom,
+        };
+        wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        }
+    }
+}
+
+/// The background data that is passed to the background shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct BackgroundUniform {
+    /// The RGBA color at the top of the viewport.
+    pub top: [f32; 4],
+
+    /// The RGBA color at the bottom of the viewport.
+    pub bottom: [f32; 4],
+}
+
+impl From<&Background> for BackgroundUniform {
+    fn from(background: &Background) -> Self {
+        match *background {
+            Background::Color { color } => Self {
+                top: color,
+                bottom: color,
+            },
+            Background::Gradient { top, bottom } => Self { top, bottom },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Background, BackgroundUniform, Lighting, RenderSettings};
+
+    #[test]
+    fn json_round_trip() {
+        let settings = RenderSettings {
+            background: Background::Gradient {
+                top: [0.0, 0.0, 0.5, 1.0],
+                bottom: [0.5, 0.5, 0.5, 1.0],
+            },
+            lighting: Lighting::World {
+                position: [1.0, -2.0, 3.0],
+            },
+            light_color: [1.0, 0.9, 0.8],
+            ambient: 0.25,
+            diffuse: 0.75,
+            specular: 0.5,
+            shininess: 8.0,
+            orientation_gizmo: false,
+        };
+        let json = settings.to_json().unwrap();
+        assert!(json.contains(r#""type":"gradient""#));
+        assert!(json.contains(r#""type":"world""#));
+        assert_eq!(RenderSettings::from_json(&json).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_are_defaults_and_unknown_fields_are_ignored() {
+        assert_eq!(
+            RenderSettings::from_json("{}").unwrap(),
+            RenderSettings::default()
+        );
+
+        let settings =
+            RenderSettings::from_json(r#"{"ambient": 0.5, "msaa": true, "theme": "dark"}"#)
+                .unwrap();
+        assert_eq!(
+            settings,
+            RenderSettings {
+                ambient: 0.5,
+                ..Default::default()
+            }
+        );
+
+        // Unknown kinds of background or lighting and values of the wrong type are errors.
+        assert!(RenderSettings::from_json(r#"{"background": {"type": "image"}}"#).is_err());
+        assert!(RenderSettings::from_json(r#"{"lighting": {"type": "world"}}"#).is_err());
+        assert!(RenderSettings::from_json(r#"{"shininess": "high"}"#).is_err());
+    }
+
+    #[test]
+    fn background_colors() {
+        let (top, bottom) = ([0.0, 0.0, 0.5, 1.0], [0.5, 0.5, 0.5, 1.0]);
+        let gradient = Background::Gradient { top, bottom };
+        let uniform = BackgroundUniform::from(&gradient);
+        assert_eq!((uniform.top, uniform.bottom), (top, bottom));
+        let settings = RenderSettings {
+            background: gradient,
+            ..Default::default()
+        };
+        assert_eq!(settings.clear_color().g, 0.5);
+
+        let uniform = BackgroundUniform::from(&Background::Color { color: top });
+        assert_eq!((uniform.top, uniform.bottom), (top, top));
+    }
+}
@@ -8,38 +8,90 @@ struct Camera {
 @group(1) @binding(0)
 var<uniform> camera: Camera;
 
+struct Object {
+    model: mat4x4<f32>,
+    // The inverse transpose of the model matrix.
+    normal: mat4x4<f32>,
+    color: vec4<f32>,
+    // 1.0 in x if the material is textured, 1.0 in y if it is lit and 1.0 in z if it shows the
+    // volume layers.
+    flags: vec4<f32>,
+};
+@group(1) @binding(1)
+var<uniform> object: Object;
+
 struct Light {
     position: vec4<f32>,
     color: vec4<f32>,
+    // Ambient, diffuse and specular strength followed by the shininess.
+    material: vec4<f32>,
 };
 @group(2) @binding(0)
 var<uniform> light: Light;
 
+struct Overlay {
+    // The minimum, maximum and threshold of the scalars followed by 1.0 if the overlay is enabled.
+    range: vec4<f32>,
+    // 1.0 if the threshold applies to the magnitude of the scalars and 1.0 if negative scalars
+    // have their own colormap in the second row of the lookup table, the rest is padding.
+    options: vec4<f32>,
+};
+@group(3) @binding(0)
+var<uniform> overlay: Overlay;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) normal: vec3<f32>,
+    // The overlay scalar premultiplied by the weight, 1.0 for vertices with data and 0.0 for
+    // vertices without.
+    @location(3) scalar: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(4) position: vec3<f32>,
+    @location(5) scale: vec3<f32>,
+    // The rotation as a quaternion.
+    @location(6) rotation: vec4<f32>,
+    @location(7) color: vec4<f32>,
 };
 
+// Rotates a vector by a unit quaternion.
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) world_normal: vec3<f32>,
     @location(2) world_position: vec3<f32>,
     @location(3) camera_view_pos: vec4<f32>,
+    @location(4) scalar: vec2<f32>,
+    @location(5) instance_color: vec4<f32>,
+    // The clip position, which is also needed for the curtain in the fragment shader.
+    @location(6) clip: vec4<f32>,
 };
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.world_normal = model.normal;
-    var world_position: vec4<f32> = vec4<f32>(model.position, 1.0);
+    // The instance is scaled, rotated and translated before the transform of the object.
+    let position = rotate(instance.rotation, model.position * instance.scale) + instance.position;
+    let normal = rotate(instance.rotation, model.normal / instance.scale);
+    out.world_normal = normalize((object.normal * vec4<f32>(normal, 0.0)).xyz);
+    var world_position: vec4<f32> = object.model * vec4<f32>(position, 1.0);
     out.world_position = world_position.xyz;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.clip = out.clip_position;
     out.camera_view_pos = camera.view_pos;
+    out.scalar = model.scalar;
+    out.instance_color = instance.color;
     return out;
 }
 
@@ -49,17 +101,221 @@ fn vs_main(
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(3) @binding(1)
+var t_colormap: texture_2d<f32>;
+@group(3) @binding(2)
+var s_colormap: sampler;
+
+struct Layer {
+    // The transform from world coordinates to the voxel coordinates of the layer.
+    world_to_voxel: mat4x4<f32>,
+    // The number of voxels along the x, y and z axes followed by the opacity.
+    dims: vec4<f32>,
+    // The intensities mapped to the start and end of the colormap and the threshold followed by
+    // 1.0 if the layer is visible.
+    window: vec4<f32>,
+    // The blend mode, 1.0 if the layer is a label map and 1.0 if the threshold applies to the
+    // magnitude of the intensities, the rest is padding.
+    options: vec4<f32>,
+};
+
+struct Layers {
+    // The layers from the bottom to the top.
+    layers: array<Layer, 4>,
+    // The number of layers, the rest is padding.
+    count: vec4<f32>,
+    // The compare mode, 0.0 without a comparison, 1.0 for a checkerboard, 2.0 for a curtain and
+    // 3.0 for the difference, followed by the indices of the reference and the moving layer and
+    // the size of the checkerboard tiles.
+    comparison: vec4<f32>,
+    // The horizontal position of the curtain divider from 0.0 to 1.0 and the difference mapped
+    // to the ends of the difference colormap, the rest is padding.
+    compare_range: vec4<f32>,
+};
+
+@group(0) @binding(2)
+var<uniform> layers: Layers;
+// Row i holds the lookup table of layer i, the last row the colormap of differences.
+@group(0) @binding(3)
+var t_layer_colormaps: texture_2d<f32>;
+@group(0) @binding(4)
+var t_layer0: texture_3d<f32>;
+@group(0) @binding(5)
+var t_layer1: texture_3d<f32>;
+@group(0) @binding(6)
+var t_layer2: texture_3d<f32>;
+@group(0) @binding(7)
+var t_layer3: texture_3d<f32>;
+
+// Reads a voxel of a layer. Textures can not be indexed dynamically.
+fn load_voxel(layer: i32, voxel: vec3<i32>) -> f32 {
+    var value: f32;
+    switch layer {
+        case 0: {
+            value = textureLoad(t_layer0, voxel, 0).x;
+        }
+        case 1: {
+            value = textureLoad(t_layer1, voxel, 0).x;
+        }
+        case 2: {
+            value = textureLoad(t_layer2, voxel, 0).x;
+        }
+        default: {
+            value = textureLoad(t_layer3, voxel, 0).x;
+        }
+    }
+    return value;
+}
+
+// Samples a layer with trilinear interpolation, the voxel textures are not filterable on every
+// backend.
+fn sample_layer(layer: i32, position: vec3<f32>, dims: vec3<f32>) -> f32 {
+    let p = clamp(position, vec3<f32>(0.0), dims - 1.0);
+    let p0 = floor(p);
+    let t = p - p0;
+    let i0 = vec3<i32>(p0);
+    let i1 = min(i0 + 1, vec3<i32>(dims) - 1);
+    let c00 = mix(load_voxel(layer, i0), load_voxel(layer, vec3<i32>(i1.x, i0.y, i0.z)), t.x);
+    let c10 = mix(
+        load_voxel(layer, vec3<i32>(i0.x, i1.y, i0.z)),
+        load_voxel(layer, vec3<i32>(i1.x, i1.y, i0.z)),
+        t.x
+    );
+    let c01 = mix(
+        load_voxel(layer, vec3<i32>(i0.x, i0.y, i1.z)),
+        load_voxel(layer, vec3<i32>(i1.x, i0.y, i1.z)),
+        t.x
+    );
+    let c11 = mix(load_voxel(layer, vec3<i32>(i0.x, i1.y, i1.z)), load_voxel(layer, i1), t.x);
+    return mix(mix(c00, c10, t.y), mix(c01, c11, t.y), t.z);
+}
+
+// Returns the color of a layer at a world position with the opacity of the layer as alpha.
+// Positions outside of the layer are transparent.
+fn layer_color(i: i32, world_position: vec3<f32>) -> vec4<f32> {
+    let layer = layers.layers[i];
+    let position = (layer.world_to_voxel * vec4<f32>(world_position, 1.0)).xyz;
+    let dims = layer.dims.xyz;
+    let inside = all(position >= vec3<f32>(-0.5)) && all(position <= dims - 0.5);
+    if !inside || layer.window.w < 0.5 {
+        return vec4<f32>(0.0);
+    }
+    var color: vec4<f32>;
+    if layer.options.y > 0.5 {
+        // Labels take the entry of the closest voxel.
+        let voxel = min(vec3<i32>(floor(position + 0.5)), vec3<i32>(dims) - 1);
+        let entry = i32(load_voxel(i, voxel));
+        color = textureLoad(t_layer_colormaps, vec2<i32>(clamp(entry, 0, 255), i), 0);
+    } else {
+        let value = sample_layer(i, position, dims);
+        let magnitude = select(value, abs(value), layer.options.z > 0.5);
+        let range = max(layer.window.y - layer.window.x, 1.0e-6);
+        let t = clamp((value - layer.window.x) / range, 0.0, 1.0);
+        color = textureLoad(t_layer_colormaps, vec2<i32>(i32(round(t * 255.0)), i), 0);
+        color.a = select(0.0, color.a, magnitude >= layer.window.z);
+    }
+    return vec4<f32>(color.rgb, color.a * layer.dims.w);
+}
+
+// Returns the intensity of a layer at a world position, or nothing outside of the layer or if
+// it is hidden.
+fn layer_value(i: i32, world_position: vec3<f32>) -> vec2<f32> {
+    let layer = layers.layers[i];
+    let position = (layer.world_to_voxel * vec4<f32>(world_position, 1.0)).xyz;
+    let dims = layer.dims.xyz;
+    let inside = all(position >= vec3<f32>(-0.5)) && all(position <= dims - 0.5);
+    if !inside || layer.window.w < 0.5 {
+        return vec2<f32>(0.0);
+    }
+    return vec2<f32>(sample_layer(i, position, dims), 1.0);
+}
+
+// Returns the color of the compared layers at a world position with alpha like layer_color.
+//
+// The screen position from 0.0 at the left to 1.0 at the right places the curtain.
+fn compared_color(world_position: vec3<f32>, screen_x: f32) -> vec4<f32> {
+    let mode = layers.comparison.x;
+    let reference = i32(layers.comparison.y);
+    let moving = i32(layers.comparison.z);
+    if mode > 2.5 {
+        // The signed difference of the moving layer and the reference.
+        let a = layer_value(reference, world_position);
+        let b = layer_value(moving, world_position);
+        if a.y < 0.5 || b.y < 0.5 {
+            return vec4<f32>(0.0);
+        }
+        let t = clamp((b.x - a.x) / (2.0 * layers.compare_range.y) + 0.5, 0.0, 1.0);
+        let color = textureLoad(t_layer_colormaps, vec2<i32>(i32(round(t * 255.0)), 4), 0);
+        return vec4<f32>(color.rgb, color.a * layers.layers[reference].dims.w);
+    }
+    var show_moving: bool;
+    if mode > 1.5 {
+        show_moving = screen_x > layers.compare_range.x;
+    } else {
+        let tile = floor(world_position / max(layers.comparison.w, 1.0e-6));
+        show_moving = (i32(tile.x + tile.y + tile.z) & 1) == 1;
+    }
+    return layer_color(select(reference, moving, show_moving), world_position);
+}
+
+// Blends the layers from the bottom to the top over a color.
+//
+// Compared layers are drawn together at the position of the reference.
+fn blend_layers(base_color: vec4<f32>, world_position: vec3<f32>, screen_x: f32) -> vec4<f32> {
+    let comparing = layers.comparison.x > 0.5;
+    var color = base_color.rgb;
+    for (var i = 0; i < i32(layers.count.x); i += 1) {
+        if comparing && i == i32(layers.comparison.z) {
+            continue;
+        }
+        var layer: vec4<f32>;
+        if comparing && i == i32(layers.comparison.y) {
+            layer = compared_color(world_position, screen_x);
+        } else {
+            layer = layer_color(i, world_position);
+        }
+        let mode = layers.layers[i].options.x;
+        if mode > 1.5 {
+            color = max(color, layer.rgb * layer.a);
+        } else if mode > 0.5 {
+            color = min(color + layer.rgb * layer.a, vec3<f32>(1.0));
+        } else {
+            color = mix(color, layer.rgb, layer.a);
+        }
+    }
+    return vec4<f32>(color, base_color.a);
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    // Both textures are sampled in uniform control flow, the overlay color is selected afterwards.
+    let texture_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let material_color = object.color * in.instance_color;
+    let base_color = select(material_color, texture_color * material_color, object.flags.x > 0.5);
+    let dual = overlay.options.y > 0.5;
+    // Vertices without data add neither to the scalar nor to its weight, so the scalar is only
+    // interpolated between the vertices with data.
+    let has_scalar = in.scalar.y >= 0.5;
+    let scalar = in.scalar.x / max(in.scalar.y, 1.0e-6);
+    let magnitude = select(scalar, abs(scalar), overlay.options.x > 0.5 || dual);
+    let scalar_range = max(overlay.range.y - overlay.range.x, 1.0e-6);
+    let t = clamp((select(scalar, magnitude, dual) - overlay.range.x) / scalar_range, 0.0, 1.0);
+    let row = select(0.25, 0.75, dual && scalar < 0.0);
+    let overlay_color: vec4<f32> = textureSample(t_colormap, s_colormap, vec2<f32>(t, row));
+    let show_overlay = overlay.range.w > 0.5 && has_scalar && magnitude >= overlay.range.z;
+    var object_color = select(base_color, overlay_color, show_overlay);
+    // The volume layers are only read with textureLoad, which does not need uniform control flow.
+    if object.flags.z > 0.5 {
+        let screen_x = in.clip.x / in.clip.w * 0.5 + 0.5;
+        object_color = blend_layers(object_color, in.world_position, screen_x);
+    }
     
-    let ambient_color = light.color.xyz * light.color.a;
+    let ambient_color = light.color.xyz * light.material.x;
 
     let light_dir = normalize(light.position.xyz - in.world_position);
 
     let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-    let diffuse_color = light.color.xyz * diffuse_strength;
+    let diffuse_color = light.color.xyz * diffuse_strength * light.material.y;
 
     // This would be lighting modeled after the Phong model only.
     //let view_dir = normalize(camera.view_pos.xyz - in.world_position);
@@ -69,11 +325,14 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // Blinn-Phong lighting.
     let view_dir = normalize(in.camera_view_pos.xyz - in.world_position);
     let half_dir = normalize(view_dir + light_dir);
-    let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), 32.0);
+    let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), light.material.w);
 
-    let specular_color = light.color.xyz * specular_strength;
+    let specular_color = light.color.xyz * specular_strength * light.material.z;
 
     let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
 
-    return vec4<f32>(result, object_color.a);
+    // Unlit objects like gizmos show their plain color.
+    let color = select(object_color.xyz, result, object.flags.y > 0.5);
+
+    return vec4<f32>(color, object_color.a);
 }
\ No newline at end of file
@@ -1,23 +1,29 @@
-use glam::Vec3;
+use glam::{Mat4, Vec3};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 use wgpu::util::DeviceExt;
 use std::f32::consts::PI;
 
+use crate::data::lookup_table::LookupTable;
+use crate::extras::math::vector3::Vector3;
 use crate::render::{
-    camera::{CameraUniform, OrbitCamera},
+    camera::{Camera, CameraUniform, OrbitCamera},
+    error::RenderError,
     geometry::r#box::get_box_vertecies,
+    gizmo::{Gizmo, GIZMO_VIEWPORT_FRACTION},
+    layers::{Comparison, LayerId, LayerSettings, LayersUniform, VolumeLayer, MAX_LAYERS},
     light::LightUniform,
+    overlay::{
+        Colormap, OverlaySettings, OverlayUniform, COLORMAP_SIZE, NO_SCALAR, NO_SCALAR_LIMIT,
+    },
+    scene::{ObjectId, ObjectUniform, Scene, SceneObject},
+    settings::{Background, BackgroundUniform, RenderSettings},
     texture,
-    vertex::Vertex,
+    vertex::{Instance, Vertex},
 };
 
-/// The number of samples taken when using multisample anti-aliasing.
-/// Valid values are `1` (no MSAA) or `4`.
-#[cfg(feature = "msaa")]
+/// The number of samples taken when multisample anti-aliasing is enabled.
 const MSAA_SAMPLE_COUNT: u32 = 4;
-#[cfg(not(feature = "msaa"))]
-const MSAA_SAMPLE_COUNT: u32 = 1;
 
 const VERTICAL_FOV: f32 = PI / 2.0; // 90 degrees in radians
 
@@ -54,25 +60,634 @@ fn calculate_distance_for_entire_mesh(bounding_box: BoundingBox, vertical_fov: f
     distance * 2.0
 }
 
-fn calculate_bounding_box(vertices: &[Vertex]) -> BoundingBox {
-    let mut min = [f32::INFINITY; 3];
-    let mut max = [f32::NEG_INFINITY; 3];
+/// Creates a wgpu instance for all supported backends.
+fn create_instance() -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+    })
+}
+
+/// Requests an adapter that is able to present to the given surface.
+///
+/// Arguments:
+///
+/// * `instance`: The wgpu instance from which the adapter is requested.
+/// * `surface`: The surface the adapter has to be compatible with, if any.
+/// * `force_fallback_adapter`: Whether a software renderer should be requested.
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface>,
+    force_fallback_adapter: bool,
+) -> Option<wgpu::Adapter> {
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: surface,
+            force_fallback_adapter,
+        })
+        .await
+}
+
+/// The optional render paths that are supported by the adapter.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Debug, Clone, Copy)]
+pub struct RenderCapabilities {
+    /// Whether the color and depth formats support multisample anti-aliasing.
+    pub msaa: bool,
+
+    /// Whether indexed draws with `u32` indices are fully supported.
+    ///
+    /// This is taken from [wgpu::DownlevelFlags::FULL_DRAW_INDEX_UINT32], which WebGL2 always
+    /// reports. Browsers whose indexed draws misbehave anyway, like Safari, are not detected and
+    /// need [State::set_indexed] with `Some(false)`.
+    pub indexed: bool,
+
+    /// Whether the adapter is a software renderer such as DX12 WARP.
+    pub software: bool,
+}
+
+impl RenderCapabilities {
+    /// Detects the capabilities of an adapter.
+    ///
+    /// Arguments:
+    ///
+    /// * `adapter`: The adapter whose capabilities will be detected.
+    /// * `format`: The texture format of the surface that is rendered to.
+    fn detect(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> Self {
+        let software = adapter.get_info().device_type == wgpu::DeviceType::Cpu;
+        let msaa = adapter
+            .get_texture_format_features(format)
+            .flags
+            .sample_count_supported(MSAA_SAMPLE_COUNT)
+            && adapter
+                .get_texture_format_features(wgpu::TextureFormat::Depth32Float)
+                .flags
+                .sample_count_supported(MSAA_SAMPLE_COUNT);
+        let indexed = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::FULL_DRAW_INDEX_UINT32);
+
+        Self {
+            msaa,
+            indexed,
+            software,
+        }
+    }
+
+    /// Returns whether multisample anti-aliasing is used unless it is overridden.
+    ///
+    /// Software renderers such as DX12 WARP are known to crash when using MSAA, so it is only
+    /// used on them if it is enabled explicitly.
+    pub fn default_msaa(&self) -> bool {
+        self.msaa && !self.software
+    }
+
+    /// Returns whether indexed drawing is used unless it is overridden.
+    pub fn default_indexed(&self) -> bool {
+        self.indexed
+    }
+
+    /// Returns whether multisample anti-aliasing is used with an override.
+    ///
+    /// An explicit override wins over the default of software renderers, but MSAA is never used
+    /// if the formats do not support it.
+    ///
+    /// Arguments:
+    ///
+    /// * `enabled`: Whether MSAA should be used, or [None] for [RenderCapabilities::default_msaa].
+    pub fn msaa_enabled(&self, enabled: Option<bool>) -> bool {
+        enabled.unwrap_or(self.default_msaa()) && self.msaa
+    }
+
+    /// Returns whether indexed drawing is used with an override.
+    ///
+    /// Unlike MSAA, an explicit override always wins, as indexed draws also work without full
+    /// `u32` support as long as the meshes do not use the largest indices.
+    ///
+    /// Arguments:
+    ///
+    /// * `enabled`: Whether an index buffer should be used, or [None] for
+    ///   [RenderCapabilities::default_indexed].
+    pub fn indexed_enabled(&self, enabled: Option<bool>) -> bool {
+        enabled.unwrap_or(self.default_indexed())
+    }
+}
+
+/// Creates the pipeline used for rendering the scene.
+///
+/// Translucent objects are blended over the scene and do not write depth, so they never hide
+/// each other.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device for which the pipeline will be created.
+/// * `layout`: The layout of the bind groups used by the pipeline.
+/// * `shader`: The shader module containing the `vs_main` and `fs_main` entry points.
+/// * `format`: The texture format of the render target.
+/// * `sample_count`: The number of samples used for _MSAA_. Valid values are `1` or `4`.
+/// * `translucent`: Whether the pipeline draws translucent objects.
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    translucent: bool,
+) -> wgpu::RenderPipeline {
+    let blend = if translucent {
+        wgpu::BlendState::ALPHA_BLENDING
+    } else {
+        wgpu::BlendState {
+            color: wgpu::BlendComponent::REPLACE,
+            alpha: wgpu::BlendComponent::REPLACE,
+        }
+    };
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(if translucent {
+            "Translucent Render Pipeline"
+        } else {
+            "Render Pipeline"
+        }),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc(), Vertex::scalar_desc(), Instance::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: !translucent,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+/// Creates the pipeline that draws the background gradient behind the scene.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device for which the pipeline will be created.
+/// * `layout`: The layout of the bind group containing the background uniform.
+/// * `shader`: The shader module containing the `vs_main` and `fs_main` entry points.
+/// * `format`: The texture format of the render target.
+/// * `sample_count`: The number of samples used for _MSAA_. Valid values are `1` or `4`.
+fn create_background_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Background Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        // The background never occludes the scene.
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+/// Expands an indexed triangle list so that it can be drawn without an index buffer.
+///
+/// Arguments:
+///
+/// * `items`: The per-vertex items referenced by `indices`.
+/// * `indices`: The indices of the triangle list.
+fn expand_indices<T: Copy>(items: &[T], indices: &[u32]) -> Vec<T> {
+    indices.iter().map(|&i| items[i as usize]).collect()
+}
+
+/// The GPU buffers of a mesh.
+struct GeometryBuffers {
+    vertex_buffer: wgpu::Buffer,
+    scalar_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    /// The number of indices, or vertices if the mesh is drawn without indices.
+    num_indices: u32,
+    instance_buffer: wgpu::Buffer,
+    /// The number of times the mesh is drawn.
+    num_instances: u32,
+}
 
-    for vertex in vertices {
-        for i in 0..3 {
-            // Update min and max for each axis
-            min[i] = min[i].min(vertex.position[i]);
-            max[i] = max[i].max(vertex.position[i]);
+/// Uploads a mesh with its per-vertex scalars.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device on which the buffers will be created.
+/// * `vertices`: The vertices of the mesh.
+/// * `scalars`: One overlay scalar per vertex.
+/// * `indices`: The indices of the triangle list.
+/// * `instances`: The instances the mesh is drawn with.
+/// * `indexed`: If `false` the triangles are expanded so that they can be drawn without indices.
+fn create_geometry_buffers(
+    device: &wgpu::Device,
+    vertices: &[Vertex],
+    scalars: &[f32],
+    indices: &[u32],
+    instances: &[Instance],
+    indexed: bool,
+) -> GeometryBuffers {
+    let (vertices, scalars, num_indices) = if indexed {
+        (vertices.to_vec(), scalars.to_vec(), indices.len() as u32)
+    } else {
+        let expanded = expand_indices(vertices, indices);
+        let num_vertices = expanded.len() as u32;
+        (expanded, expand_indices(scalars, indices), num_vertices)
+    };
+    // Scalars without data get a weight of zero, see [Vertex::scalar_desc].
+    let scalars: Vec<[f32; 2]> = scalars
+        .iter()
+        .map(|&scalar| {
+            if scalar > NO_SCALAR_LIMIT {
+                [scalar, 1.0]
+            } else {
+                [0.0, 0.0]
+            }
+        })
+        .collect();
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+    let scalar_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Scalar Buffer"),
+        contents: bytemuck::cast_slice(&scalars),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Index Buffer"),
+        contents: bytemuck::cast_slice(indices),
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Instance Buffer"),
+        contents: bytemuck::cast_slice(instances),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+
+    GeometryBuffers {
+        vertex_buffer,
+        scalar_buffer,
+        index_buffer,
+        num_indices,
+        instance_buffer,
+        num_instances: instances.len() as u32,
+    }
+}
+
+/// Uploads the geometry of a scene object and marks it as uploaded.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device on which the buffers will be created.
+/// * `object`: The object whose vertices, indices, scalars and instances are uploaded.
+/// * `indexed`: If `false` the triangles are expanded so that they can be drawn without indices.
+fn create_object_buffers(
+    device: &wgpu::Device,
+    object: &mut SceneObject,
+    indexed: bool,
+) -> GeometryBuffers {
+    object.dirty = false;
+    let no_scalars;
+    let scalars = match object.scalars() {
+        Some(scalars) => scalars,
+        None => {
+            no_scalars = vec![NO_SCALAR; object.vertices().len()];
+            &no_scalars
         }
+    };
+    // Objects without instances are drawn once at the origin.
+    let default_instance = [Instance::default()];
+    let instances = object.instances().unwrap_or(&default_instance);
+    create_geometry_buffers(
+        device,
+        object.vertices(),
+        scalars,
+        object.indices(),
+        instances,
+        indexed,
+    )
+}
+
+/// A volume layer together with its uploaded voxels.
+struct LayerEntry {
+    id: LayerId,
+    layer: VolumeLayer,
+    texture: texture::Texture,
+}
+
+/// Uploads the meshes of the orientation gizmo.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device on which the buffers will be created.
+/// * `gizmo`: The gizmo whose objects are uploaded.
+/// * `indexed`: If `false` the triangles are expanded so that they can be drawn without indices.
+fn create_gizmo_buffers(
+    device: &wgpu::Device,
+    gizmo: &mut Gizmo,
+    indexed: bool,
+) -> Vec<GeometryBuffers> {
+    gizmo
+        .objects
+        .iter_mut()
+        .map(|object| create_object_buffers(device, object, indexed))
+        .collect()
+}
+
+/// Returns the distance between the [ObjectUniform]s in the object buffer.
+///
+/// Arguments:
+///
+/// * `device`: The device whose alignment of dynamic uniform offsets is used.
+fn object_uniform_stride(device: &wgpu::Device) -> u32 {
+    let alignment = device.limits().min_uniform_buffer_offset_alignment;
+    let size = std::mem::size_of::<ObjectUniform>() as u32;
+    size.div_ceil(alignment) * alignment
+}
+
+/// Creates the buffer holding the [ObjectUniform]s of all objects.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device on which the buffer will be created.
+/// * `stride`: The distance between the uniforms, see [object_uniform_stride].
+/// * `capacity`: The number of objects the buffer can hold.
+fn create_object_buffer(device: &wgpu::Device, stride: u32, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Object Buffer"),
+        size: stride as wgpu::BufferAddress * capacity as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Creates the bind group with the camera uniform and the dynamically offset object uniforms.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device for which the bind group will be created.
+/// * `layout`: The layout of the camera bind group.
+/// * `camera_buffer`: The buffer containing the [CameraUniform].
+/// * `object_buffer`: The buffer containing the [ObjectUniform]s.
+fn create_camera_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera_buffer: &wgpu::Buffer,
+    object_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: object_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ObjectUniform>() as u64),
+                }),
+            },
+        ],
+        label: Some("camera_bind_group"),
+    })
+}
+
+/// Creates the bind group with the overlay uniform and the lookup tables of its colormaps.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device for which the bind group will be created.
+/// * `queue`: The wgpu queue used to upload the lookup table.
+/// * `layout`: The layout of the overlay bind group.
+/// * `buffer`: The buffer containing the [OverlayUniform].
+/// * `overlay`: The settings whose colormaps are uploaded.
+fn create_overlay_bind_group(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+    overlay: &OverlaySettings,
+) -> wgpu::BindGroup {
+    // The second row holds the colormap of negative scalars.
+    let negative = overlay.negative_colormap.unwrap_or(overlay.colormap);
+    let colormap = texture::Texture::from_lookup_tables(
+        device,
+        queue,
+        &[
+            &overlay.colormap.lookup_table(),
+            &negative.lookup_table(),
+        ],
+        "colormap_texture",
+    );
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&colormap.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&colormap.sampler),
+            },
+        ],
+        label: Some("overlay_bind_group"),
+    })
+}
+
+/// Creates the layout of the bind group with the diffuse texture and the volume layers.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device for which the layout will be created.
+fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let texture_entry = |binding, view_dimension, filterable| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type: wgpu::TextureSampleType::Float { filterable },
+        },
+        count: None,
+    };
+    let mut entries = vec![
+        texture_entry(0, wgpu::TextureViewDimension::D2, true),
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+        texture_entry(3, wgpu::TextureViewDimension::D2, true),
+    ];
+    // Every layer has its own binding, WebGL does not support arrays of textures.
+    for i in 0..MAX_LAYERS as u32 {
+        entries.push(texture_entry(4 + i, wgpu::TextureViewDimension::D3, false));
     }
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &entries,
+        label: Some("texture_bind_group_layout"),
+    })
+}
+
+/// Creates the bind group with the diffuse texture, the uniform and voxels of the volume layers
+/// and the lookup tables of their colormaps.
+///
+/// Arguments:
+///
+/// * `device`: The wgpu device for which the bind group will be created.
+/// * `queue`: The wgpu queue used to upload the lookup tables.
+/// * `layout`: The layout of the texture bind group.
+/// * `diffuse_texture`: The texture of textured materials.
+/// * `buffer`: The buffer containing the [LayersUniform].
+/// * `layers`: The volume layers from the bottom to the top.
+/// * `comparison`: The comparison of two layers, whose difference colormap is uploaded.
+/// * `empty_volume`: The texture bound in place of missing layers.
+#[allow(clippy::too_many_arguments)]
+fn create_texture_bind_group(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    diffuse_texture: &texture::Texture,
+    buffer: &wgpu::Buffer,
+    layers: &[LayerEntry],
+    comparison: Option<&Comparison>,
+    empty_volume: &texture::Texture,
+) -> wgpu::BindGroup {
+    // Row i holds the lookup table of layer i, the last row the colormap of differences.
+    let mut tables: Vec<Vec<[u8; 4]>> = (0..MAX_LAYERS)
+        .map(|i| match layers.get(i) {
+            Some(entry) => entry.layer.lookup_table(),
+            None => vec![[0; 4]; COLORMAP_SIZE],
+        })
+        .collect();
+    let difference_colormap = comparison.map_or(Colormap::BlueRed, |comparison| {
+        comparison.settings.difference_colormap
+    });
+    tables.push(difference_colormap.lookup_table());
+    let tables: Vec<&[[u8; 4]]> = tables.iter().map(|table| table.as_slice()).collect();
+    let colormaps =
+        texture::Texture::from_lookup_tables(device, queue, &tables, "layer_colormap_texture");
 
-    (min, max)
+    let mut entries = vec![
+        wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+        },
+        wgpu::BindGroupEntry {
+            binding: 1,
+            resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+        },
+        wgpu::BindGroupEntry {
+            binding: 2,
+            resource: buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 3,
+            resource: wgpu::BindingResource::TextureView(&colormaps.view),
+        },
+    ];
+    for i in 0..MAX_LAYERS {
+        let volume = layers.get(i).map_or(empty_volume, |entry| &entry.texture);
+        entries.push(wgpu::BindGroupEntry {
+            binding: 4 + i as u32,
+            resource: wgpu::BindingResource::TextureView(&volume.view),
+        });
+    }
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &entries,
+        label: Some("diffuse_bind_group"),
+    })
 }
 
+/// The texture format used for offscreen rendering.
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
 /// The state holds all data about the rendering cycle and the objects that are drawn to the screen.
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub struct State {
-    surface: wgpu::Surface,
+    /// The surface that is presented to. If [None] the state renders offscreen.
+    surface: Option<wgpu::Surface>,
+    /// The render target used instead of the surface when rendering offscreen.
+    offscreen_texture: Option<wgpu::Texture>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
@@ -83,26 +698,66 @@ pub struct State {
     /// The height of the wgpu renderer in pixels.
     pub height: u32,
 
+    capabilities: RenderCapabilities,
+    sample_count: u32,
+    indexed: bool,
+
+    shader: wgpu::ShaderModule,
+    render_pipeline_layout: wgpu::PipelineLayout,
     render_pipeline: wgpu::RenderPipeline,
+    translucent_pipeline: wgpu::RenderPipeline,
+    background_shader: wgpu::ShaderModule,
+    background_pipeline_layout: wgpu::PipelineLayout,
+    background_pipeline: wgpu::RenderPipeline,
+    background_buffer: wgpu::Buffer,
+    background_bind_group: wgpu::BindGroup,
     depth_texture_view: wgpu::TextureView,
     multisampled_framebuffer: wgpu::TextureView,
-    vertex_buffer: wgpu::Buffer,
-    #[allow(dead_code)] // Ideally we will switch to indexed meshes once supported everywhere.
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
-    #[allow(dead_code)]
+    /// The objects of the scene in drawing order with their uploaded geometry.
+    objects: Scene<GeometryBuffers>,
+    /// The object whose geometry is replaced by [State::set_mesh].
+    mesh_object: ObjectId,
+    object_buffer: wgpu::Buffer,
+    object_uniform_stride: u32,
+    /// The number of objects the object buffer can hold.
+    object_capacity: usize,
     diffuse_texture: texture::Texture,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
     diffuse_bind_group: wgpu::BindGroup,
+    /// The volume layers from the bottom to the top.
+    layers: Vec<LayerEntry>,
+    next_layer_id: u32,
+    /// The two layers that are compared, if any.
+    comparison: Option<Comparison>,
+    layers_buffer: wgpu::Buffer,
+    /// The texture bound in place of missing layers.
+    empty_volume_texture: texture::Texture,
 
     /// The camera used for rendering the scene.
     pub camera: OrbitCamera,
 
+    settings: RenderSettings,
+
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
     camera_bind_group: wgpu::BindGroup,
     light_uniform: LightUniform,
     light_buffer: wgpu::Buffer,
     light_bind_group: wgpu::BindGroup,
+
+    overlay: OverlaySettings,
+    overlay_buffer: wgpu::Buffer,
+    overlay_bind_group_layout: wgpu::BindGroupLayout,
+    overlay_bind_group: wgpu::BindGroup,
+
+    /// The transform from scene coordinates to RAS+ world coordinates that labels the gizmo.
+    orientation: Mat4,
+    gizmo: Gizmo,
+    gizmo_buffers: Vec<GeometryBuffers>,
+    gizmo_camera_buffer: wgpu::Buffer,
+    gizmo_object_buffer: wgpu::Buffer,
+    gizmo_bind_group: wgpu::BindGroup,
 }
 
 impl State {
@@ -115,31 +770,58 @@ impl State {
     /// * `height`: The height of the wgpu renderer in pixels.
     /// * `camera`: For now this only accepts an [OrbitCamera]. However in the future [State] should
     /// become generic and this should accept any struct that implements [super::camera::Camera].
-    pub async fn new<W>(window: &W, width: u32, height: u32, camera: OrbitCamera) -> Self
+    ///
+    /// If no hardware adapter is available a software fallback adapter is tried before a
+    /// [RenderError] is returned.
+    pub async fn new<W>(
+        window: &W,
+        width: u32,
+        height: u32,
+        camera: OrbitCamera,
+    ) -> Result<Self, RenderError>
     where
         W: raw_window_handle::HasRawWindowHandle + raw_window_handle::HasRawDisplayHandle,
     {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor { backends: wgpu::Backends::all(), dx12_shader_compiler: wgpu::Dx12Compiler::Fxc } );
-        let surface = unsafe { instance.create_surface(window).expect("Failed to create surface.") };
-
-        #[cfg(not(feature = "force_fallback"))]
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false, // If possible do not use a software renderer.
-            })
-            .await
-            .unwrap();
-        #[cfg(feature = "force_fallback")]
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: true,
-            })
-            .await
-            .unwrap();
+        let instance = create_instance();
+        let surface = unsafe { instance.create_surface(window)? };
+
+        Self::from_surface(instance, Some(surface), width, height, camera).await
+    }
+
+    /// Create a new application [State] that renders into a texture instead of a window.
+    ///
+    /// The rendered image can be read back with [State::capture].
+    ///
+    /// Arguments:
+    ///
+    /// * `width`: The width of the render target in pixels.
+    /// * `height`: The height of the render target in pixels.
+    /// * `camera`: The camera used for rendering the scene.
+    pub async fn new_offscreen(
+        width: u32,
+        height: u32,
+        camera: OrbitCamera,
+    ) -> Result<Self, RenderError> {
+        Self::from_surface(create_instance(), None, width, height, camera).await
+    }
+
+    /// Creates the [State] for an optional surface.
+    async fn from_surface(
+        instance: wgpu::Instance,
+        surface: Option<wgpu::Surface>,
+        width: u32,
+        height: u32,
+        camera: OrbitCamera,
+    ) -> Result<Self, RenderError> {
+        // If possible do not use a software renderer.
+        let mut adapter = None;
+        if !cfg!(feature = "force_fallback") {
+            adapter = request_adapter(&instance, surface.as_ref(), false).await;
+        }
+        if adapter.is_none() {
+            adapter = request_adapter(&instance, surface.as_ref(), true).await;
+        }
+        let adapter = adapter.ok_or(RenderError::NoAdapter)?;
 
         let (device, queue) = adapter
             .request_device(
@@ -151,69 +833,73 @@ impl State {
                 },
                 None, // Trace path
             )
-            .await
-            .unwrap();
+            .await?;
 
-        let capabilities = surface.get_capabilities(&adapter);
-        let formats = capabilities.formats;
+        let format = match &surface {
+            Some(surface) => *surface
+                .get_capabilities(&adapter)
+                .formats
+                .first()
+                .ok_or(RenderError::NoSurfaceFormat)?,
+            None => OFFSCREEN_FORMAT,
+        };
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: *formats.first().expect("No supported texture formats."),
+            format,
             width,
             height,
             present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: (&[]).to_vec(),
         };
-        surface.configure(&device, &config);
+        let offscreen_texture = match &surface {
+            Some(surface) => {
+                surface.configure(&device, &config);
+                None
+            }
+            None => Some(texture::Texture::create_render_target(
+                &device,
+                &config,
+                "offscreen_texture",
+            )),
+        };
+
+        let capabilities = RenderCapabilities::detect(&adapter, config.format);
+        let sample_count = if capabilities.msaa_enabled(None) {
+            MSAA_SAMPLE_COUNT
+        } else {
+            1
+        };
+        let indexed = capabilities.indexed_enabled(None);
 
         let diffuse_bytes = include_bytes!("texture.png");
         let diffuse_texture =
-            texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "texture.png").unwrap();
-
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            });
+            texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "texture.png")?;
 
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
+        let texture_bind_group_layout = create_texture_bind_group_layout(&device);
+        let layers_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Layers Buffer"),
+            contents: bytemuck::cast_slice(&[LayersUniform::new(std::iter::empty(), None)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let empty_volume_texture =
+            texture::Texture::from_volume(&device, &queue, [1, 1, 1], &[0.0], "empty_volume");
+        let diffuse_bind_group = create_texture_bind_group(
+            &device,
+            &queue,
+            &texture_bind_group_layout,
+            &diffuse_texture,
+            &layers_buffer,
+            &[],
+            None,
+            &empty_volume_texture,
+        );
 
         let depth_texture_view = texture::Texture::create_depth_texture(
             &device,
             &config,
-            MSAA_SAMPLE_COUNT,
+            sample_count,
             "depth_texture",
         );
 
@@ -226,34 +912,53 @@ impl State {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        // The objects share the bind group of the camera, each one is selected with a dynamic offset.
+        let object_uniform_stride = object_uniform_stride(&device);
+        let object_capacity = 1;
+        let object_buffer = create_object_buffer(&device, object_uniform_stride, object_capacity);
+
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<ObjectUniform>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                ],
                 label: Some("camera_bind_group_layout"),
             });
 
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
-            label: Some("camera_bind_group"),
-        });
+        let camera_bind_group = create_camera_bind_group(
+            &device,
+            &camera_bind_group_layout,
+            &camera_buffer,
+            &object_buffer,
+        );
 
-        let light_uniform = LightUniform {
-            position: [2.0, 6.0, 4.0, 1.0],
-            color: [1.0, 1.0, 1.0, 0.1],
-        };
+        let settings = RenderSettings::default();
+        let view_position = camera_uniform.view_position;
+        let light_uniform = LightUniform::from_settings(
+            &settings,
+            [view_position[0], view_position[1], view_position[2]],
+        );
 
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Light VB"),
@@ -285,6 +990,54 @@ impl State {
             label: None,
         });
 
+        let overlay = OverlaySettings::default();
+        let overlay_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Buffer"),
+            contents: bytemuck::cast_slice(&[OverlayUniform::from(&overlay)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let overlay_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("overlay_bind_group_layout"),
+            });
+
+        let overlay_bind_group = create_overlay_bind_group(
+            &device,
+            &queue,
+            &overlay_bind_group_layout,
+            &overlay_buffer,
+            &overlay,
+        );
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
@@ -297,6 +1050,7 @@ impl State {
                     &texture_bind_group_layout,
                     &camera_bind_group_layout,
                     &light_bind_group_layout,
+                    &overlay_bind_group_layout,
                 ],
                 push_constant_ranges: &[],
             });
@@ -304,56 +1058,78 @@ impl State {
         let multisampled_framebuffer = texture::Texture::create_multisampled_framebuffer(
             &device,
             &config,
-            MSAA_SAMPLE_COUNT,
+            sample_count,
             "multisampled_framebuffer",
         );
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent::REPLACE,
-                        alpha: wgpu::BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: MSAA_SAMPLE_COUNT,
-                ..Default::default()
-            },
-            multiview: None,
+        let render_pipeline = create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+            false,
+        );
+        let translucent_pipeline = create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+            true,
+        );
+
+        let background_uniform = BackgroundUniform::from(&settings.background);
+        let background_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Background Buffer"),
+            contents: bytemuck::cast_slice(&[background_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let background_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("background_bind_group_layout"),
+            });
+
+        let background_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &background_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: background_buffer.as_entire_binding(),
+            }],
+            label: Some("background_bind_group"),
         });
 
+        let background_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Background Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("background.wgsl").into()),
+        });
+
+        let background_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Background Pipeline Layout"),
+                bind_group_layouts: &[&background_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let background_pipeline = create_background_pipeline(
+            &device,
+            &background_pipeline_layout,
+            &background_shader,
+            config.format,
+            sample_count,
+        );
+
         let (vertices, indices) = get_box_vertecies(
             0,
             Vec3::new(0.0, 0.0, 0.0),
@@ -361,63 +1137,85 @@ impl State {
             Vec3::new(0.0, 0.0, 0.0),
         );
 
-        /* let mut vertices = Vec::new();
-        let mut indices = Vec::new();
-        let mut indices_count: u32 = 0;
-        for x in -5..5 {
-            for y in -8..8 {
-                for z in -5..5 {
-                    let (mut vertices_temp, mut indices_temp) = get_box_vertecies(
-                        indices_count,
-                        Vec3::new(x as f32, y as f32, z as f32),
-                        Vec3::new(0.9, 0.9, 0.9),
-                        Vec3::new(0.0, 0.0, 0.0)
-                    );
-                    indices_count += 24;
-                    vertices.append(&mut vertices_temp);
-                    indices.append(&mut indices_temp);
-                }
-            }
-        } */
+        let mut object = SceneObject::new("box", vertices, indices);
+        object.material.textured = true;
+        let buffers = create_object_buffers(&device, &mut object, indexed);
+        let mut objects = Scene::new();
+        let mesh_object = objects.add(object, buffers);
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-        #[cfg(not(feature = "indexed"))]
-        let num_indices = vertices.len() as u32;
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        // The gizmo is drawn with its own camera and object uniforms into a corner.
+        let orientation = Mat4::IDENTITY;
+        let mut gizmo = Gizmo::new(orientation);
+        let gizmo_buffers = create_gizmo_buffers(&device, &mut gizmo, indexed);
+        let gizmo_camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Camera Buffer"),
+            contents: bytemuck::cast_slice(&[Gizmo::camera_uniform(&camera)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
-        #[cfg(feature = "indexed")]
-        let num_indices = indices.len() as u32;
+        let gizmo_object_buffer =
+            create_object_buffer(&device, object_uniform_stride, gizmo.objects.len());
+        let gizmo_bind_group = create_camera_bind_group(
+            &device,
+            &camera_bind_group_layout,
+            &gizmo_camera_buffer,
+            &gizmo_object_buffer,
+        );
 
-        Self {
+        Ok(Self {
             surface,
+            offscreen_texture,
             device,
             queue,
             config,
             width,
             height,
+            capabilities,
+            sample_count,
+            indexed,
+            shader,
+            render_pipeline_layout,
             render_pipeline,
+            translucent_pipeline,
+            background_shader,
+            background_pipeline_layout,
+            background_pipeline,
+            background_buffer,
+            background_bind_group,
             depth_texture_view,
             multisampled_framebuffer,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            objects,
+            mesh_object,
+            object_buffer,
+            object_uniform_stride,
+            object_capacity,
             diffuse_texture,
+            texture_bind_group_layout,
             diffuse_bind_group,
+            layers: Vec::new(),
+            next_layer_id: 0,
+            comparison: None,
+            layers_buffer,
+            empty_volume_texture,
             camera,
+            settings,
             camera_buffer,
+            camera_bind_group_layout,
             camera_bind_group,
             camera_uniform,
             light_uniform,
             light_buffer,
             light_bind_group,
-        }
+            overlay,
+            overlay_buffer,
+            overlay_bind_group_layout,
+            overlay_bind_group,
+            orientation,
+            gizmo,
+            gizmo_buffers,
+            gizmo_camera_buffer,
+            gizmo_object_buffer,
+            gizmo_bind_group,
+        })
     }
 
     /// Resizes the renderer and adjusts the camera aspect.
@@ -436,17 +1234,26 @@ impl State {
             self.depth_texture_view = texture::Texture::create_depth_texture(
                 &self.device,
                 &self.config,
-                MSAA_SAMPLE_COUNT,
+                self.sample_count,
                 "depth_texture",
             );
             self.multisampled_framebuffer = texture::Texture::create_multisampled_framebuffer(
                 &self.device,
                 &self.config,
-                MSAA_SAMPLE_COUNT,
+                self.sample_count,
                 "multisampled_framebuffer",
             );
 
-            self.surface.configure(&self.device, &self.config);
+            match &self.surface {
+                Some(surface) => surface.configure(&self.device, &self.config),
+                None => {
+                    self.offscreen_texture = Some(texture::Texture::create_render_target(
+                        &self.device,
+                        &self.config,
+                        "offscreen_texture",
+                    ))
+                }
+            }
             self.camera.aspect = self.config.width as f32 / self.config.height as f32;
         }
     }
@@ -460,13 +1267,12 @@ impl State {
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
 
-        // Update the light so that it is transformed with the camera
-        self.light_uniform.position = [
-            self.camera_uniform.view_position[0],
-            self.camera_uniform.view_position[1],
-            self.camera_uniform.view_position[2],
-            1.0,
-        ];
+        // Update the light so that a headlight is transformed with the camera
+        let view_position = self.camera_uniform.view_position;
+        self.light_uniform = LightUniform::from_settings(
+            &self.settings,
+            [view_position[0], view_position[1], view_position[2]],
+        );
         self.queue.write_buffer(
             &self.light_buffer,
             0,
@@ -474,47 +1280,614 @@ impl State {
         );
     }
 
-    pub fn update_box_size(&mut self, new_size: Vec3) {
-        let (vertices, indices) = get_box_vertecies(
-            0,
-            Vec3::new(0.0, 0.0, 0.0),
-            new_size,
-            Vec3::new(0.0, 0.0, 0.0),
-        );        
+    /// Returns the settings that control the appearance of the scene.
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
 
+    /// Replaces the settings that control the appearance of the scene.
+    ///
+    /// The lighting is applied with the next call to [State::update].
+    ///
+    /// Arguments:
+    ///
+    /// * `settings`: The new render settings.
+    pub fn set_settings(&mut self, settings: RenderSettings) {
+        self.settings = settings;
         self.queue.write_buffer(
-            &self.vertex_buffer,
+            &self.background_buffer,
             0,
-            bytemuck::cast_slice(&vertices),
+            bytemuck::cast_slice(&[BackgroundUniform::from(&settings.background)]),
+        );
+    }
+
+    /// Returns the optional render paths that are supported by the adapter.
+    pub fn capabilities(&self) -> RenderCapabilities {
+        self.capabilities
+    }
+
+    /// Returns whether multisample anti-aliasing is currently used.
+    pub fn msaa(&self) -> bool {
+        self.sample_count > 1
+    }
+
+    /// Overrides whether multisample anti-aliasing is used.
+    ///
+    /// MSAA can be enabled on software renderers, but it stays disabled if the color or depth
+    /// format does not support it.
+    ///
+    /// Arguments:
+    ///
+    /// * `enabled`: Whether MSAA should be used, or [None] to use it if the adapter supports it
+    ///   well, see [RenderCapabilities::default_msaa].
+    pub fn set_msaa(&mut self, enabled: Option<bool>) {
+        let sample_count = if self.capabilities.msaa_enabled(enabled) {
+            MSAA_SAMPLE_COUNT
+        } else {
+            1
+        };
+        if sample_count == self.sample_count {
+            return;
+        }
+
+        self.sample_count = sample_count;
+        self.render_pipeline = create_render_pipeline(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            self.config.format,
+            sample_count,
+            false,
+        );
+        self.translucent_pipeline = create_render_pipeline(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            self.config.format,
+            sample_count,
+            true,
         );
+        self.background_pipeline = create_background_pipeline(
+            &self.device,
+            &self.background_pipeline_layout,
+            &self.background_shader,
+            self.config.format,
+            sample_count,
+        );
+        self.depth_texture_view = texture::Texture::create_depth_texture(
+            &self.device,
+            &self.config,
+            sample_count,
+            "depth_texture",
+        );
+        self.multisampled_framebuffer = texture::Texture::create_multisampled_framebuffer(
+            &self.device,
+            &self.config,
+            sample_count,
+            "multisampled_framebuffer",
+        );
+    }
+
+    /// Returns whether the geometry is drawn with an index buffer.
+    pub fn indexed(&self) -> bool {
+        self.indexed
+    }
+
+    /// Overrides whether the geometry is drawn with an index buffer.
+    ///
+    /// Indexed drawing can be enabled on adapters without full `u32` index support, where it
+    /// works as long as the meshes do not use the largest indices. Disabling it is the only way
+    /// to avoid it on browsers whose indexed draws misbehave although WebGL2 reports full
+    /// support, like Safari, see [RenderCapabilities::indexed].
+    ///
+    /// Arguments:
+    ///
+    /// * `enabled`: Whether an index buffer should be used, or [None] to use one if the adapter
+    ///   fully supports it, see [RenderCapabilities::default_indexed].
+    pub fn set_indexed(&mut self, enabled: Option<bool>) {
+        let indexed = self.capabilities.indexed_enabled(enabled);
+        if indexed == self.indexed {
+            return;
+        }
+
+        self.indexed = indexed;
+        for entry in self.objects.entries_mut() {
+            entry.object.dirty = true;
+        }
+        self.upload_objects();
+        self.gizmo_buffers = create_gizmo_buffers(&self.device, &mut self.gizmo, indexed);
+    }
+
+    /// Returns the transform from scene coordinates to RAS+ world coordinates.
+    pub fn orientation(&self) -> Mat4 {
+        self.orientation
+    }
+
+    /// Sets the transform from scene coordinates to RAS+ world coordinates, which decides the
+    /// anatomical labels of the orientation gizmo.
+    ///
+    /// Meshes placed in world space like isosurfaces use the identity, which is the default.
+    ///
+    /// Arguments:
+    ///
+    /// * `orientation`: The transform, e.g. [crate::data::volume::Volume::box_transform] if
+    ///   the volume is shown as the box proxy.
+    pub fn set_orientation(&mut self, orientation: Mat4) {
+        self.orientation = orientation;
+        self.gizmo = Gizmo::new(orientation);
+        self.gizmo_buffers = create_gizmo_buffers(&self.device, &mut self.gizmo, self.indexed);
+    }
+
+    pub fn update_box_size(&mut self, new_size: Vec3) {
+        let (vertices, indices) = get_box_vertecies(
+            0,
+            Vec3::new(0.0, 0.0, 0.0),
+            new_size,
+            Vec3::new(0.0, 0.0, 0.0),
+        );
+        self.set_mesh(vertices, indices);
+    }
+
+    /// Replaces the geometry of the mesh object with an indexed triangle mesh and moves the camera
+    /// so that the whole mesh is visible.
+    ///
+    /// The overlay scalars and instances of the previous mesh are removed.
+    ///
+    /// Arguments:
+    ///
+    /// * `vertices`: The vertices of the mesh in world space.
+    /// * `indices`: The indices of the triangle list.
+    pub fn set_mesh(&mut self, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        let mesh_object = self.mesh_object;
+        let object = self.mesh_object_mut();
+        object.set_geometry(vertices, indices);
+        self.upload_objects();
+
+        // update the camera so we can see the mesh
+        self.fit_camera(&[mesh_object]);
+    }
+
+    /// Returns the vertices and indices of the mesh object.
+    pub fn mesh(&self) -> (&[Vertex], &[u32]) {
+        let object = self.mesh_object_ref();
+        (object.vertices(), object.indices())
+    }
+
+    /// Returns the overlay scalars of the mesh object.
+    pub fn scalars(&self) -> Option<&[f32]> {
+        self.mesh_object_ref().scalars()
+    }
+
+    /// Returns the colors of the mesh vertices as drawn by the overlay, e.g. for exporting the
+    /// mesh, or [None] if the mesh has no overlay scalars or the overlay is disabled.
+    pub fn mesh_colors(&self) -> Option<Vec<[u8; 4]>> {
+        let object = self.mesh_object_ref();
+        let scalars = object.scalars().filter(|_| self.overlay.enabled)?;
+        Some(self.overlay.vertex_colors(scalars, object.material.color))
+    }
+
+    /// Sets the per-vertex scalars of the mesh object that are colored by the overlay.
+    ///
+    /// Vertices whose scalar is [NO_SCALAR] keep their base color.
+    ///
+    /// Arguments:
+    ///
+    /// * `scalars`: One scalar per vertex of the mesh, or [None] to remove the overlay.
+    pub fn set_scalars(&mut self, scalars: Option<Vec<f32>>) -> Result<(), RenderError> {
+        self.mesh_object_mut().set_scalars(scalars)?;
+        self.upload_objects();
+        Ok(())
+    }
+
+    /// Returns the object whose geometry is replaced by [State::set_mesh].
+    ///
+    /// Initially this is a textured box.
+    pub fn mesh_object(&self) -> ObjectId {
+        self.mesh_object
+    }
+
+    /// Adds an object to the scene. Objects are drawn in the order they were added.
+    ///
+    /// Arguments:
+    ///
+    /// * `object`: The object to add.
+    pub fn add_object(&mut self, mut object: SceneObject) -> ObjectId {
+        let buffers = create_object_buffers(&self.device, &mut object, self.indexed);
+        let id = self.objects.add(object, buffers);
+
+        if self.objects.len() > self.object_capacity {
+            self.object_capacity = self.objects.len().next_power_of_two();
+            self.object_buffer = create_object_buffer(
+                &self.device,
+                self.object_uniform_stride,
+                self.object_capacity,
+            );
+            self.camera_bind_group = create_camera_bind_group(
+                &self.device,
+                &self.camera_bind_group_layout,
+                &self.camera_buffer,
+                &self.object_buffer,
+            );
+        }
+        id
+    }
+
+    /// Removes an object from the scene and returns it.
+    ///
+    /// The mesh object can not be removed, but it can be hidden.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The object to remove.
+    pub fn remove_object(&mut self, id: ObjectId) -> Option<SceneObject> {
+        if id == self.mesh_object {
+            return None;
+        }
+        self.objects.remove(id)
+    }
+
+    /// Returns an object of the scene.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The object to return.
+    pub fn object(&self, id: ObjectId) -> Option<&SceneObject> {
+        self.objects.get(id)
+    }
 
+    /// Returns an object of the scene for modification.
+    ///
+    /// Changes to the geometry are uploaded before the next frame is rendered.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The object to return.
+    pub fn object_mut(&mut self, id: ObjectId) -> Option<&mut SceneObject> {
+        self.objects.get_mut(id)
+    }
+
+    /// Returns all objects of the scene in drawing order.
+    pub fn objects(&self) -> impl Iterator<Item = (ObjectId, &SceneObject)> {
+        self.objects
+            .entries()
+            .map(|entry| (entry.id, &entry.object))
+    }
+
+    /// Returns the world position of the closest visible surface under a pixel, or [None] if no
+    /// object is hit, e.g. to look up the label of an atlas under the cursor.
+    ///
+    /// Arguments:
+    ///
+    /// * `x`, `y`: The pixel position from the top left corner of the surface.
+    pub fn pick(&self, x: f32, y: f32) -> Option<Vec3> {
+        // The ray through the pixel from the near to the far plane. The depth range of wgpu is
+        // `[0, 1]`.
+        let inverse_view_proj = self.camera.build_view_projection_matrix().inverse();
+        let ndc_x = x / self.width as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - y / self.height as f32 * 2.0;
+        let origin = inverse_view_proj.project_point3(Vec3::new(ndc_x, ndc_y, 0.0));
+        let far = inverse_view_proj.project_point3(Vec3::new(ndc_x, ndc_y, 1.0));
+        let direction = (far - origin).normalize();
+
+        self.objects
+            .intersect_ray(origin, direction)
+            .map(|t| origin + direction * t)
+    }
+
+    /// Moves the camera so that the given objects are visible.
+    ///
+    /// Arguments:
+    ///
+    /// * `ids`: The objects to show, or all visible objects if empty.
+    pub fn fit_camera(&mut self, ids: &[ObjectId]) {
+        if let Some((min, max)) = self.objects.bounding_box(ids) {
+            let center = (min + max) * 0.5;
+            self.camera.target = Vector3::from_vec3(center);
+            let distance =
+                calculate_distance_for_entire_mesh((min.to_array(), max.to_array()), VERTICAL_FOV);
+            self.camera.set_distance(distance);
+        }
+    }
+
+    fn mesh_object_ref(&self) -> &SceneObject {
+        self.object(self.mesh_object)
+            .expect("The mesh object is never removed.")
+    }
+
+    fn mesh_object_mut(&mut self) -> &mut SceneObject {
+        let id = self.mesh_object;
+        self.object_mut(id)
+            .expect("The mesh object is never removed.")
+    }
+
+    /// Returns the settings that control how the overlay scalars are colored.
+    pub fn overlay(&self) -> &OverlaySettings {
+        &self.overlay
+    }
+
+    /// Replaces the settings that control how the overlay scalars are colored.
+    ///
+    /// Arguments:
+    ///
+    /// * `overlay`: The new overlay settings.
+    pub fn set_overlay(&mut self, overlay: OverlaySettings) {
+        if overlay.colormap != self.overlay.colormap
+            || overlay.negative_colormap != self.overlay.negative_colormap
+        {
+            self.overlay_bind_group = create_overlay_bind_group(
+                &self.device,
+                &self.queue,
+                &self.overlay_bind_group_layout,
+                &self.overlay_buffer,
+                &overlay,
+            );
+        }
+        self.overlay = overlay;
         self.queue.write_buffer(
-            &self.index_buffer,
+            &self.overlay_buffer,
             0,
-            bytemuck::cast_slice(&indices),
+            bytemuck::cast_slice(&[OverlayUniform::from(&overlay)]),
         );
-        
-        #[cfg(not(feature = "indexed"))]
-        let num_indices = vertices.len() as u32;
-        
-        #[cfg(feature = "indexed")]
-        let num_indices = indices.len() as u32;
+    }
+
+    /// Adds a volume layer on top of the others and uploads its voxels.
+    ///
+    /// The layers color the objects whose material is `layered`, the bottom layer is usually
+    /// the anatomical underlay.
+    ///
+    /// Arguments:
+    ///
+    /// * `layer`: The volume layer to add.
+    pub fn add_layer(&mut self, layer: VolumeLayer) -> Result<LayerId, RenderError> {
+        if self.layers.len() >= MAX_LAYERS {
+            return Err(RenderError::LayerCount { max: MAX_LAYERS });
+        }
+        let max = self.device.limits().max_texture_dimension_3d as usize;
+        let dims = layer.volume.dims;
+        if dims.iter().any(|&n| n == 0 || n > max) {
+            return Err(RenderError::VolumeSize { dims, max });
+        }
+
+        let id = LayerId(self.next_layer_id);
+        self.next_layer_id += 1;
+        let texture = texture::Texture::from_volume(
+            &self.device,
+            &self.queue,
+            dims,
+            &layer.voxels(),
+            "layer_texture",
+        );
+        self.layers.push(LayerEntry { id, layer, texture });
+        self.update_layers();
+        Ok(id)
+    }
+
+    /// Removes a volume layer and returns it.
+    ///
+    /// A comparison of the layer ends as well.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The layer to remove.
+    pub fn remove_layer(&mut self, id: LayerId) -> Option<VolumeLayer> {
+        let index = self.layers.iter().position(|entry| entry.id == id)?;
+        let entry = self.layers.remove(index);
+        self.comparison = self
+            .comparison
+            .filter(|comparison| comparison.reference != id && comparison.moving != id);
+        self.update_layers();
+        Some(entry.layer)
+    }
 
-        self.num_indices = num_indices;
+    /// Returns a volume layer.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The layer to return.
+    pub fn layer(&self, id: LayerId) -> Option<&VolumeLayer> {
+        self.layers
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| &entry.layer)
+    }
 
-        // update the camera so we can see the box
-        let bounding_box = calculate_bounding_box(&vertices);
-        let distance = calculate_distance_for_entire_mesh(bounding_box, VERTICAL_FOV);
-        self.camera.set_distance(distance);
-        
+    /// Returns all volume layers from the bottom to the top.
+    pub fn layers(&self) -> impl Iterator<Item = (LayerId, &VolumeLayer)> {
+        self.layers.iter().map(|entry| (entry.id, &entry.layer))
+    }
+
+    /// Moves a volume layer to another position in the stack. Returns `false` if there is no
+    /// such layer.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The layer to move.
+    /// * `index`: The new position, `0` for the bottom. It is clamped to the number of layers.
+    pub fn move_layer(&mut self, id: LayerId, index: usize) -> bool {
+        let Some(current) = self.layers.iter().position(|entry| entry.id == id) else {
+            return false;
+        };
+        let entry = self.layers.remove(current);
+        self.layers.insert(index.min(self.layers.len()), entry);
+        self.update_layers();
+        true
+    }
+
+    /// Replaces the colormap, window, threshold, opacity, visibility and blend mode of a volume
+    /// layer. Returns `false` if there is no such layer.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The layer to change.
+    /// * `settings`: The new settings of the layer.
+    pub fn set_layer_settings(&mut self, id: LayerId, settings: LayerSettings) -> bool {
+        let Some(entry) = self.layers.iter_mut().find(|entry| entry.id == id) else {
+            return false;
+        };
+        entry.layer.settings = settings;
+        self.update_layers();
+        true
+    }
+
+    /// Replaces the lookup table of a label map layer, e.g. after labels were hidden. Returns
+    /// `false` if there is no such label map layer.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The layer to change.
+    /// * `table`: The new lookup table of the label map.
+    pub fn set_layer_table(&mut self, id: LayerId, table: LookupTable) -> bool {
+        let Some(entry) = self
+            .layers
+            .iter_mut()
+            .find(|entry| entry.id == id && entry.layer.is_label_map())
+        else {
+            return false;
+        };
+        entry.layer.table = Some(table);
+        self.update_layers();
+        true
+    }
+
+    /// Displays another frame of a time series layer. Returns `false` if there is no such layer.
+    ///
+    /// Only the voxels of the frame are written into the existing 3D texture, the geometry of
+    /// the objects it colors is kept.
+    ///
+    /// Arguments:
+    ///
+    /// * `id`: The layer to change.
+    /// * `frame`: The index of the frame, which is clamped to the number of frames.
+    pub fn set_layer_frame(&mut self, id: LayerId, frame: usize) -> bool {
+        let Some(entry) = self.layers.iter_mut().find(|entry| entry.id == id) else {
+            return false;
+        };
+        if entry.layer.volume.frame() != frame {
+            entry.layer.volume.set_frame(frame);
+            let dims = entry.layer.volume.dims;
+            entry
+                .texture
+                .write_volume(&self.queue, dims, &entry.layer.voxels());
+        }
+        true
+    }
+
+    /// Returns the comparison of two volume layers, if any.
+    pub fn comparison(&self) -> Option<&Comparison> {
+        self.comparison.as_ref()
+    }
+
+    /// Compares two volume layers, e.g. with a checkerboard, a curtain or their difference, or
+    /// ends the comparison with [None].
+    ///
+    /// The layers are drawn together at the position of the reference in the stack with its
+    /// blend mode. Returns `false` and keeps the current comparison if the layers are missing
+    /// or the same.
+    ///
+    /// Arguments:
+    ///
+    /// * `comparison`: The layers and how they are compared.
+    pub fn set_comparison(&mut self, comparison: Option<Comparison>) -> bool {
+        if let Some(comparison) = &comparison {
+            let valid = comparison.reference != comparison.moving
+                && self.layer(comparison.reference).is_some()
+                && self.layer(comparison.moving).is_some();
+            if !valid {
+                return false;
+            }
+        }
+        self.comparison = comparison;
+        self.update_layers();
+        true
+    }
+
+    /// Writes the uniform of the volume layers and recreates their bind group with the lookup
+    /// tables of their colormaps.
+    fn update_layers(&mut self) {
+        let index = |id: LayerId| self.layers.iter().position(|entry| entry.id == id);
+        let comparison = self.comparison.as_ref().and_then(|comparison| {
+            Some((
+                index(comparison.reference)?,
+                index(comparison.moving)?,
+                &comparison.settings,
+            ))
+        });
+        self.queue.write_buffer(
+            &self.layers_buffer,
+            0,
+            bytemuck::cast_slice(&[LayersUniform::new(
+                self.layers.iter().map(|entry| &entry.layer),
+                comparison,
+            )]),
+        );
+        self.diffuse_bind_group = create_texture_bind_group(
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+            &self.diffuse_texture,
+            &self.layers_buffer,
+            &self.layers,
+            self.comparison.as_ref(),
+            &self.empty_volume_texture,
+        );
+    }
+
+    /// Uploads the geometry of all objects that changed since they were last uploaded.
+    fn upload_objects(&mut self) {
+        for entry in self
+            .objects
+            .entries_mut()
+            .filter(|entry| entry.object.dirty)
+        {
+            entry.data = create_object_buffers(&self.device, &mut entry.object, self.indexed);
+        }
+    }
+
+    /// Writes the transform and material of every object into the object buffer.
+    fn write_object_uniforms(&self) {
+        for (i, entry) in self.objects.entries().enumerate() {
+            self.queue.write_buffer(
+                &self.object_buffer,
+                i as wgpu::BufferAddress * self.object_uniform_stride as wgpu::BufferAddress,
+                bytemuck::cast_slice(&[ObjectUniform::from(&entry.object)]),
+            );
+        }
+    }
+
+    /// Turns the gizmo towards the camera and writes its camera and object uniforms.
+    fn write_gizmo_uniforms(&mut self) {
+        self.gizmo.face_camera(&self.camera);
+        self.queue.write_buffer(
+            &self.gizmo_camera_buffer,
+            0,
+            bytemuck::cast_slice(&[Gizmo::camera_uniform(&self.camera)]),
+        );
+        for (i, object) in self.gizmo.objects.iter().enumerate() {
+            self.queue.write_buffer(
+                &self.gizmo_object_buffer,
+                i as wgpu::BufferAddress * self.object_uniform_stride as wgpu::BufferAddress,
+                bytemuck::cast_slice(&[ObjectUniform::from(object)]),
+            );
+        }
     }
 
     /// Renders the scene based on the [State].
+    ///
+    /// When rendering offscreen the result stays in the internal render target.
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.upload_objects();
+        self.write_object_uniforms();
+        self.write_gizmo_uniforms();
+
+        let output = match &self.surface {
+            Some(surface) => Some(surface.get_current_texture()?),
+            None => None,
+        };
+        let view = match (&output, &self.offscreen_texture) {
+            (Some(output), _) => output.texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            (None, Some(texture)) => texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            (None, None) => unreachable!("An offscreen state always has a render target."),
+        };
 
         let mut encoder = self
             .device
@@ -522,17 +1895,190 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
-        // TODO: This should probably be stored in state instead.
-        let clear_color = wgpu::Color {
-            r: 0.1,
-            g: 0.2,
-            b: 0.3,
-            a: 1.0,
+        self.draw(
+            &mut encoder,
+            &view,
+            &self.multisampled_framebuffer,
+            &self.depth_texture_view,
+            (self.config.width, self.config.height),
+        );
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(output) = output {
+            output.present();
+        }
+
+        Ok(())
+    }
+
+    /// Renders the scene at an arbitrary resolution and reads the image back.
+    ///
+    /// This works both for window and offscreen states and does not affect the presented image.
+    ///
+    /// Arguments:
+    ///
+    /// * `width`: The width of the image in pixels.
+    /// * `height`: The height of the image in pixels.
+    pub fn capture(&mut self, width: u32, height: u32) -> Result<image::RgbaImage, RenderError> {
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max || height > max {
+            return Err(RenderError::CaptureSize { width, height, max });
+        }
+        // The pixels are read back with 4 bytes each.
+        let bgra = match self.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(RenderError::CaptureFormat(format)),
         };
 
-        let rpass_color_attachment = if MSAA_SAMPLE_COUNT == 1 {
+        self.upload_objects();
+        self.write_object_uniforms();
+        self.write_gizmo_uniforms();
+
+        let mut config = self.config.clone();
+        config.width = width;
+        config.height = height;
+        let target = texture::Texture::create_render_target(&self.device, &config, "capture_texture");
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture_view = texture::Texture::create_depth_texture(
+            &self.device,
+            &config,
+            self.sample_count,
+            "capture_depth_texture",
+        );
+        let multisampled_framebuffer = texture::Texture::create_multisampled_framebuffer(
+            &self.device,
+            &config,
+            self.sample_count,
+            "capture_multisampled_framebuffer",
+        );
+
+        // Render with the aspect ratio of the image instead of the one of the window.
+        let mut camera = self.camera;
+        camera.aspect = width as f32 / height as f32;
+        let mut camera_uniform = self.camera_uniform;
+        camera_uniform.update_view_proj(&camera);
+        self.queue
+            .write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+        // Rows of the copy have to be aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+        let unpadded_bytes_per_row = 4 * width;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            });
+        self.draw(
+            &mut encoder,
+            &view,
+            &multisampled_framebuffer,
+            &depth_texture_view,
+            (width, height),
+        );
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &target,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        // Restore the camera of the window.
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+
+        let slice = readback_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        // The callback is dropped without being called if the device is lost.
+        receiver
+            .recv()
+            .map_err(|_| RenderError::DeviceLost)?
+            .map_err(RenderError::BufferMap)?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        readback_buffer.unmap();
+
+        if bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or(RenderError::CaptureFormat(config.format))
+    }
+
+    /// Renders the scene at an arbitrary resolution and encodes it as PNG.
+    ///
+    /// Arguments:
+    ///
+    /// * `width`: The width of the image in pixels.
+    /// * `height`: The height of the image in pixels.
+    pub fn capture_png(&mut self, width: u32, height: u32) -> Result<Vec<u8>, RenderError> {
+        let image = self.capture(width, height)?;
+        let mut png = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .map_err(RenderError::EncodeImage)?;
+        Ok(png.into_inner())
+    }
+
+    /// Records the render pass drawing the scene.
+    ///
+    /// Arguments:
+    ///
+    /// * `encoder`: The encoder the render pass is recorded into.
+    /// * `view`: The view of the texture that receives the final image.
+    /// * `multisampled_framebuffer`: The framebuffer used if _MSAA_ is enabled.
+    /// * `depth_texture_view`: The depth buffer matching the size of `view`.
+    /// * `size`: The width and height of `view` in pixels.
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        multisampled_framebuffer: &wgpu::TextureView,
+        depth_texture_view: &wgpu::TextureView,
+        size: (u32, u32),
+    ) {
+        let clear_color = self.settings.clear_color();
+
+        let rpass_color_attachment = if self.sample_count == 1 {
             wgpu::RenderPassColorAttachment {
-                view: &view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(clear_color),
@@ -541,8 +2087,8 @@ impl State {
             }
         } else {
             wgpu::RenderPassColorAttachment {
-                view: &self.multisampled_framebuffer,
-                resolve_target: Some(&view),
+                view: multisampled_framebuffer,
+                resolve_target: Some(view),
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(clear_color),
                     store: true,
@@ -554,7 +2100,81 @@ impl State {
             label: Some("Render Pass"),
             color_attachments: &[Some(rpass_color_attachment)],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture_view,
+                view: depth_texture_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        if let Background::Gradient { .. } = self.settings.background {
+            render_pass.set_pipeline(&self.background_pipeline);
+            render_pass.set_bind_group(0, &self.background_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        let geometry = self.objects.entries().map(|entry| {
+            let translucent = entry.object.material.color[3] < 1.0;
+            (entry.object.visible, translucent, &entry.data)
+        });
+        self.draw_geometry(&mut render_pass, &self.camera_bind_group, geometry);
+        drop(render_pass);
+
+        if self.settings.orientation_gizmo {
+            self.draw_gizmo(
+                encoder,
+                view,
+                multisampled_framebuffer,
+                depth_texture_view,
+                size,
+            );
+        }
+    }
+
+    /// Records a second render pass drawing the orientation gizmo into the lower left corner.
+    ///
+    /// The depth buffer is cleared, so the gizmo is never hidden by the scene.
+    ///
+    /// Arguments:
+    ///
+    /// * `encoder`: The encoder the render pass is recorded into.
+    /// * `view`: The view of the texture that receives the final image.
+    /// * `multisampled_framebuffer`: The framebuffer used if _MSAA_ is enabled.
+    /// * `depth_texture_view`: The depth buffer matching the size of `view`.
+    /// * `size`: The width and height of `view` in pixels.
+    fn draw_gizmo(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        multisampled_framebuffer: &wgpu::TextureView,
+        depth_texture_view: &wgpu::TextureView,
+        (width, height): (u32, u32),
+    ) {
+        let ops = wgpu::Operations {
+            load: wgpu::LoadOp::Load,
+            store: true,
+        };
+        let rpass_color_attachment = if self.sample_count == 1 {
+            wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops,
+            }
+        } else {
+            wgpu::RenderPassColorAttachment {
+                view: multisampled_framebuffer,
+                resolve_target: Some(view),
+                ops,
+            }
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Gizmo Render Pass"),
+            color_attachments: &[Some(rpass_color_attachment)],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_texture_view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(1.0),
                     store: true,
@@ -563,22 +2183,130 @@ impl State {
             }),
         });
 
-        render_pass.set_pipeline(&self.render_pipeline);
+        let gizmo_size = (width.min(height) as f32 * GIZMO_VIEWPORT_FRACTION).floor();
+        render_pass.set_viewport(
+            0.0,
+            height as f32 - gizmo_size,
+            gizmo_size,
+            gizmo_size,
+            0.0,
+            1.0,
+        );
+        let geometry = self
+            .gizmo_buffers
+            .iter()
+            .map(|buffers| (true, false, buffers));
+        self.draw_geometry(&mut render_pass, &self.gizmo_bind_group, geometry);
+    }
+
+    /// Draws meshes whose object uniforms are stored in order behind the camera uniform.
+    ///
+    /// Translucent meshes are drawn after all opaque ones, in the order of the object uniforms.
+    ///
+    /// Arguments:
+    ///
+    /// * `render_pass`: The render pass the meshes are drawn in.
+    /// * `camera_bind_group`: The bind group with the camera and object uniforms.
+    /// * `geometry`: The visibility, translucency and buffers of each mesh in the order of the
+    ///   object uniforms.
+    fn draw_geometry<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        geometry: impl Iterator<Item = (bool, bool, &'a GeometryBuffers)>,
+    ) {
+        let geometry: Vec<_> = geometry
+            .enumerate()
+            .filter(|(_, (visible, _, buffers))| {
+                *visible && buffers.num_indices > 0 && buffers.num_instances > 0
+            })
+            .collect();
         render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
         render_pass.set_bind_group(2, &self.light_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        #[cfg(feature = "indexed")]
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        #[cfg(feature = "indexed")]
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-        #[cfg(not(feature = "indexed"))]
-        render_pass.draw(0..self.num_indices, 0..1);
-        drop(render_pass);
+        render_pass.set_bind_group(3, &self.overlay_bind_group, &[]);
+        for (pipeline, translucent) in [
+            (&self.render_pipeline, false),
+            (&self.translucent_pipeline, true),
+        ] {
+            render_pass.set_pipeline(pipeline);
+            let meshes = geometry.iter().filter(|(_, (_, t, _))| *t == translucent);
+            for &(i, (_, _, buffers)) in meshes {
+                self.draw_mesh(render_pass, camera_bind_group, i, buffers);
+            }
+        }
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+    /// Draws a mesh with the object uniform at an index.
+    ///
+    /// Arguments:
+    ///
+    /// * `render_pass`: The render pass the mesh is drawn in.
+    /// * `camera_bind_group`: The bind group with the camera and object uniforms.
+    /// * `i`: The index of the object uniform.
+    /// * `buffers`: The buffers of the mesh.
+    fn draw_mesh<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        i: usize,
+        buffers: &'a GeometryBuffers,
+    ) {
+        let offset = i as u32 * self.object_uniform_stride;
+        render_pass.set_bind_group(1, camera_bind_group, &[offset]);
+        render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, buffers.scalar_buffer.slice(..));
+        render_pass.set_vertex_buffer(2, buffers.instance_buffer.slice(..));
+        if self.indexed {
+            render_pass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..buffers.num_indices, 0, 0..buffers.num_instances);
+        } else {
+            render_pass.draw(0..buffers.num_indices, 0..buffers.num_instances);
+        }
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::RenderCapabilities;
+
+    /// Returns the capabilities of an adapter.
+    fn capabilities(msaa: bool, indexed: bool, software: bool) -> RenderCapabilities {
+        RenderCapabilities {
+            msaa,
+            indexed,
+            software,
+        }
+    }
+
+    #[test]
+    fn msaa_overrides() {
+        let hardware = capabilities(true, true, false);
+        assert!(hardware.msaa_enabled(None));
+        assert!(hardware.msaa_enabled(Some(true)));
+        assert!(!hardware.msaa_enabled(Some(false)));
+
+        // Software renderers only use MSAA if it is enabled explicitly.
+        let software = capabilities(true, true, true);
+        assert!(!software.msaa_enabled(None));
+        assert!(software.msaa_enabled(Some(true)));
+        assert!(!software.msaa_enabled(Some(false)));
+
+        // Formats without multisampling never use it.
+        let unsupported = capabilities(false, true, false);
+        assert!(!unsupported.msaa_enabled(None));
+        assert!(!unsupported.msaa_enabled(Some(true)));
+    }
+
+    #[test]
+    fn indexed_overrides() {
+        let full = capabilities(true, true, false);
+        assert!(full.indexed_enabled(None));
+        assert!(full.indexed_enabled(Some(true)));
+        assert!(!full.indexed_enabled(Some(false)));
+
+        let partial = capabilities(true, false, false);
+        assert!(!partial.indexed_enabled(None));
+        assert!(partial.indexed_enabled(Some(true)));
+        assert!(!partial.indexed_enabled(Some(false)));
     }
 }
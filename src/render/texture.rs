@@ -93,6 +93,110 @@ impl Texture {
         }
     }
 
+    /// Creates a lookup table texture with one row of sRGB colors per table, e.g. for colormaps.
+    ///
+    /// Arguments:
+    ///
+    /// * `device`: The wgpu device for which the texture will be generated.
+    /// * `queue`: The wgpu queue for which the texture will be generated.
+    /// * `tables`: The RGBA entries of the lookup tables, which all have the same length.
+    /// * `label`: The label of the new texture.
+    pub fn from_lookup_tables(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tables: &[&[[u8; 4]]],
+        label: &str,
+    ) -> Self {
+        let width = tables.first().map_or(0, |table| table.len());
+        let image =
+            image::RgbaImage::from_raw(width as u32, tables.len() as u32, tables.concat().concat())
+                .expect("The lookup tables match the image size.");
+        Self::from_image(device, queue, &image::DynamicImage::ImageRgba8(image), Some(label))
+    }
+
+    /// Creates a 3D texture of 32 bit floats from the voxels of a volume.
+    ///
+    /// The texture is not filterable on every backend, so the shader reads it with
+    /// `textureLoad` and interpolates itself.
+    ///
+    /// Arguments:
+    ///
+    /// * `device`: The wgpu device for which the texture will be generated.
+    /// * `queue`: The wgpu queue for which the texture will be generated.
+    /// * `dims`: The number of voxels along the x, y and z axes.
+    /// * `voxels`: The voxels, x varies fastest.
+    /// * `label`: The label of the new texture.
+    pub fn from_volume(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dims: [usize; 3],
+        voxels: &[f32],
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: dims[0] as u32,
+            height: dims[1] as u32,
+            depth_or_array_layers: dims[2] as u32,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let texture = Self {
+            texture,
+            view,
+            sampler,
+        };
+        texture.write_volume(queue, dims, voxels);
+        texture
+    }
+
+    /// Replaces the voxels of a 3D texture created by [Texture::from_volume], e.g. with another
+    /// frame of a time series. The texture and its bind groups are kept.
+    ///
+    /// Arguments:
+    ///
+    /// * `queue`: The wgpu queue of the device that created the texture.
+    /// * `dims`: The number of voxels along the x, y and z axes, which have to match the texture.
+    /// * `voxels`: The voxels, x varies fastest.
+    pub fn write_volume(&self, queue: &wgpu::Queue, dims: [usize; 3], voxels: &[f32]) {
+        let size = wgpu::Extent3d {
+            width: dims[0] as u32,
+            height: dims[1] as u32,
+            depth_or_array_layers: dims[2] as u32,
+        };
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(voxels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+    }
+
     /// Creates a new depth texture.
     ///
     /// Arguments:
@@ -161,4 +265,32 @@ impl Texture {
             .create_texture(multisampled_frame_descriptor)
             .create_view(&wgpu::TextureViewDescriptor::default())
     }
+
+    /// Creates a texture that can be rendered to and copied from, e.g. for offscreen rendering.
+    ///
+    /// Arguments:
+    ///
+    /// * `device`: The wgpu device for which the texture will be generated.
+    /// * `config`: The wgpu surface configuration providing the size and format of the texture.
+    /// * `label`: The label of the texture.
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
 }
@@ -32,4 +32,90 @@ impl Vertex {
             ],
         }
     }
+
+    /// Returns the layout of the second vertex buffer holding one scalar per vertex.
+    ///
+    /// The scalars are colored with a colormap, see [crate::render::overlay::OverlaySettings].
+    /// Each scalar is followed by its weight, `1.0` for vertices with data and `0.0` for
+    /// vertices without, so that both can be interpolated across triangles.
+    pub fn scalar_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 3,
+                format: wgpu::VertexFormat::Float32x2,
+            }],
+        }
+    }
+}
+
+/// An instance places a copy of a mesh with its own position, scale, rotation and color.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    /// The translation of the instance.
+    pub position: [f32; 3],
+
+    /// The scale of the instance along its local axes.
+    pub scale: [f32; 3],
+
+    /// The rotation of the instance as a quaternion `[x, y, z, w]`.
+    pub rotation: [f32; 4],
+
+    /// The RGBA color that is multiplied with the color of the material.
+    pub color: [f32; 4],
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            scale: [1.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            color: [1.0; 4],
+        }
+    }
+}
+
+impl Instance {
+    /// Returns the transform of the instance.
+    pub fn transform(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(
+            self.scale.into(),
+            glam::Quat::from_array(self.rotation),
+            self.position.into(),
+        )
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
 }